@@ -8,12 +8,19 @@ use std::{
 use anyhow::Result;
 use log::warn;
 use once_cell::sync::OnceCell;
-use tokio::sync::Mutex;
+use tokio::{
+    spawn,
+    sync::{Mutex, watch},
+};
 
 use crate::{
     core::{
-        entities::{settings::Settings, yp_config::YPConfig},
-        utils::failure::Failure,
+        entities::{
+            broadcast_state::{BroadcastEvent, BroadcastState},
+            settings::{GeneralSettings, Settings},
+            yp_config::YPConfig,
+        },
+        utils::{broadcast_state_machine::BroadcastStateMachine, failure::Failure},
     },
     features::{
         bbs::BbsListenerContainer,
@@ -25,10 +32,17 @@ use crate::{
         },
         hidden_features::{external_channels::ExternalChannels, stream_redirect::StreamRedirect},
         logger::LoggerController,
-        peercast::broadcasting::Broadcasting,
+        peercast::{
+            broadcast_preview::BroadcastPreview,
+            broadcasting::Broadcasting,
+            peercast_backend::{PeerCastBackendFactory, default_backend_factory},
+            peercast_endpoint::PeerCastEndpoint,
+            port_discovery::{DiscoveredPorts, discover_ports},
+        },
         rtmp::rtmp_server::RtmpServer,
         terms_check::check_expired_terms,
         ui::Ui,
+        yp_index::{OwnChannel, YPDirectory, fetch_directories},
     },
 };
 
@@ -39,14 +53,39 @@ use super::{
     entities::settings::{ChannelContent, ChannelSettings, Hidden},
 };
 
-async fn listen_rtmp_if_need(
-    app: &App,
-    app_rtmp_listener_delegate: Weak<AppRtmpListenerDelegate>,
-) -> bool {
+async fn listen_rtmp_if_need(app: &App, app_rtmp_listener_delegate: Weak<AppRtmpListenerDelegate>) {
     let mut rtmp_server = app.rtmp_server.lock().await;
     rtmp_server.set_delegate(app_rtmp_listener_delegate);
     app.listen_rtmp_if_need(&mut rtmp_server, app.settings.lock().await.deref())
-        .await
+        .await;
+}
+
+/// 配信の状態が変わるたびに画面に反映し、PeerCast のチャンネルの有無に合わせてログを開始、終了する。
+/// App が破棄されると終わる
+async fn observe_broadcast_state(app: Weak<App>, mut receiver: watch::Receiver<BroadcastState>) {
+    let mut previous = *receiver.borrow_and_update();
+    while receiver.changed().await.is_ok() {
+        let state = *receiver.borrow_and_update();
+        let Some(app) = app.upgrade() else {
+            return;
+        };
+        app.ui.set_broadcast_state(state);
+        let res = match (previous.is_on_air(), state.is_on_air()) {
+            (false, true) => {
+                let settings = app.settings.lock().await;
+                let channels = app.broadcasting.lock().await.channels().clone();
+                app.logger_controller
+                    .on_broadcast(channels, &settings)
+                    .await
+            }
+            (true, false) => app.logger_controller.on_stop_channel().await,
+            _ => Ok(()),
+        };
+        if let Err(err) = res {
+            app.ui.notify_failure(&Failure::Warn(err.to_string()));
+        }
+        previous = state;
+    }
 }
 
 fn updated_value_with_history(history: Vec<String>, limit: usize) -> Vec<String> {
@@ -87,6 +126,7 @@ pub struct App {
     pub yp_configs: Vec<YPConfig>,
     pub settings: Mutex<Settings>,
     pub ui: Ui,
+    pub broadcast_state: BroadcastStateMachine,
     pub rtmp_server: Mutex<RtmpServer>,
     pub broadcasting: Mutex<Broadcasting>,
     pub bbs_listener_container: std::sync::Mutex<BbsListenerContainer>,
@@ -94,23 +134,27 @@ pub struct App {
     external_channels: Mutex<Option<ExternalChannels>>,
     _app_bbs_listener_delegate: OnceCell<Arc<AppBbsListenerDelegate>>,
     _app_rtmp_listener_delegate: OnceCell<Arc<AppRtmpListenerDelegate>>,
+    backend_factory: PeerCastBackendFactory,
 }
 
 impl App {
     async fn internal_new(app_dir: &Path, settings_path: &Path, resource_dir: &Path) -> Self {
+        let backend_factory = default_backend_factory();
         Self {
             yp_configs: read_yp_configs_and_show_dialog_if_error(app_dir, resource_dir).await,
             settings: Mutex::new(
                 load_settings_and_show_dialog_if_error(app_dir, settings_path).await,
             ),
             ui: Ui::new(),
+            broadcast_state: BroadcastStateMachine::new(),
             rtmp_server: Mutex::new(RtmpServer::new()),
-            broadcasting: Mutex::new(Broadcasting::new()),
+            broadcasting: Mutex::new(Broadcasting::new(backend_factory.clone())),
             bbs_listener_container: std::sync::Mutex::new(BbsListenerContainer::new()),
-            logger_controller: LoggerController::new(),
+            logger_controller: LoggerController::new(backend_factory.clone()),
             external_channels: Default::default(),
             _app_bbs_listener_delegate: OnceCell::new(),
             _app_rtmp_listener_delegate: OnceCell::new(),
+            backend_factory,
         }
     }
 
@@ -146,11 +190,7 @@ impl App {
         }
 
         let weak = Arc::downgrade(&app_rtmp_listener_delegate);
-        let initial_rtmp = if listen_rtmp_if_need(&zelf, weak).await {
-            "listening"
-        } else {
-            "idle"
-        };
+        listen_rtmp_if_need(&zelf, weak).await;
         zelf._app_rtmp_listener_delegate
             .set(app_rtmp_listener_delegate)
             .unwrap_or_else(|_| panic!());
@@ -160,10 +200,15 @@ impl App {
             if let Some(hidden) = &settings.other_settings.hidden
                 && let Some(stream_redirect_port) = hidden.stream_redirect_port
             {
+                let general_settings = &settings.general_settings;
+                let backend = (zelf.backend_factory)(
+                    general_settings.peer_cast_type,
+                    &PeerCastEndpoint::new(general_settings),
+                );
                 StreamRedirect::default()
                     .run(
                         stream_redirect_port,
-                        settings.general_settings.peer_cast_port,
+                        backend,
                         settings.general_settings.channel_name[0].clone(),
                     )
                     .await;
@@ -174,7 +219,11 @@ impl App {
             zelf.settings.lock().await.general_settings.channel_name[0].clone();
         let weak = Arc::downgrade(&app_ui_delegate);
         zelf.ui
-            .prepare_ui(initial_rtmp.to_owned(), initial_channel_name, weak);
+            .prepare_ui(zelf.broadcast_state.state(), initial_channel_name, weak);
+        spawn(observe_broadcast_state(
+            Arc::downgrade(&zelf),
+            zelf.broadcast_state.subscribe(),
+        ));
 
         zelf
     }
@@ -208,19 +257,98 @@ impl App {
             .await
         {
             Err(err) => {
-                let ui = &self.ui;
-                ui.notify_failure(&Failure::Error(err.to_string()));
-                ui.set_rtmp("idle".to_owned());
+                self.ui.notify_failure(&Failure::Error(err.to_string()));
+                self.broadcast_state.handle(BroadcastEvent::ListenFailed);
                 false
             }
             Ok(listening) => {
-                let status = if listening { "listening" } else { "idle" };
-                self.ui.set_rtmp(status.to_owned());
+                self.broadcast_state.handle(if listening {
+                    BroadcastEvent::Listen
+                } else {
+                    BroadcastEvent::Unlisten
+                });
                 listening
             }
         }
     }
 
+    /// 対応していない PeerCast や、配信に使えない設定の場合は警告する。
+    /// PeerCast に繋がらない場合は配信開始時に分かるため、ログに残すだけにする
+    pub async fn warn_if_unsupported_peercast(&self, settings: &Settings) {
+        let general_settings = &settings.general_settings;
+        let backend = (self.backend_factory)(
+            general_settings.peer_cast_type,
+            &PeerCastEndpoint::new(general_settings),
+        );
+        let capabilities = match backend.capabilities().await {
+            Ok(capabilities) => capabilities,
+            Err(err) => {
+                warn!("{:?}", err);
+                self.suggest_peercast_port(general_settings).await;
+                return;
+            }
+        };
+        log::info!("{:?}", capabilities);
+        if let Err(Failure::Warn(message) | Failure::Error(message) | Failure::Fatal(message)) =
+            capabilities.validate(
+                &settings.yellow_pages_settings,
+                general_settings.content_type,
+            )
+        {
+            self.ui.notify_failure(&Failure::Warn(message));
+        }
+    }
+
+    /// 設定されたポート番号で PeerCast が応答しない場合に、見つかったポート番号を知らせる
+    async fn suggest_peercast_port(&self, general_settings: &GeneralSettings) {
+        let discovered = self.discover_peercast_ports(general_settings).await;
+        if let Some(port) = discovered.peer_cast_port
+            && port != general_settings.peer_cast_port
+        {
+            self.ui.notify_failure(&Failure::Warn(format!(
+                "ポート番号 {} で PeerCast が応答しません。ポート番号 {} で PeerCast が見つかりました。",
+                general_settings.peer_cast_port, port
+            )));
+        }
+    }
+
+    pub async fn discover_peercast_ports(
+        &self,
+        general_settings: &GeneralSettings,
+    ) -> DiscoveredPorts {
+        discover_ports(&self.backend_factory, general_settings).await
+    }
+
+    /// 配信中はチャンネル ID で、配信していない場合はチャンネル名で自分のチャンネルを探す
+    pub async fn fetch_yp_directories(&self) -> Vec<YPDirectory> {
+        let settings = self.settings.lock().await.clone();
+        let channel_ids: Vec<_> = {
+            let broadcasting = self.broadcasting.lock().await;
+            broadcasting
+                .channel_ids()
+                .into_iter()
+                .map(|x| x.to_owned())
+                .collect()
+        };
+        let own_channel = OwnChannel {
+            ids: channel_ids.iter().map(|x| x.as_str()).collect(),
+            name: &settings.general_settings.channel_name[0],
+            genre: &settings.channel_settings.genre,
+        };
+        fetch_directories(
+            &self.yp_configs,
+            &settings.yellow_pages_settings,
+            &own_channel,
+        )
+        .await
+    }
+
+    pub async fn preview_broadcast(&self) -> BroadcastPreview {
+        let settings = self.settings.lock().await;
+        let broadcasting = self.broadcasting.lock().await;
+        broadcasting.preview(&self.yp_configs, &settings).await
+    }
+
     pub async fn update_channel(&self, broadcasting: &Broadcasting, settings: &Settings) {
         let res = broadcasting.update(&self.yp_configs, settings).await;
         if let Some(err) = res.err() {
@@ -241,6 +369,15 @@ impl App {
             updated_value_with_history(take(&mut settings.channel_settings.comment), 20);
         settings.channel_settings.contact_url =
             updated_value_with_history(take(&mut settings.channel_settings.contact_url), 5);
+        let channel_settings = &mut settings.channel_settings;
+        for track in [
+            &mut channel_settings.track_name,
+            &mut channel_settings.track_creator,
+            &mut channel_settings.track_album,
+            &mut channel_settings.track_url,
+        ] {
+            *track = updated_value_with_history(take(track), 20);
+        }
         ui.push_settings(settings);
     }
 
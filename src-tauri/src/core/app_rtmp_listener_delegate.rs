@@ -1,70 +1,744 @@
 use std::{
+    num::{NonZeroU16, NonZeroU32},
     path::PathBuf,
     sync::{Arc, Weak},
+    time::Duration,
 };
 
 use async_trait::async_trait;
-use tokio::net::TcpStream;
+use futures::future::join_all;
+use tokio::{
+    select, spawn,
+    sync::{Mutex, watch},
+    task::JoinHandle,
+    time::{interval, sleep},
+};
 
 use crate::{
-    core::utils::{
-        broadcast_events::{self, stop_broadcast},
-        tcp::{connect, pipe},
+    core::{
+        entities::{
+            broadcast_state::{BroadcastEvent, BroadcastState},
+            now_playing::NowPlaying,
+            settings::{PeerCastType, Settings},
+            stream_properties::StreamProperties,
+        },
+        utils::{
+            broadcast_events::{self, stop_broadcast},
+            failure::Failure,
+        },
+    },
+    features::{
+        hidden_features::jpnkn_bbs_auto_comment::JpnknBbsAutoComment,
+        peercast::peercast_endpoint::PeerCastEndpoint,
+        rtmp::{
+            RtmpListenerDelegate,
+            flv_http_publisher::FlvHttpListener,
+            flv_recorder::FlvRecorder,
+            publish_session::{PUBLISH_FAILED, PublishRequest},
+            rtmp_fan_out::RtmpFanOut,
+            rtmp_publisher::RtmpPublisher,
+            rtmp_relay::{RelayDestination, RelayEnd, relay},
+            spliced_destination::{Splicer, splice},
+            stream_analyzer::StreamAnalyzer,
+        },
+        yp_index::wait_for_listing,
     },
-    features::rtmp::RtmpListenerDelegate,
 };
 
 use super::app::App;
 
+const UI_BITRATE_INTERVAL: Duration = Duration::from_secs(5);
+const CHANNEL_BITRATE_INTERVAL: Duration = Duration::from_secs(60);
+const STREAM_STATS_INTERVAL: Duration = Duration::from_secs(10);
+const PEERCAST_STATS_INTERVAL: Duration = Duration::from_secs(30);
+const NOW_PLAYING_INTERVAL: Duration = Duration::from_secs(3);
+/// YP の index.txt は数十秒ごとに更新される
+const YP_LISTING_INTERVAL: Duration = Duration::from_secs(20);
+const YP_LISTING_TIMEOUT: Duration = Duration::from_secs(180);
+
+/// 前回通知した値から 10% 以上変化していれば通知する
+fn should_report_bitrate(reported: Option<NonZeroU32>, current: Option<NonZeroU32>) -> bool {
+    match (reported, current) {
+        (Some(reported), Some(current)) => {
+            reported.get().abs_diff(current.get()) * 10 >= reported.get()
+        }
+        (reported, current) => reported != current,
+    }
+}
+
+/// 受信ビットレートを UI と PeerCast に通知し続ける。返らない
+async fn report_bitrate(app: &App, analyzer: &std::sync::Mutex<StreamAnalyzer>) {
+    let mut interval = interval(UI_BITRATE_INTERVAL);
+    let mut reported: Option<NonZeroU32> = None;
+    let mut reported_at = None;
+    loop {
+        let now = interval.tick().await;
+        let kbps = analyzer.lock().unwrap().kbps();
+        app.ui.set_ingest_bitrate(Some(kbps));
+
+        let bitrate = NonZeroU32::new(kbps);
+        let expired = reported_at.is_none_or(|at| now - at >= CHANNEL_BITRATE_INTERVAL);
+        if !expired || !should_report_bitrate(reported, bitrate) {
+            continue;
+        }
+        let settings = app.settings.lock().await;
+        let mut broadcasting = app.broadcasting.lock().await;
+        if !broadcasting.is_broadcasting() {
+            continue;
+        }
+        broadcasting.set_bitrate(bitrate);
+        app.update_channel(&broadcasting, &settings).await;
+        reported = bitrate;
+        reported_at = Some(now);
+    }
+}
+
+/// キーフレーム間隔などをロガーに渡し、キーフレーム間隔が長すぎれば警告する。返らない
+async fn report_stream_stats(app: &App, analyzer: &std::sync::Mutex<StreamAnalyzer>) {
+    let mut interval = interval(STREAM_STATS_INTERVAL);
+    let mut warned = false;
+    loop {
+        interval.tick().await;
+        let stats = analyzer.lock().unwrap().stats();
+        app.logger_controller.set_stream_stats(stats);
+
+        let max_seconds = app
+            .settings
+            .lock()
+            .await
+            .general_settings
+            .max_keyframe_interval_seconds;
+        let Some(keyframe_interval) = stats.keyframe_interval else {
+            continue;
+        };
+        let exceeded = max_seconds != 0 && keyframe_interval > max_seconds as u32 * 1000;
+        if exceeded && !warned {
+            let message = format!(
+                "キーフレーム間隔が {:.1} 秒あります。配信ソフトのキーフレーム間隔を {} 秒以下に設定してください。",
+                keyframe_interval as f64 / 1000.0,
+                max_seconds
+            );
+            app.ui.notify_failure(&Failure::Warn(message));
+        }
+        warned = exceeded;
+    }
+}
+
+/// リスナー数や帯域を UI に送り続ける。返らない
+async fn report_peercast_stats(app: &App) {
+    let mut interval = interval(PEERCAST_STATS_INTERVAL);
+    loop {
+        interval.tick().await;
+        let result = {
+            let settings = app.settings.lock().await;
+            let endpoint = PeerCastEndpoint::new(&settings.general_settings);
+            let broadcasting = app.broadcasting.lock().await;
+            if !broadcasting.is_broadcasting() {
+                continue;
+            }
+            broadcasting.fetch_peercast_stats(&endpoint).await
+        };
+        match result {
+            Ok(stats) => app.ui.set_peercast_stats(Some(stats)),
+            // NOTE: 一時的な失敗で通知を出し続けないよう、ログにだけ残す
+            Err(err) => log::warn!("{:?}", err),
+        }
+    }
+}
+
+/// 0 の場合は監視しない
+fn channel_health_check_interval(settings: &Settings) -> Option<Duration> {
+    match settings.general_settings.channel_health_check_seconds {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds as u64)),
+    }
+}
+
+/// 正常でないチャンネルを作り直す。配信元から直接受け取るチャンネルを作り直した場合は true を返す。
+/// broken は PeerCast への送信に失敗した場合で、1 回の確認で作り直す
+async fn recover_channels(
+    app: &App,
+    rtmp_conn_port: NonZeroU16,
+    suspected: &mut Vec<String>,
+    check_failed: &mut bool,
+    broken: bool,
+) -> Result<bool, Failure> {
+    let settings = app.settings.lock().await;
+    let endpoint = PeerCastEndpoint::new(&settings.general_settings);
+    let mut broadcasting = app.broadcasting.lock().await;
+    if !broadcasting.is_broadcasting() {
+        return Ok(false);
+    }
+    let unhealthy = match broadcasting.check_health(&endpoint).await {
+        Ok(unhealthy) => unhealthy,
+        Err(err) => {
+            log::warn!("{:?}", err);
+            if !*check_failed {
+                *check_failed = true;
+                let message = "PeerCast からチャンネルの状態を取得できませんでした。".to_owned();
+                app.ui.notify_failure(&Failure::Warn(message));
+            }
+            return Ok(false);
+        }
+    };
+    *check_failed = false;
+    // NOTE: チャンネルの作成直後などの一時的な状態で作り直さないよう、続けて 2 回異常だった場合に作り直す
+    let confirmed: Vec<_> = unhealthy
+        .iter()
+        .filter(|(host, _)| broken || suspected.contains(host))
+        .collect();
+    *suspected = unhealthy.iter().map(|(host, _)| host.clone()).collect();
+    if confirmed.is_empty() {
+        return Ok(false);
+    }
+    let description = confirmed
+        .iter()
+        .map(|(host, health)| format!("{}: {}", host, health))
+        .collect::<Vec<_>>()
+        .join(", ");
+    log::warn!("rebroadcast unhealthy channels ({})", description);
+    let hosts: Vec<_> = confirmed.iter().map(|(host, _)| host.clone()).collect();
+    let result = broadcasting
+        .rebroadcast(&app.yp_configs, &settings, rtmp_conn_port, &hosts)
+        .await;
+    // NOTE: 作り直しに失敗しても、一部のチャンネルは作り直されていることがある
+    app.logger_controller
+        .on_change_channels(broadcasting.channels().clone())
+        .await;
+    let resplice = result?;
+    suspected.clear();
+    log::info!("rebroadcast done");
+    let message = format!(
+        "PeerCast のチャンネルに異常があったため、チャンネルを作り直しました。({})",
+        description
+    );
+    app.ui.notify_failure(&Failure::Warn(message));
+    Ok(resplice)
+}
+
+/// 配信中のチャンネルを監視し、異常があれば作り直して中継をつなぎ直す。返らない
+async fn watch_channel_health(
+    app: &App,
+    input: &PeerCastInput,
+    splicer: &Splicer,
+    check_interval: Duration,
+) {
+    let mut interval = interval(check_interval);
+    // NOTE: 最初の tick はすぐに完了するので読み捨てる
+    interval.tick().await;
+    let mut suspected = Vec::new();
+    let mut check_failed = false;
+    loop {
+        // NOTE: PeerCast への送信に失敗した場合は、次の確認を待たずに作り直す
+        let broken = select! {
+            _ = interval.tick() => false,
+            err = splicer.wait_for_break() => {
+                log::warn!("failed to relay to PeerCast: {}", err);
+                interval.reset();
+                true
+            }
+        };
+        let resplice = match recover_channels(
+            app,
+            input.rtmp_conn_port,
+            &mut suspected,
+            &mut check_failed,
+            broken,
+        )
+        .await
+        {
+            Ok(resplice) => resplice,
+            Err(err) => {
+                log::error!("{:?}", err);
+                app.ui.notify_failure(&err);
+                continue;
+            }
+        };
+        if !resplice && !splicer.is_broken() {
+            continue;
+        }
+        let (peer_cast_type, endpoint) = {
+            let settings = app.settings.lock().await;
+            let general_settings = &settings.general_settings;
+            (
+                general_settings.peer_cast_type,
+                PeerCastEndpoint::new(general_settings),
+            )
+        };
+        match connect_peer_cast(peer_cast_type, &endpoint, input).await {
+            Ok(publisher) => {
+                log::info!("resplice the relay");
+                splicer.splice(publisher);
+            }
+            // NOTE: 次の確認でつなぎ直す
+            Err(err) => log::warn!("{}", err),
+        }
+    }
+}
+
+/// onMetaData を受け取るたびにチャンネル情報を更新し続ける。返らない
+async fn report_stream_properties(
+    app: &App,
+    mut receiver: watch::Receiver<Option<StreamProperties>>,
+) {
+    loop {
+        if receiver.changed().await.is_err() {
+            // NOTE: 中継が終わると sender が破棄されるが、その時は select! ごと終了している
+            return std::future::pending().await;
+        }
+        let Some(stream_properties) = receiver.borrow_and_update().clone() else {
+            continue;
+        };
+        let settings = app.settings.lock().await;
+        let mut broadcasting = app.broadcasting.lock().await;
+        if !broadcasting.is_broadcasting() {
+            continue;
+        }
+        broadcasting.set_stream_properties(stream_properties);
+        app.update_channel(&broadcasting, &settings).await;
+    }
+}
+
+/// 再生中の曲のファイルが書き換わるたびにチャンネル情報を更新し続ける。返らない
+async fn report_now_playing(app: &App) {
+    let mut interval = interval(NOW_PLAYING_INTERVAL);
+    let mut reported: Option<NowPlaying> = None;
+    loop {
+        interval.tick().await;
+        let path = app
+            .settings
+            .lock()
+            .await
+            .channel_settings
+            .now_playing_path
+            .clone();
+        let now_playing = if path.is_empty() {
+            None
+        } else {
+            match tokio::fs::read(&path).await {
+                Ok(bytes) => NowPlaying::parse(&bytes),
+                Err(err) => {
+                    // NOTE: 書き換えている途中などで読めないことがあるので、前回の値のままにする
+                    log::trace!("{}: {}", path, err);
+                    continue;
+                }
+            }
+        };
+        if now_playing == reported {
+            continue;
+        }
+        let settings = app.settings.lock().await;
+        let mut broadcasting = app.broadcasting.lock().await;
+        if !broadcasting.is_broadcasting() {
+            continue;
+        }
+        log::info!("now playing: {:?}", now_playing);
+        broadcasting.set_now_playing(now_playing.clone());
+        app.update_channel(&broadcasting, &settings).await;
+        reported = now_playing;
+    }
+}
+
+async fn start_recording(app: &App) -> Option<FlvRecorder> {
+    let (directory, channel_name) = {
+        let settings = app.settings.lock().await;
+        let other_settings = &settings.other_settings;
+        if !other_settings.recording_enabled || other_settings.recording_output_directory.is_empty()
+        {
+            return None;
+        }
+        (
+            other_settings.recording_output_directory.clone(),
+            settings.general_settings.channel_name[0].clone(),
+        )
+    };
+    match FlvRecorder::create(&directory, &channel_name).await {
+        Ok(recorder) => Some(recorder),
+        Err(err) => {
+            log::error!("{}", err);
+            let message = format!("録画ファイルの作成に失敗しました。({})", err);
+            app.ui.notify_failure(&Failure::Warn(message));
+            None
+        }
+    }
+}
+
+/// 0 の場合は監視しない
+fn stall_timeout(settings: &Settings) -> Option<Duration> {
+    match settings.general_settings.stall_timeout_seconds {
+        0 => None,
+        seconds => Some(Duration::from_secs(seconds as u64)),
+    }
+}
+
+fn spawn_fan_out(app: &Arc<App>, settings: &Settings) -> RtmpFanOut {
+    let app = Arc::downgrade(app);
+    RtmpFanOut::spawn(
+        &settings.general_settings.rtmp_extra_destinations,
+        Arc::new(move |failure| {
+            if let Some(app) = app.upgrade() {
+                app.ui.notify_failure(&failure);
+            }
+        }),
+    )
+}
+
+async fn finish_recording(app: &App, recording: bool, recorder: Option<FlvRecorder>) {
+    // 中継中に書き込みに失敗していれば recorder は None になっている
+    let failed = match recorder {
+        Some(recorder) => recorder
+            .finish()
+            .await
+            .inspect_err(|err| log::error!("{}", err))
+            .is_err(),
+        None => recording,
+    };
+    if failed {
+        let message = "録画ファイルへの書き込みに失敗したため、録画を中止しました。".to_owned();
+        app.ui.notify_failure(&Failure::Warn(message));
+    }
+}
+
+/// チャンネルを作り直して ID が変わるまで待つ
+async fn wait_for_channel_ids_change(app: &Weak<App>, channel_ids: &[String]) {
+    let mut interval = interval(YP_LISTING_INTERVAL);
+    loop {
+        interval.tick().await;
+        let Some(app) = app.upgrade() else {
+            return;
+        };
+        if app.broadcasting.lock().await.channel_ids() != channel_ids {
+            return;
+        }
+    }
+}
+
+/// YP の index.txt にチャンネルが載るまで待ち、載らなければ警告する。
+/// チャンネルを作り直した場合は、新しい ID で確認し直す
+async fn verify_yp_listings(app: Weak<App>) {
+    loop {
+        let (listings, channel_ids) = {
+            let Some(app) = app.upgrade() else {
+                return;
+            };
+            let settings = app.settings.lock().await;
+            let broadcasting = app.broadcasting.lock().await;
+            let channel_ids: Vec<_> = broadcasting
+                .channel_ids()
+                .into_iter()
+                .map(|x| x.to_owned())
+                .collect();
+            (
+                broadcasting.expected_listings(&app.yp_configs, &settings),
+                channel_ids,
+            )
+        };
+        if channel_ids.is_empty() {
+            return;
+        }
+        let verification = async {
+            join_all(listings.iter().map(|listing| {
+                let app = app.clone();
+                async move {
+                    let Err(err) =
+                        wait_for_listing(listing, YP_LISTING_INTERVAL, YP_LISTING_TIMEOUT).await
+                    else {
+                        log::info!("listed on {}", listing.yp_name);
+                        return;
+                    };
+                    let Some(app) = app.upgrade() else {
+                        return;
+                    };
+                    let message = format!(
+                        "{} への掲載を確認できませんでした。({})",
+                        listing.yp_name, err
+                    );
+                    app.ui.notify_failure(&Failure::Warn(message));
+                }
+            }))
+            .await;
+            std::future::pending().await
+        };
+        select! {
+            _ = verification => unreachable!(),
+            _ = wait_for_channel_ids_change(&app, &channel_ids) => {
+                log::info!("channel ids changed, verify the listings again");
+            }
+        }
+    }
+}
+
+/// PeerCast のチャンネルへの入力
+#[derive(Clone)]
+struct PeerCastInput {
+    rtmp_conn_port: NonZeroU16,
+    /// PeerCast (Original) の場合は、チャンネルを作る前から待ち受けている
+    flv_http_listener: Option<Arc<FlvHttpListener>>,
+}
+
+/// 配信中、または再接続を待っているチャンネル
+struct LiveChannel {
+    input: PeerCastInput,
+    jpnkn_bbs_auto_comment: Option<JpnknBbsAutoComment>,
+    /// 再接続の猶予期間が終わるとチャンネルを終了するタスク
+    grace_period: Option<JoinHandle<()>>,
+    /// YP への掲載を確認するタスク
+    yp_verification: JoinHandle<()>,
+}
+
+async fn stop_live_channel(app: &App, live_channel: &Mutex<Option<LiveChannel>>) {
+    let mut live_channel = live_channel.lock().await;
+    let Some(mut live_channel) = live_channel.take() else {
+        return;
+    };
+    live_channel.yp_verification.abort();
+    app.broadcast_state.handle(BroadcastEvent::Stop);
+    match stop_broadcast(app, live_channel.jpnkn_bbs_auto_comment.as_mut()).await {
+        Ok(_) => {
+            app.broadcast_state.handle(BroadcastEvent::Stopped);
+        }
+        Err(err) => {
+            app.ui.notify_failure(&err);
+            app.broadcast_state.handle(BroadcastEvent::StopFailed);
+        }
+    }
+}
+
 pub struct AppRtmpListenerDelegate {
     app: Weak<App>,
     settings_path: PathBuf,
+    live_channel: Arc<Mutex<Option<LiveChannel>>>,
 }
 
 impl AppRtmpListenerDelegate {
     pub fn new(app: Weak<App>, settings_path: PathBuf) -> Self {
-        Self { app, settings_path }
+        Self {
+            app,
+            settings_path,
+            live_channel: Default::default(),
+        }
     }
 
     fn app(&self) -> Arc<App> {
         self.app.upgrade().unwrap()
     }
-}
 
-#[async_trait]
-impl RtmpListenerDelegate for AppRtmpListenerDelegate {
-    async fn on_connect(&self, incoming: TcpStream) {
-        let app = self.app();
+    /// 再接続待ちのチャンネルがあればそれを引き継ぎ、なければ配信を開始する
+    async fn prepare_live_channel(&self, app: &Arc<App>) -> Result<PeerCastInput, &'static str> {
+        let mut live_channel = self.live_channel.lock().await;
+        if let Some(live_channel) = live_channel.as_mut() {
+            if let Some(grace_period) = live_channel.grace_period.take() {
+                grace_period.abort();
+            }
+            log::info!("resume the channel");
+            app.broadcast_state.handle(BroadcastEvent::Resume);
+            return Ok(live_channel.input.clone());
+        }
+
+        // NOTE: 前回終了できなかったチャンネルが残っている間は配信を始めない
+        if app.broadcast_state.state() == BroadcastState::Error
+            && let Err(err) = broadcast_events::stop_remaining_channels(app).await
+        {
+            app.ui.notify_failure(&err);
+            return Err("Channels of the previous broadcast remain.");
+        }
+        if !app.broadcast_state.handle(BroadcastEvent::Publish) {
+            return Err("Not ready to broadcast.");
+        }
         if !app
             .show_check_again_terms_dialog_if_expired(&self.settings_path)
             .await
         {
-            return;
+            app.broadcast_state.handle(BroadcastEvent::StartFailed);
+            return Err("Terms of the YP have been updated.");
         }
-
-        let (rtmp_conn_port, mut jpnkn_bbs_auto_comment) =
-            match broadcast_events::start_broadcast(&app, &self.settings_path).await {
+        let (rtmp_conn_port, flv_http_listener, jpnkn_bbs_auto_comment) =
+            match broadcast_events::start_broadcast(app, &self.settings_path).await {
                 Ok(ok) => ok,
                 Err(err) => {
                     app.ui.notify_failure(&err);
-                    return;
+                    match broadcast_events::stop_remaining_channels(app).await {
+                        Ok(()) => app.broadcast_state.handle(BroadcastEvent::StartFailed),
+                        Err(err) => {
+                            app.ui.notify_failure(&err);
+                            app.broadcast_state.handle(BroadcastEvent::StopFailed)
+                        }
+                    };
+                    return Err("Failed to start broadcasting.");
                 }
             };
+        app.broadcast_state.handle(BroadcastEvent::Started);
+        let input = PeerCastInput {
+            rtmp_conn_port,
+            flv_http_listener: flv_http_listener.map(Arc::new),
+        };
+        *live_channel = Some(LiveChannel {
+            input: input.clone(),
+            jpnkn_bbs_auto_comment,
+            grace_period: None,
+            yp_verification: spawn(verify_yp_listings(Arc::downgrade(app))),
+        });
+        Ok(input)
+    }
 
-        let outgoing = match connect(&format!("localhost:{}", rtmp_conn_port)).await {
-            Ok(ok) => ok,
-            Err(err) => {
-                app.ui.notify_failure(&err);
+    /// 猶予期間内に再接続されなければチャンネルを終了する
+    async fn wait_for_reconnect(&self, app: &App) {
+        let grace_seconds = app
+            .settings
+            .lock()
+            .await
+            .general_settings
+            .reconnect_grace_seconds;
+        if grace_seconds == 0 {
+            stop_live_channel(app, &self.live_channel).await;
+            return;
+        }
+        let message = format!(
+            "配信元との接続が切れました。{} 秒以内に再接続されなければチャンネルを終了します。",
+            grace_seconds
+        );
+        app.ui.notify_failure(&Failure::Warn(message));
+
+        let mut live_channel = self.live_channel.lock().await;
+        let Some(live_channel) = live_channel.as_mut() else {
+            return;
+        };
+        app.broadcast_state.handle(BroadcastEvent::Disconnect);
+        let app = self.app.clone();
+        let live_channel_arc = self.live_channel.clone();
+        live_channel.grace_period = Some(spawn(async move {
+            sleep(Duration::from_secs(grace_seconds as u64)).await;
+            log::info!("grace period expired");
+            let Some(app) = app.upgrade() else {
+                return;
+            };
+            stop_live_channel(&app, &live_channel_arc).await;
+        }));
+    }
+}
+
+/// PeerCastStation には RTMP で送り、PeerCast には HTTP で取得させる
+async fn connect_peer_cast(
+    peer_cast_type: PeerCastType,
+    endpoint: &PeerCastEndpoint,
+    input: &PeerCastInput,
+) -> anyhow::Result<Box<dyn RelayDestination>> {
+    Ok(match peer_cast_type {
+        PeerCastType::PeerCastStation => {
+            let host = format!("{}:{}", endpoint.host(), input.rtmp_conn_port);
+            Box::new(RtmpPublisher::connect(&host, "live", "livestream").await?)
+        }
+        PeerCastType::PeerCastOriginal => {
+            let Some(flv_http_listener) = &input.flv_http_listener else {
+                anyhow::bail!("the channel was not created for PeerCast");
+            };
+            Box::new(flv_http_listener.accept().await?)
+        }
+    })
+}
+
+#[async_trait]
+impl RtmpListenerDelegate for AppRtmpListenerDelegate {
+    async fn on_connect(&self, request: PublishRequest) {
+        let app = self.app();
+        let input = match self.prepare_live_channel(&app).await {
+            Ok(input) => input,
+            Err(description) => {
+                request.reject(PUBLISH_FAILED, description).await;
                 return;
             }
         };
-        pipe(incoming, outgoing).await; // long long awaiting
 
-        match stop_broadcast(&app, jpnkn_bbs_auto_comment.as_mut()).await {
-            Ok(_) => {}
+        let (peer_cast_type, endpoint) = {
+            let settings = app.settings.lock().await;
+            let general_settings = &settings.general_settings;
+            (
+                general_settings.peer_cast_type,
+                PeerCastEndpoint::new(general_settings),
+            )
+        };
+        let result = match connect_peer_cast(peer_cast_type, &endpoint, &input).await {
             Err(err) => {
-                app.ui.notify_failure(&err);
+                log::error!("{}", err);
+                let message = match peer_cast_type {
+                    PeerCastType::PeerCastStation => {
+                        "PeerCastStation への RTMP 接続に失敗しました。"
+                    }
+                    PeerCastType::PeerCastOriginal => "PeerCast からの接続がありませんでした。",
+                };
+                app.ui.notify_failure(&Failure::Error(message.to_owned()));
+                request
+                    .reject(PUBLISH_FAILED, "Failed to connect to PeerCast.")
+                    .await;
+                Ok(RelayEnd::Unpublished)
+            }
+            Ok(publisher) => match request.accept().await {
+                Err(err) => Err(err),
+                Ok(mut session) => {
+                    let analyzer = std::sync::Mutex::new(StreamAnalyzer::default());
+                    let mut recorder = start_recording(&app).await;
+                    let recording = recorder.is_some();
+                    let (fan_out, stall_timeout, check_interval) = {
+                        let settings = app.settings.lock().await;
+                        (
+                            spawn_fan_out(&app, &settings),
+                            stall_timeout(&settings),
+                            channel_health_check_interval(&settings),
+                        )
+                    };
+                    // NOTE: チャンネルを作り直すと PeerCast との接続も切れるので、監視する場合は中継先を差し替えられるようにする
+                    let (mut publisher, health_check) = match check_interval {
+                        Some(check_interval) => {
+                            let (spliced, splicer) = splice(publisher);
+                            let publisher: Box<dyn RelayDestination> = Box::new(spliced);
+                            (publisher, Some((splicer, check_interval)))
+                        }
+                        None => (publisher, None),
+                    };
+                    let watch_health = async {
+                        let Some((splicer, check_interval)) = &health_check else {
+                            return std::future::pending().await;
+                        };
+                        watch_channel_health(&app, &input, splicer, *check_interval).await
+                    };
+                    let (properties, properties_receiver) = watch::channel(None);
+                    let result = select! {
+                        result = relay(&mut session, &mut *publisher, &analyzer, &mut recorder, &fan_out, &properties, stall_timeout) => result, // long long awaiting
+                        _ = watch_health => unreachable!(),
+                        _ = report_bitrate(&app, &analyzer) => unreachable!(),
+                        _ = report_stream_stats(&app, &analyzer) => unreachable!(),
+                        _ = report_peercast_stats(&app) => unreachable!(),
+                        _ = report_stream_properties(&app, properties_receiver) => unreachable!(),
+                        _ = report_now_playing(&app) => unreachable!(),
+                    };
+                    app.ui.set_ingest_bitrate(None);
+                    app.ui.set_peercast_stats(None);
+                    app.logger_controller.set_stream_stats(Default::default());
+                    finish_recording(&app, recording, recorder).await;
+                    result
+                }
+            },
+        };
+
+        match result {
+            // 正常に配信が終了した
+            Ok(RelayEnd::Unpublished) => stop_live_channel(&app, &self.live_channel).await,
+            Ok(RelayEnd::Stalled) => {
+                let message =
+                    "配信元からデータが届かなくなったため、配信を終了しました。".to_owned();
+                app.ui.notify_failure(&Failure::Warn(message));
+                stop_live_channel(&app, &self.live_channel).await;
+            }
+            // NOTE: チャンネルは残し、次の配信元の on_connect で引き継ぐ
+            Ok(RelayEnd::Replaced) => log::info!("replaced by another publisher"),
+            Err(err) => {
+                log::warn!("{}", err);
+                self.wait_for_reconnect(&app).await;
             }
         }
     }
+
+    fn on_failure(&self, failure: &Failure) {
+        self.app().ui.notify_failure(failure);
+    }
 }
@@ -5,6 +5,7 @@ use std::{
 };
 
 use async_trait::async_trait;
+use tokio::spawn;
 
 use crate::{
     core::{
@@ -16,7 +17,15 @@ use crate::{
         },
         utils::failure::Failure,
     },
-    features::{files::settings::save_settings_and_show_dialog_if_error, ui::UiDelegate},
+    features::{
+        files::settings::save_settings_and_show_dialog_if_error,
+        peercast::{
+            broadcast_preview::BroadcastPreview, peercast_endpoint::PeerCastEndpoint,
+            port_discovery::DiscoveredPorts,
+        },
+        ui::UiDelegate,
+        yp_index::YPDirectory,
+    },
 };
 
 use super::{app::App, entities::contact_status::ContactStatus};
@@ -50,14 +59,53 @@ impl UiDelegate for AppUiDelegate {
         (yp_configs, settings, contact_status)
     }
 
+    async fn check_peercast(&self) {
+        let app = self.app();
+        let settings = app.settings.lock().await.clone();
+        app.warn_if_unsupported_peercast(&settings).await;
+    }
+
+    async fn discover_peercast_ports(&self) -> DiscoveredPorts {
+        let app = self.app();
+        let general_settings = app.settings.lock().await.general_settings.clone();
+        let discovered = app.discover_peercast_ports(&general_settings).await;
+        if discovered.peer_cast_port.is_none() {
+            let failure = Failure::Warn("PeerCast が見つかりませんでした。".to_owned());
+            app.ui.notify_failure(&failure);
+        }
+        discovered
+    }
+
+    async fn fetch_yp_directories(&self) -> Vec<YPDirectory> {
+        self.app().fetch_yp_directories().await
+    }
+
+    async fn preview_broadcast(&self) -> BroadcastPreview {
+        self.app().preview_broadcast().await
+    }
+
     async fn on_change_general_settings(&self, general_settings: GeneralSettings) {
         log::trace!("{:?}", general_settings);
 
         let app = self.app();
         let mut settings = app.settings.lock().await;
+        let peercast_changed = settings.general_settings.peer_cast_type
+            != general_settings.peer_cast_type
+            || PeerCastEndpoint::new(&settings.general_settings)
+                != PeerCastEndpoint::new(&general_settings)
+            || settings.general_settings.content_type != general_settings.content_type;
         settings.general_settings = general_settings;
         save_settings_and_show_dialog_if_error(&self.settings_path, &settings).await;
 
+        // NOTE: PeerCast に繋がらない場合に設定の保存などを待たせない
+        if peercast_changed {
+            let app = app.clone();
+            spawn(async move {
+                let settings = app.settings.lock().await.clone();
+                app.warn_if_unsupported_peercast(&settings).await;
+            });
+        }
+
         self.app()
             .listen_rtmp_if_need(self.app().rtmp_server.lock().await.deref_mut(), &settings)
             .await;
@@ -74,6 +122,11 @@ impl UiDelegate for AppUiDelegate {
         let app = self.app();
         let mut settings = app.settings.lock().await;
         settings.yellow_pages_settings = yellow_pages_settings;
+        // NOTE: ホストが空のものは掲載しないものとして扱う
+        settings
+            .yellow_pages_settings
+            .announcements
+            .retain(|x| !x.settings.host.is_empty());
         save_settings_and_show_dialog_if_error(&self.settings_path, &settings).await;
 
         app.listen_rtmp_if_need(app.rtmp_server.lock().await.deref_mut(), &settings)
@@ -140,18 +193,17 @@ impl UiDelegate for AppUiDelegate {
         settings.other_settings = other_settings;
         save_settings_and_show_dialog_if_error(&self.settings_path, &settings).await;
 
-        let (is_broadcasting, ipv4_id, ipv6_id) = {
+        let (is_broadcasting, channels) = {
             let broadcasting = app.broadcasting.lock().await;
             (
                 broadcasting.is_broadcasting(),
-                broadcasting.ipv4_id().clone(),
-                broadcasting.ipv6_id().clone(),
+                broadcasting.channels().clone(),
             )
         };
         if let Err(err) = self
             .app()
             .logger_controller
-            .on_change_other_settings(ipv4_id, ipv6_id, &settings, is_broadcasting)
+            .on_change_other_settings(channels, &settings, is_broadcasting)
             .await
         {
             let failure = Failure::Warn(err.to_string());
@@ -0,0 +1,73 @@
+use serde::Serialize;
+
+/// 配信の状態。画面には小文字の名前で送る
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum BroadcastState {
+    /// RTMP を待ち受けていない
+    #[default]
+    Idle,
+    /// RTMP を待ち受けていて、配信していない
+    Listening,
+    /// 配信元から接続され、チャンネルを作っている
+    Starting,
+    Streaming,
+    /// 配信元との接続が切れ、再接続を待っている
+    Reconnecting,
+    /// チャンネルを終了している
+    Stopping,
+    /// RTMP の待ち受けかチャンネルの終了に失敗した。
+    /// 終了できなかったチャンネルが残っている間は配信を始めない
+    Error,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BroadcastEvent {
+    Listen,
+    Unlisten,
+    ListenFailed,
+    /// 配信元から接続された
+    Publish,
+    Started,
+    StartFailed,
+    /// 配信元との接続が切れた
+    Disconnect,
+    /// 猶予期間内に配信元から再接続された
+    Resume,
+    Stop,
+    Stopped,
+    StopFailed,
+}
+
+impl BroadcastState {
+    /// PeerCast にチャンネルがある状態
+    pub fn is_on_air(self) -> bool {
+        matches!(self, Self::Streaming | Self::Reconnecting | Self::Stopping)
+    }
+
+    /// 遷移できないイベントの場合は None。
+    /// listening はイベントを反映した後の RTMP の待ち受け状態
+    pub fn next(self, event: BroadcastEvent, listening: bool) -> Option<Self> {
+        use BroadcastEvent as E;
+        let resting = if listening {
+            Self::Listening
+        } else {
+            Self::Idle
+        };
+        match (self, event) {
+            (Self::Idle | Self::Listening | Self::Error, E::Listen | E::Unlisten) => Some(resting),
+            (Self::Idle | Self::Listening | Self::Error, E::ListenFailed) => Some(Self::Error),
+            // NOTE: 配信中の待ち受けの変化は、配信が終わってから反映する
+            (_, E::Listen | E::Unlisten | E::ListenFailed) => Some(self),
+            (Self::Listening | Self::Error, E::Publish) => Some(Self::Starting),
+            (Self::Starting, E::Started) => Some(Self::Streaming),
+            (Self::Starting, E::StartFailed) => Some(resting),
+            (Self::Streaming, E::Disconnect) => Some(Self::Reconnecting),
+            (Self::Streaming | Self::Reconnecting, E::Resume) => Some(Self::Streaming),
+            (Self::Streaming | Self::Reconnecting, E::Stop) => Some(Self::Stopping),
+            (Self::Stopping, E::Stopped) => Some(resting),
+            (Self::Starting | Self::Stopping, E::StopFailed) => Some(Self::Error),
+            _ => None,
+        }
+    }
+}
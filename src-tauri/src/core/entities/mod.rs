@@ -1,3 +1,8 @@
+pub mod broadcast_state;
 pub mod contact_status;
+pub mod now_playing;
+pub mod peercast_stats;
 pub mod settings;
+pub mod stream_properties;
+pub mod stream_stats;
 pub mod yp_config;
@@ -0,0 +1,37 @@
+use encoding_rs::{Encoding, SHIFT_JIS};
+
+/// 音楽プレイヤーなどがファイルに書き出した再生中の曲
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct NowPlaying {
+    pub name: String,
+    pub creator: String,
+    pub album: String,
+    pub url: String,
+}
+
+impl NowPlaying {
+    /// 1 行目から順にタイトル、アーティスト、アルバム、URL として読む。空の場合は None
+    pub fn parse(bytes: &[u8]) -> Option<Self> {
+        let text = decode(bytes);
+        let mut lines = text.lines().map(|line| line.trim().to_owned());
+        let now_playing = Self {
+            name: lines.next().unwrap_or_default(),
+            creator: lines.next().unwrap_or_default(),
+            album: lines.next().unwrap_or_default(),
+            url: lines.next().unwrap_or_default(),
+        };
+        (now_playing != Self::default()).then_some(now_playing)
+    }
+}
+
+/// BOM があればそれに従い、無ければ UTF-8 として読めなければ Shift_JIS とみなす
+fn decode(bytes: &[u8]) -> String {
+    if let Some((encoding, bom_length)) = Encoding::for_bom(bytes) {
+        let (text, _) = encoding.decode_without_bom_handling(&bytes[bom_length..]);
+        return text.into_owned();
+    }
+    match std::str::from_utf8(bytes) {
+        Ok(text) => text.to_owned(),
+        Err(_) => SHIFT_JIS.decode(bytes).0.into_owned(),
+    }
+}
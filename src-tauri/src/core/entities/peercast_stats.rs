@@ -0,0 +1,39 @@
+use serde::Serialize;
+
+use super::settings::NetworkType;
+
+/// チャンネルのリスナー数など
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelStats {
+    pub listeners: u32,
+    pub relays: u32,
+    /// チャンネルに接続しているホスト数
+    pub hosts: u32,
+}
+
+/// 掲載先ごとのチャンネルの状態
+#[derive(Clone, Debug, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AnnouncedChannelStats {
+    /// 掲載先の YP のホスト
+    pub host: String,
+    pub network_type: NetworkType,
+    /// viewxml にチャンネルが無ければ None
+    pub stats: Option<ChannelStats>,
+}
+
+/// viewxml から得た PeerCast の状態
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PeerCastStats {
+    /// PeerCast の起動からの秒数
+    pub uptime: u64,
+    /// 下りの帯域 (kbps)
+    pub bandwidth_in: u32,
+    /// 上りの帯域 (kbps)。リレーとリスナーへの送信を含む
+    pub bandwidth_out: u32,
+    pub total_connections: u32,
+    /// 配信中のチャンネルの順
+    pub channels: Vec<AnnouncedChannelStats>,
+}
@@ -1,14 +1,56 @@
-use std::{cmp::max, collections::HashMap, num::NonZeroU16};
+use std::{
+    cmp::max,
+    collections::HashMap,
+    fmt::{self, Display},
+    num::NonZeroU16,
+};
 
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone, Copy, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
 pub enum PeerCastType {
     PeerCastOriginal,
     #[default]
     PeerCastStation,
 }
 
+/// PeerCast に伝えるチャンネルの形式
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ContentType {
+    #[default]
+    Flv,
+    Mkv,
+    WebM,
+}
+
+impl ContentType {
+    /// チャンネル情報の type
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            ContentType::Flv => "FLV",
+            ContentType::Mkv => "MKV",
+            ContentType::WebM => "WEBM",
+        }
+    }
+
+    /// RTMP の配信元は FLV しか運べないので、他の形式ではチャンネルを作れない
+    pub fn is_rtmp_compatible(self) -> bool {
+        self == ContentType::Flv
+    }
+}
+
+/// 配信中に別の配信元が接続してきた場合の扱い
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum ConcurrentPublisherPolicy {
+    /// 後から来た配信元を拒否する
+    Reject,
+    /// 配信中の配信元を切断して切り替える
+    Replace,
+    /// 配信中の配信元が切断するまで待たせる
+    #[default]
+    Queue,
+}
+
 fn at_least_one_value(list: Vec<String>) -> Vec<String> {
     if list.is_empty() {
         vec!["".into()]
@@ -20,20 +62,92 @@ fn at_least_one_value(list: Vec<String>) -> Vec<String> {
 #[derive(Clone, Debug, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct GeneralSettings {
+    #[serde(default)]
+    pub peer_cast_type: PeerCastType,
+    /// 空の場合は localhost
+    #[serde(default)]
+    pub peer_cast_host: String,
     pub peer_cast_port: NonZeroU16,
+    /// 空の場合は BASIC 認証を使わない
+    #[serde(default)]
+    pub peer_cast_user: String,
+    #[serde(default)]
+    pub peer_cast_password: String,
+    /// 空の場合は認証トークンを使わない
+    #[serde(default)]
+    pub peer_cast_auth_token: String,
     #[serde(default)]
     pub peer_cast_rtmp_port: u16,
+    #[serde(default)]
+    pub content_type: ContentType,
     pub channel_name: Vec<String>,
     pub rtmp_listen_port: NonZeroU16,
+    /// 空の場合は待ち受けない
+    #[serde(default = "default_rtmp_listen_hosts")]
+    pub rtmp_listen_hosts: Vec<String>,
+    #[serde(default)]
+    pub rtmp_stream_key: String,
+    #[serde(default)]
+    pub concurrent_publisher_policy: ConcurrentPublisherPolicy,
+    /// PeerCast の他に中継する RTMP の URL
+    #[serde(default)]
+    pub rtmp_extra_destinations: Vec<String>,
+    /// 配信元との接続が切れてからチャンネルを終了するまでの秒数
+    #[serde(default = "default_reconnect_grace_seconds")]
+    pub reconnect_grace_seconds: u16,
+    /// 配信元からデータが届かなくなってから配信を終了するまでの秒数
+    #[serde(default = "default_stall_timeout_seconds")]
+    pub stall_timeout_seconds: u16,
+    /// キーフレーム間隔がこの秒数を超えたら警告する。0 の場合は警告しない
+    #[serde(default = "default_max_keyframe_interval_seconds")]
+    pub max_keyframe_interval_seconds: u16,
+    /// 配信中のチャンネルの状態を確かめる間隔の秒数。0 の場合は確かめない
+    #[serde(default = "default_channel_health_check_seconds")]
+    pub channel_health_check_seconds: u16,
+}
+
+/// 同じ PC の OBS からのみ接続できるようにする
+fn default_rtmp_listen_hosts() -> Vec<String> {
+    vec!["127.0.0.1".to_owned(), "::1".to_owned()]
+}
+
+fn default_reconnect_grace_seconds() -> u16 {
+    10
+}
+
+fn default_stall_timeout_seconds() -> u16 {
+    30
+}
+
+fn default_max_keyframe_interval_seconds() -> u16 {
+    5
+}
+
+fn default_channel_health_check_seconds() -> u16 {
+    30
 }
 
 impl Default for GeneralSettings {
     fn default() -> Self {
         GeneralSettings {
+            peer_cast_type: Default::default(),
+            peer_cast_host: "".to_owned(),
             peer_cast_port: NonZeroU16::new(7144u16).unwrap(),
+            peer_cast_user: "".to_owned(),
+            peer_cast_password: "".to_owned(),
+            peer_cast_auth_token: "".to_owned(),
             peer_cast_rtmp_port: 0,
+            content_type: Default::default(),
             channel_name: vec!["".to_owned()],
             rtmp_listen_port: NonZeroU16::new(1935u16).unwrap(),
+            rtmp_listen_hosts: default_rtmp_listen_hosts(),
+            rtmp_stream_key: "".to_owned(),
+            concurrent_publisher_policy: Default::default(),
+            rtmp_extra_destinations: Vec::new(),
+            reconnect_grace_seconds: default_reconnect_grace_seconds(),
+            stall_timeout_seconds: default_stall_timeout_seconds(),
+            max_keyframe_interval_seconds: default_max_keyframe_interval_seconds(),
+            channel_health_check_seconds: default_channel_health_check_seconds(),
         }
     }
 }
@@ -49,14 +163,104 @@ pub struct EachYellowPagesSettings {
     pub icon: String,
 }
 
+/// チャンネルを掲載するネットワーク
+#[derive(Clone, Copy, Debug, Default, Deserialize, Eq, PartialEq, Serialize)]
+pub enum NetworkType {
+    #[default]
+    Ipv4,
+    Ipv6,
+}
+
+impl NetworkType {
+    /// broadcastChannel の networkType
+    pub fn as_str(self) -> &'static str {
+        match self {
+            NetworkType::Ipv4 => "ipv4",
+            NetworkType::Ipv6 => "ipv6",
+        }
+    }
+}
+
+impl Display for NetworkType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NetworkType::Ipv4 => write!(f, "IPv4"),
+            NetworkType::Ipv6 => write!(f, "IPv6"),
+        }
+    }
+}
+
+/// 1 つの YP への掲載
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YPAnnouncement {
+    pub network_type: NetworkType,
+    pub settings: EachYellowPagesSettings,
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct YellowPagesSettings {
-    pub ipv4: EachYellowPagesSettings,
-    pub ipv6: EachYellowPagesSettings,
+    /// 先頭の YP のチャンネルが配信元から受け取り、他の YP のチャンネルはそれを中継する
+    pub announcements: Vec<YPAnnouncement>,
     pub agreed_terms: HashMap<String, String>,
 }
 
+impl YellowPagesSettings {
+    pub fn hosts(&self) -> impl Iterator<Item = &str> {
+        self.announcements.iter().map(|x| x.settings.host.as_str())
+    }
+
+    pub fn find(&self, host: &str) -> Option<&YPAnnouncement> {
+        self.announcements.iter().find(|x| x.settings.host == host)
+    }
+
+    pub fn has_ipv6(&self) -> bool {
+        self.announcements
+            .iter()
+            .any(|x| x.network_type == NetworkType::Ipv6)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct StoredYellowPagesSettings {
+    #[serde(default)]
+    pub announcements: Option<Vec<YPAnnouncement>>,
+    /// 以前の形式。announcements が無い場合に使う
+    #[serde(default)]
+    pub ipv4: Option<EachYellowPagesSettings>,
+    #[serde(default)]
+    pub ipv6: Option<EachYellowPagesSettings>,
+    #[serde(default)]
+    pub agreed_terms: HashMap<String, String>,
+}
+
+impl StoredYellowPagesSettings {
+    /// NOTE: 以前は IPv6 のチャンネルが配信元から受け取っていたので、IPv6 を先頭にする
+    pub fn into_internal(self) -> YellowPagesSettings {
+        let announcements = self.announcements.unwrap_or_else(|| {
+            [
+                (NetworkType::Ipv6, self.ipv6),
+                (NetworkType::Ipv4, self.ipv4),
+            ]
+            .into_iter()
+            .filter_map(|(network_type, settings)| {
+                let settings = settings.filter(|x| !x.host.is_empty())?;
+                Some(YPAnnouncement {
+                    network_type,
+                    settings,
+                })
+            })
+            .collect()
+        });
+        YellowPagesSettings {
+            announcements,
+            agreed_terms: self.agreed_terms,
+        }
+    }
+}
+
 #[derive(Clone, Debug, Deserialize, Eq, PartialEq, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ChannelContent {
@@ -72,6 +276,12 @@ pub struct ChannelSettings {
     pub desc: String,
     pub comment: Vec<String>,
     pub contact_url: Vec<String>,
+    pub track_name: Vec<String>,
+    pub track_creator: Vec<String>,
+    pub track_album: Vec<String>,
+    pub track_url: Vec<String>,
+    /// 再生中の曲を書き出すファイル。空の場合は読まない
+    pub now_playing_path: String,
 }
 
 impl Default for ChannelSettings {
@@ -82,6 +292,11 @@ impl Default for ChannelSettings {
             desc: Default::default(),
             comment: vec!["".to_owned()],
             contact_url: vec!["".to_owned()],
+            track_name: vec!["".to_owned()],
+            track_creator: vec!["".to_owned()],
+            track_album: vec!["".to_owned()],
+            track_url: vec!["".to_owned()],
+            now_playing_path: "".to_owned(),
         }
     }
 }
@@ -93,6 +308,16 @@ pub struct StoredChannelSettings {
     pub desc: Vec<String>,
     pub comment: Vec<String>,
     pub contact_url: Vec<String>,
+    #[serde(default)]
+    pub track_name: Vec<String>,
+    #[serde(default)]
+    pub track_creator: Vec<String>,
+    #[serde(default)]
+    pub track_album: Vec<String>,
+    #[serde(default)]
+    pub track_url: Vec<String>,
+    #[serde(default)]
+    pub now_playing_path: String,
 }
 
 impl StoredChannelSettings {
@@ -122,6 +347,11 @@ impl StoredChannelSettings {
             desc,
             comment: at_least_one_value(self.comment),
             contact_url: at_least_one_value(self.contact_url),
+            track_name: at_least_one_value(self.track_name),
+            track_creator: at_least_one_value(self.track_creator),
+            track_album: at_least_one_value(self.track_album),
+            track_url: at_least_one_value(self.track_url),
+            now_playing_path: self.now_playing_path,
         }
     }
 }
@@ -133,6 +363,11 @@ pub struct StoringChannelSettings<'a> {
     pub desc: Vec<&'a str>,
     pub comment: &'a Vec<String>,
     pub contact_url: &'a Vec<String>,
+    pub track_name: &'a Vec<String>,
+    pub track_creator: &'a Vec<String>,
+    pub track_album: &'a Vec<String>,
+    pub track_url: &'a Vec<String>,
+    pub now_playing_path: &'a str,
 }
 
 impl<'a> From<&'a ChannelSettings> for StoringChannelSettings<'a> {
@@ -158,6 +393,11 @@ impl<'a> From<&'a ChannelSettings> for StoringChannelSettings<'a> {
             desc,
             comment: &settings.comment,
             contact_url: &settings.contact_url,
+            track_name: &settings.track_name,
+            track_creator: &settings.track_creator,
+            track_album: &settings.track_album,
+            track_url: &settings.track_url,
+            now_playing_path: &settings.now_playing_path,
         }
     }
 }
@@ -192,6 +432,10 @@ pub struct Hidden {
 pub struct OtherSettings {
     pub log_enabled: bool,
     pub log_output_directory: String,
+    #[serde(default)]
+    pub recording_enabled: bool,
+    #[serde(default)]
+    pub recording_output_directory: String,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub hidden: Option<Hidden>,
 }
@@ -209,7 +453,7 @@ pub struct Settings {
 #[serde(rename_all = "camelCase")]
 pub struct StoredSettings {
     pub general_settings: GeneralSettings,
-    pub yellow_pages_settings: YellowPagesSettings,
+    pub yellow_pages_settings: StoredYellowPagesSettings,
     pub channel_settings: StoredChannelSettings,
     #[serde(default)]
     pub other_settings: OtherSettings,
@@ -220,7 +464,7 @@ impl StoredSettings {
         self.general_settings.channel_name = at_least_one_value(self.general_settings.channel_name);
         Settings {
             general_settings: self.general_settings,
-            yellow_pages_settings: self.yellow_pages_settings,
+            yellow_pages_settings: self.yellow_pages_settings.into_internal(),
             channel_settings: self.channel_settings.into_internal(),
             other_settings: self.other_settings,
         }
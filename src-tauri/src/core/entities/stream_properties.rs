@@ -0,0 +1,61 @@
+use std::num::NonZeroU32;
+
+/// 配信元から送られてきた onMetaData の内容
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct StreamProperties {
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub framerate: Option<f64>,
+    pub video_codec: Option<String>,
+    pub audio_codec: Option<String>,
+    /// kbps
+    pub video_bitrate: Option<u32>,
+    /// kbps
+    pub audio_bitrate: Option<u32>,
+    pub encoder: Option<String>,
+}
+
+fn to_string<T: ToString>(value: &Option<T>) -> String {
+    value.as_ref().map(|x| x.to_string()).unwrap_or_default()
+}
+
+impl StreamProperties {
+    /// 映像と音声のビットレートの合計 (kbps)
+    pub fn bitrate(&self) -> Option<NonZeroU32> {
+        let bitrate =
+            self.video_bitrate.unwrap_or_default() + self.audio_bitrate.unwrap_or_default();
+        NonZeroU32::new(bitrate)
+    }
+
+    /// {width} などのプレースホルダーを置き換える。不明な値は空文字列になる
+    pub fn expand(&self, template: &str) -> String {
+        if !template.contains('{') {
+            return template.to_owned();
+        }
+        // 29.97 などはそのまま、60.0 などは整数にする
+        let fps = self
+            .framerate
+            .map(|x| {
+                format!("{:.2}", x)
+                    .trim_end_matches('0')
+                    .trim_end_matches('.')
+                    .to_owned()
+            })
+            .unwrap_or_default();
+        [
+            ("{width}", to_string(&self.width)),
+            ("{height}", to_string(&self.height)),
+            ("{fps}", fps),
+            ("{video_codec}", to_string(&self.video_codec)),
+            ("{audio_codec}", to_string(&self.audio_codec)),
+            ("{video_bitrate}", to_string(&self.video_bitrate)),
+            ("{audio_bitrate}", to_string(&self.audio_bitrate)),
+            ("{bitrate}", to_string(&self.bitrate())),
+            ("{encoder}", to_string(&self.encoder)),
+        ]
+        .into_iter()
+        .fold(template.to_owned(), |text, (placeholder, value)| {
+            text.replace(placeholder, &value)
+        })
+    }
+}
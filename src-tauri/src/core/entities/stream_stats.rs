@@ -0,0 +1,8 @@
+/// 配信元から受信している映像と音声の状態
+#[derive(Clone, Copy, Debug, Default)]
+pub struct StreamStats {
+    /// 直近のキーフレーム間隔 (ms)
+    pub keyframe_interval: Option<u32>,
+    /// 映像のタイムスタンプから音声のタイムスタンプを引いた値 (ms)
+    pub av_drift: Option<i64>,
+}
@@ -15,6 +15,9 @@ pub struct YPConfig {
     pub support_ipv6: bool,
     pub prefix_header: String,
     pub supported_params: Vec<String>,
+    /// 掲載の確認とチャンネル一覧に使う。None の場合はどちらも行わない
+    #[serde(rename = "indexTxtURL", default)]
+    pub index_txt_url: Option<String>,
 }
 
 impl YPConfig {
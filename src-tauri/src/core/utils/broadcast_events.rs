@@ -1,18 +1,24 @@
-use std::{num::NonZero, path::Path, sync::Arc, time::Duration};
+use std::{num::NonZero, path::Path, sync::Arc};
 
 use tokio::{sync::Mutex, time::sleep};
-use versions::Version;
 
 use crate::{
     core::{
         app::App,
-        entities::{settings::Settings, yp_config::YPConfig},
+        entities::{
+            settings::{PeerCastType, Settings},
+            yp_config::YPConfig,
+        },
         utils::failure::Failure,
     },
     features::{
         files::settings::save_settings_and_show_dialog_if_error,
-        hidden_features::jpnkn_bbs_auto_comment::JpnknBbsAutoComment, logger::LoggerController,
-        peercast::broadcasting::Broadcasting,
+        hidden_features::jpnkn_bbs_auto_comment::JpnknBbsAutoComment,
+        peercast::{
+            broadcasting::{Broadcasting, rtmp_conn_port},
+            peercast_endpoint::PeerCastEndpoint,
+        },
+        rtmp::flv_http_publisher::FlvHttpListener,
     },
 };
 
@@ -29,45 +35,68 @@ fn jpnkn_bbs_auto_comment(settings: &Settings, app: Arc<App>) -> Option<JpnknBbs
     }
 }
 
+/// PeerCast (Original) はチャンネルを作るとすぐに取得しに来るので、その前に待ち受ける
+async fn listen_flv_http(
+    settings: &Settings,
+    rtmp_conn_port: NonZero<u16>,
+) -> Result<Option<FlvHttpListener>, Failure> {
+    match settings.general_settings.peer_cast_type {
+        PeerCastType::PeerCastStation => Ok(None),
+        PeerCastType::PeerCastOriginal => FlvHttpListener::bind(rtmp_conn_port)
+            .await
+            .map(Some)
+            .map_err(|err| {
+                log::error!("{}", err);
+                Failure::Error(format!(
+                    "PeerCast に FLV を渡すポート {} を待ち受けられませんでした。({})",
+                    rtmp_conn_port, err
+                ))
+            }),
+    }
+}
+
 async fn start_channel(
     broadcasting: &mut Broadcasting,
     yp_configs: &[YPConfig],
     settings: &Settings,
-    logger_controller: &LoggerController,
     jpnkn_bbs_auto_comment: Option<&mut JpnknBbsAutoComment>,
-) -> Result<NonZero<u16>, Failure> {
-    let rtmp_conn_port = broadcasting.broadcast(yp_configs, settings).await?;
+) -> Result<(NonZero<u16>, Option<FlvHttpListener>), Failure> {
+    let rtmp_conn_port = rtmp_conn_port(&settings.general_settings).await;
+    let flv_http_listener = listen_flv_http(settings, rtmp_conn_port).await?;
+    broadcasting
+        .broadcast(yp_configs, settings, rtmp_conn_port)
+        .await?;
 
-    let ipv4_id = broadcasting.ipv4_id().clone();
-    let ipv6_id = broadcasting.ipv6_id().clone();
-    logger_controller
-        .on_broadcast(ipv4_id, ipv6_id, settings)
-        .await
-        .map_err(|err| Failure::Warn(err.to_string()))?;
     if let Some(jpnkn_bbs_auto_comment) = jpnkn_bbs_auto_comment {
         jpnkn_bbs_auto_comment.on_broadcast().await;
     }
 
-    Ok(rtmp_conn_port)
+    Ok((rtmp_conn_port, flv_http_listener))
 }
 
+/// 配信元のポート番号と、PeerCast (Original) の場合はその待ち受け
+pub type StartedBroadcast = (
+    NonZero<u16>,
+    Option<FlvHttpListener>,
+    Option<JpnknBbsAutoComment>,
+);
+
 pub async fn start_broadcast(
     app: &Arc<App>,
     settings_path: &Path,
-) -> Result<(NonZero<u16>, Option<JpnknBbsAutoComment>), Failure> {
-    let (rtmp_conn_port, jpnkn_bbs_auto_comment) = {
+) -> Result<StartedBroadcast, Failure> {
+    let ((rtmp_conn_port, flv_http_listener), jpnkn_bbs_auto_comment) = {
         let settings = app.settings.lock().await;
         let mut broadcasting = app.broadcasting.lock().await;
         let mut jpnkn_bbs_auto_comment = jpnkn_bbs_auto_comment(&settings, app.clone());
-        let rtmp_conn_port = start_channel(
+        let started = start_channel(
             &mut broadcasting,
             &app.yp_configs,
             &settings,
-            &app.logger_controller,
             jpnkn_bbs_auto_comment.as_mut(),
         )
         .await?;
-        (rtmp_conn_port, jpnkn_bbs_auto_comment)
+        (started, jpnkn_bbs_auto_comment)
     };
 
     {
@@ -76,55 +105,52 @@ pub async fn start_broadcast(
         save_settings_and_show_dialog_if_error(settings_path, &settings).await;
     }
 
-    app.ui.set_rtmp("streaming".to_owned());
-
-    Ok((rtmp_conn_port, jpnkn_bbs_auto_comment))
+    Ok((rtmp_conn_port, flv_http_listener, jpnkn_bbs_auto_comment))
 }
 
 async fn stop_channel(
     broadcasting: &Mutex<Broadcasting>,
-    peer_cast_port: NonZero<u16>,
-    logger_controller: &LoggerController,
+    peer_cast_endpoint: &PeerCastEndpoint,
     jpnkn_bbs_auto_comment: Option<&mut JpnknBbsAutoComment>,
 ) -> Result<(), Failure> {
-    logger_controller
-        .on_stop_channel()
-        .await
-        .map_err(|err| Failure::Warn(err.to_string()))?;
     if let Some(jpnkn_bbs_auto_comment) = jpnkn_bbs_auto_comment {
         jpnkn_bbs_auto_comment.on_stop_channel();
     }
 
-    broadcasting.lock().await.stop(peer_cast_port).await
+    broadcasting.lock().await.stop(peer_cast_endpoint).await
+}
+
+/// 配信の開始や終了に失敗して残ったチャンネルを終了する
+pub async fn stop_remaining_channels(app: &App) -> Result<(), Failure> {
+    let settings = app.settings.lock().await;
+    let mut broadcasting = app.broadcasting.lock().await;
+    if !broadcasting.is_broadcasting() {
+        return Ok(());
+    }
+    broadcasting
+        .stop(&PeerCastEndpoint::new(&settings.general_settings))
+        .await
 }
 
 pub async fn stop_broadcast(
     app: &App,
     jpnkn_bbs_auto_comment: Option<&mut JpnknBbsAutoComment>,
 ) -> Result<(), Failure> {
-    let pecast_version = {
+    let stop_delay = {
         let settings = app.settings.lock().await;
         app.broadcasting
             .lock()
             .await
-            .fetch_version(settings.general_settings.peer_cast_port)
+            .stop_delay(&PeerCastEndpoint::new(&settings.general_settings))
             .await?
     };
-    if pecast_version < Version::new("3.1.0.0").unwrap() {
-        // NOTE: If the channel is deleted within 3 seconds of the stream closed,
-        //       a tcp listener on PeerCastStation will remain.
-        //       https://github.com/kumaryu/peercaststation/issues/490
-        sleep(Duration::from_secs(6)).await;
-    }
-
-    app.ui.set_rtmp("listening".to_owned());
+    sleep(stop_delay).await;
 
     {
         let settings = app.settings.lock().await;
         stop_channel(
             &app.broadcasting,
-            settings.general_settings.peer_cast_port,
-            &app.logger_controller,
+            &PeerCastEndpoint::new(&settings.general_settings),
             jpnkn_bbs_auto_comment,
         )
         .await?;
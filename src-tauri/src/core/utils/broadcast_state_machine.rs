@@ -0,0 +1,54 @@
+use tokio::sync::watch;
+
+use crate::core::entities::broadcast_state::{BroadcastEvent, BroadcastState};
+
+struct Inner {
+    state: BroadcastState,
+    listening: bool,
+}
+
+/// 配信の状態を持ち、イベントに応じて遷移させる。画面やログは subscribe して状態の変化を受け取る
+pub struct BroadcastStateMachine {
+    inner: std::sync::Mutex<Inner>,
+    sender: watch::Sender<BroadcastState>,
+}
+
+impl BroadcastStateMachine {
+    pub fn new() -> Self {
+        Self {
+            inner: std::sync::Mutex::new(Inner {
+                state: BroadcastState::default(),
+                listening: false,
+            }),
+            sender: watch::channel(BroadcastState::default()).0,
+        }
+    }
+
+    pub fn state(&self) -> BroadcastState {
+        self.inner.lock().unwrap().state
+    }
+
+    pub fn subscribe(&self) -> watch::Receiver<BroadcastState> {
+        self.sender.subscribe()
+    }
+
+    /// 遷移できないイベントの場合は何もせずに false を返す
+    pub fn handle(&self, event: BroadcastEvent) -> bool {
+        let mut inner = self.inner.lock().unwrap();
+        match event {
+            BroadcastEvent::Listen => inner.listening = true,
+            BroadcastEvent::Unlisten | BroadcastEvent::ListenFailed => inner.listening = false,
+            _ => {}
+        }
+        let Some(next) = inner.state.next(event, inner.listening) else {
+            log::warn!("ignore {:?} in {:?}", event, inner.state);
+            return false;
+        };
+        if next != inner.state {
+            log::info!("{:?} -> {:?} ({:?})", inner.state, next, event);
+            inner.state = next;
+            self.sender.send_replace(next);
+        }
+        true
+    }
+}
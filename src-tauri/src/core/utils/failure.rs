@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Failure {
     #[allow(dead_code)]
     Warn(String),
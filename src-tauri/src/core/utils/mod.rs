@@ -1,4 +1,5 @@
 pub mod broadcast_events;
+pub mod broadcast_state_machine;
 pub mod dialog;
 pub mod failure;
 pub mod tcp;
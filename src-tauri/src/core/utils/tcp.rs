@@ -1,12 +1,7 @@
 use std::num::NonZeroU16;
 
 use anyhow::Result;
-use log::trace;
-use tokio::{
-    io::copy,
-    join,
-    net::{TcpListener, TcpStream, ToSocketAddrs},
-};
+use tokio::net::{TcpListener, TcpStream, ToSocketAddrs};
 
 use super::failure::Failure;
 
@@ -24,17 +19,6 @@ pub async fn connect<A: ToSocketAddrs>(addr: A) -> Result<TcpStream, Failure> {
     Err(Failure::Fatal("Connection error".into()))
 }
 
-pub async fn pipe(incoming: TcpStream, outgoing: TcpStream) {
-    let (mut incoming_read, mut incoming_write) = incoming.into_split();
-    let (mut outgoing_read, mut outgoing_write) = outgoing.into_split();
-    trace!("Start piping");
-    let (result1, result2) = join!(
-        copy(&mut incoming_read, &mut outgoing_write),
-        copy(&mut outgoing_read, &mut incoming_write),
-    );
-    trace!("End piping {:?} {:?}", result1, result2);
-}
-
 pub async fn find_free_port() -> Option<NonZeroU16> {
     match TcpListener::bind("0.0.0.0:0").await {
         Ok(listener) => {
@@ -48,3 +32,8 @@ pub async fn find_free_port() -> Option<NonZeroU16> {
         Err(_) => None,
     }
 }
+
+/// 全てのアドレスで待ち受けられるか
+pub async fn is_free_port(port: NonZeroU16) -> bool {
+    TcpListener::bind(("0.0.0.0", port.get())).await.is_ok()
+}
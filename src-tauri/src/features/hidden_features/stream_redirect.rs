@@ -1,4 +1,4 @@
-use std::{num::NonZeroU16, time::SystemTime};
+use std::{num::NonZeroU16, sync::Arc};
 
 use actix_web::{
     get,
@@ -6,68 +6,26 @@ use actix_web::{
     web::{self, Data},
     App, HttpResponse, HttpServer, Responder,
 };
-use anyhow::{anyhow, Result};
+use anyhow::Result;
 use getset::Setters;
-use serde_json::json;
 use tokio::task::JoinHandle;
 
-async fn fetch_channel_id(
-    client: &reqwest::Client,
-    origin: &str,
-    channel_name: &str,
-) -> Result<String> {
-    let id = SystemTime::now()
-        .duration_since(SystemTime::UNIX_EPOCH)?
-        .subsec_micros();
-    let json = client
-        .post(format!("{}/api/1", origin))
-        .header("X-Requested-With", "XMLHttpRequest")
-        .json(&json! ({
-            "jsonrpc": "2.0",
-            "id": id,
-            "method": "getChannels"
-        }))
-        .send()
-        .await?
-        .json::<serde_json::Value>()
-        .await?;
-    let channels = json
-        .get("result")
-        .and_then(|x| x.as_array())
-        .ok_or_else(|| anyhow!("invalid json"))?;
-    let channel = channels
-        .iter()
-        .find(|&x| {
-            x.get("info")
-                .and_then(|x| x.as_object())
-                .and_then(|x| x.get("name"))
-                .and_then(|x| x.as_str())
-                .map(|x| x == channel_name)
-                .unwrap_or(false)
-        })
-        .ok_or_else(|| anyhow!("channel not found"))?;
-    Ok(channel
-        .get("channelId")
-        .and_then(|x| x.as_str())
-        .ok_or_else(|| anyhow!("invalid channel json"))?
-        .to_owned())
-}
+use crate::features::peercast::peercast_backend::PeerCastBackend;
 
 #[get("/stream/my.flv")]
-async fn get_my_flv(data: web::Data<(NonZeroU16, String)>) -> impl Responder {
-    let (port, channel_name) = data.get_ref();
-    let origin = format!("http://127.0.0.1:{}", port);
-    let client = reqwest::Client::new();
-    let channel_id = if let Ok(channel_id) =
-        fetch_channel_id(&client, &origin, &format!("{channel_name} (IPv6)")).await
+async fn get_my_flv(data: web::Data<(Arc<dyn PeerCastBackend>, String)>) -> impl Responder {
+    let (backend, channel_name) = data.get_ref();
+    let channel_id = if let Ok(Some(channel_id)) = backend
+        .find_channel_id(&format!("{channel_name} (IPv6)"))
+        .await
     {
         channel_id
-    } else if let Ok(channel_id) = fetch_channel_id(&client, &origin, channel_name).await {
+    } else if let Ok(Some(channel_id)) = backend.find_channel_id(channel_name).await {
         channel_id
     } else {
         return HttpResponse::NotFound().finish();
     };
-    let url = format!("{}/stream/{}.flv", origin, channel_id);
+    let url = backend.stream_url(&channel_id);
 
     HttpResponse::TemporaryRedirect()
         .append_header((LOCATION, url))
@@ -83,17 +41,18 @@ impl StreamRedirect {
     pub async fn run(
         &mut self,
         listen_port: NonZeroU16,
-        communication_port: NonZeroU16,
+        backend: Box<dyn PeerCastBackend>,
         channel_name: String,
     ) {
         if let Some(handle) = self.join_handle.take() {
             handle.abort();
             let _ = handle.await;
         };
+        let backend: Arc<dyn PeerCastBackend> = Arc::from(backend);
         self.join_handle = Some(tokio::spawn(async move {
             let factory = move || {
                 App::new()
-                    .app_data(Data::new((communication_port, channel_name.clone())))
+                    .app_data(Data::new((backend.clone(), channel_name.clone())))
                     .service(get_my_flv)
             };
             let server = HttpServer::new(factory)
@@ -1,7 +1,4 @@
-use std::{
-    num::NonZeroU16,
-    sync::{Arc, Mutex},
-};
+use std::sync::{Arc, Mutex};
 
 use chrono::{DateTime, Local, SecondsFormat};
 use tokio::{
@@ -9,41 +6,87 @@ use tokio::{
     io::AsyncWriteExt,
     spawn,
     task::JoinHandle,
-    time::{interval, Duration},
+    time::{Duration, interval},
 };
 
-use crate::{core::utils::failure::Failure, features::peercast::pecast_adapter::PeCaStAdapter};
+use crate::{
+    core::{
+        entities::{
+            peercast_stats::{AnnouncedChannelStats, PeerCastStats},
+            settings::{GeneralSettings, NetworkType, PeerCastType},
+            stream_stats::StreamStats,
+        },
+        utils::failure::Failure,
+    },
+    features::peercast::{
+        broadcasting::BroadcastChannel, peercast_backend::PeerCastBackendFactory,
+        peercast_endpoint::PeerCastEndpoint,
+    },
+};
 
 fn to_csv_column(column: &str) -> String {
     column.replace('"', "\"\"")
 }
 
+fn to_csv_number<T: ToString>(value: Option<T>) -> String {
+    value.map(|x| x.to_string()).unwrap_or_default()
+}
+
+fn to_csv_other_channel(channel: &AnnouncedChannelStats) -> String {
+    format!(
+        ",{} ({}),{},{},{}",
+        to_csv_column(&channel.host),
+        channel.network_type.as_str(),
+        to_csv_number(channel.stats.map(|x| x.listeners)),
+        to_csv_number(channel.stats.map(|x| x.relays)),
+        to_csv_number(channel.stats.map(|x| x.hosts)),
+    )
+}
+
+/// NOTE: IPv4 と IPv6 の列にはそれぞれ最初の掲載先を入れる。
+///       残りの掲載先は末尾に「ホスト (ネットワーク)」とリスナー数、リレー数、ホスト数を並べる
 fn to_csv_line(
     local: DateTime<Local>,
-    ipv4_listeners_relays: Option<(u32, u32)>,
-    ipv6_listeners_relays: Option<(u32, u32)>,
+    peercast_stats: Option<&PeerCastStats>,
     genre: &str,
     description: &str,
     comment: &str,
+    stream_stats: Option<StreamStats>,
 ) -> String {
+    let channels = peercast_stats.map_or(&[][..], |x| &x.channels);
+    let first = |network_type| channels.iter().position(|x| x.network_type == network_type);
+    let ipv4_index = first(NetworkType::Ipv4);
+    let ipv6_index = first(NetworkType::Ipv6);
+    let ipv4 = ipv4_index.and_then(|i| channels[i].stats);
+    let ipv6 = ipv6_index.and_then(|i| channels[i].stats);
+    let others: String = channels
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| Some(i) != ipv4_index && Some(i) != ipv6_index)
+        .map(|(_, x)| to_csv_other_channel(x))
+        .collect();
+    let keyframe_interval = stream_stats.and_then(|x| x.keyframe_interval);
+    let av_drift = stream_stats.and_then(|x| x.av_drift);
     format!(
-        "{},{},{},{},{},{},{},{}\n",
+        "{},{},{},{},{},{},{},{},{},{},{},{},{},{},{}{}\n",
         local.to_rfc3339_opts(SecondsFormat::Secs, true),
-        ipv4_listeners_relays
-            .map(|x| x.0.to_string())
-            .unwrap_or_default(),
-        ipv4_listeners_relays
-            .map(|x| x.1.to_string())
-            .unwrap_or_default(),
-        ipv6_listeners_relays
-            .map(|x| x.0.to_string())
-            .unwrap_or_default(),
-        ipv6_listeners_relays
-            .map(|x| x.1.to_string())
-            .unwrap_or_default(),
+        to_csv_number(ipv4.map(|x| x.listeners)),
+        to_csv_number(ipv4.map(|x| x.relays)),
+        to_csv_number(ipv6.map(|x| x.listeners)),
+        to_csv_number(ipv6.map(|x| x.relays)),
         to_csv_column(genre),
         to_csv_column(description),
-        to_csv_column(comment)
+        to_csv_column(comment),
+        keyframe_interval
+            .map(|x| format!("{:.1}", x as f64 / 1000.0))
+            .unwrap_or_default(),
+        to_csv_number(av_drift),
+        to_csv_number(ipv4.map(|x| x.hosts)),
+        to_csv_number(ipv6.map(|x| x.hosts)),
+        to_csv_number(peercast_stats.map(|x| x.bandwidth_out)),
+        to_csv_number(peercast_stats.map(|x| x.bandwidth_in)),
+        to_csv_number(peercast_stats.map(|x| x.total_connections)),
+        others,
     )
 }
 
@@ -57,49 +100,42 @@ async fn put_line(line: &str, path: &str) -> anyhow::Result<()> {
     Ok(())
 }
 
-async fn put_listeners_relays(
-    ipv4_listeners_relays: Option<(u32, u32)>,
-    ipv6_listeners_relays: Option<(u32, u32)>,
+async fn put_stats(
+    peercast_stats: PeerCastStats,
+    stream_stats: StreamStats,
     path: &str,
 ) -> anyhow::Result<()> {
     put_line(
         &to_csv_line(
             Local::now(),
-            ipv4_listeners_relays,
-            ipv6_listeners_relays,
+            Some(&peercast_stats),
             "",
             "",
             "",
+            Some(stream_stats),
         ),
         path,
     )
     .await
 }
 
-async fn find_listeners_relays(
-    port: NonZeroU16,
-    ipv4_channel_id: Option<&str>,
-    ipv6_channel_id: Option<&str>,
-) -> Result<(Option<(u32, u32)>, Option<(u32, u32)>), Failure> {
-    let view_xml = PeCaStAdapter::new(port).view_xml().await?;
-    log::trace!("{:?}", view_xml);
-    let ipv4_listeners_relays =
-        ipv4_channel_id.and_then(|channel_id| view_xml.find_listeners_relays(channel_id));
-    let ipv6_listeners_relays =
-        ipv6_channel_id.and_then(|channel_id| view_xml.find_listeners_relays(channel_id));
-    Ok((ipv4_listeners_relays, ipv6_listeners_relays))
-}
-
 async fn tick(
-    peer_cast_port: &Mutex<NonZeroU16>,
-    ipv4_channel_id: Option<&str>,
-    ipv6_channel_id: Option<&str>,
+    backend_factory: &PeerCastBackendFactory,
+    peer_cast_type: PeerCastType,
+    peer_cast_endpoint: &Mutex<PeerCastEndpoint>,
+    channels: &Mutex<Vec<BroadcastChannel>>,
+    stream_stats: &Mutex<StreamStats>,
     path: &str,
 ) -> Result<(), Failure> {
-    let peer_cast_port = *peer_cast_port.lock().unwrap();
-    let (ipv4, ipv6) =
-        find_listeners_relays(peer_cast_port, ipv4_channel_id, ipv6_channel_id).await?;
-    put_listeners_relays(ipv4, ipv6, path)
+    let peer_cast_endpoint = peer_cast_endpoint.lock().unwrap().clone();
+    let view_xml = backend_factory(peer_cast_type, &peer_cast_endpoint)
+        .view_xml()
+        .await?;
+    log::trace!("{:?}", view_xml);
+    let channels = channels.lock().unwrap().clone();
+    let peercast_stats = view_xml.peercast_stats(&channels);
+    let stream_stats = *stream_stats.lock().unwrap();
+    put_stats(peercast_stats, stream_stats, path)
         .await
         .map_err(|err| {
             log::error!("{:?}", err);
@@ -111,27 +147,33 @@ async fn tick(
 pub struct Logger {
     join_handle: JoinHandle<()>,
     path: String,
-    peer_cast_port: Arc<std::sync::Mutex<NonZeroU16>>,
+    peer_cast_endpoint: Arc<std::sync::Mutex<PeerCastEndpoint>>,
+    channels: Arc<Mutex<Vec<BroadcastChannel>>>,
 }
 
 impl Logger {
     pub fn spawn(
         directory: &str,
-        ipv4_channel_id: Option<String>,
-        ipv6_channel_id: Option<String>,
-        channel_name: &str,
-        peer_cast_port: NonZeroU16,
+        channels: Vec<BroadcastChannel>,
+        general_settings: &GeneralSettings,
+        stream_stats: Arc<Mutex<StreamStats>>,
+        backend_factory: PeerCastBackendFactory,
         on_error: Box<dyn Send + Sync + Fn(Failure)>,
     ) -> Self {
         let path = format!(
             "{}/{}_{}.csv",
             directory,
             Local::now().format("%Y%m%dT%H%M%S"),
-            channel_name
+            general_settings.channel_name[0]
         );
-        let peer_cast_port = Arc::new(std::sync::Mutex::new(peer_cast_port));
+        let peer_cast_type = general_settings.peer_cast_type;
+        let peer_cast_endpoint = Arc::new(std::sync::Mutex::new(PeerCastEndpoint::new(
+            general_settings,
+        )));
+        let channels = Arc::new(Mutex::new(channels));
         let join_handle = {
-            let peer_cast_port = peer_cast_port.clone();
+            let peer_cast_endpoint = peer_cast_endpoint.clone();
+            let channels = channels.clone();
             let path = path.clone();
             spawn(async move {
                 let mut interval = interval(Duration::from_secs(60));
@@ -139,9 +181,11 @@ impl Logger {
                 loop {
                     interval.tick().await;
                     match tick(
-                        peer_cast_port.as_ref(),
-                        ipv4_channel_id.as_deref(),
-                        ipv6_channel_id.as_deref(),
+                        &backend_factory,
+                        peer_cast_type,
+                        peer_cast_endpoint.as_ref(),
+                        channels.as_ref(),
+                        &stream_stats,
                         &path,
                     )
                     .await
@@ -155,17 +199,23 @@ impl Logger {
         Self {
             join_handle,
             path,
-            peer_cast_port,
+            peer_cast_endpoint,
+            channels,
         }
     }
 
-    pub fn set_peer_cast_port(&mut self, peer_cast_port: NonZeroU16) {
-        *self.peer_cast_port.lock().unwrap() = peer_cast_port;
+    pub fn set_peer_cast_endpoint(&mut self, peer_cast_endpoint: PeerCastEndpoint) {
+        *self.peer_cast_endpoint.lock().unwrap() = peer_cast_endpoint;
+    }
+
+    /// チャンネルを作り直すと ID が変わるため、次に記録する行から新しいチャンネルを使う
+    pub fn set_channels(&mut self, channels: Vec<BroadcastChannel>) {
+        *self.channels.lock().unwrap() = channels;
     }
 
     pub async fn put_info(&self, genre: &str, desc: &str, comment: &str) -> anyhow::Result<()> {
         put_line(
-            &to_csv_line(Local::now(), None, None, genre, desc, comment),
+            &to_csv_line(Local::now(), None, genre, desc, comment, None),
             &self.path,
         )
         .await
@@ -4,9 +4,18 @@ use std::sync::Arc;
 
 use tokio::sync::Mutex;
 
-use crate::core::{
-    entities::settings::{ChannelSettings, GeneralSettings, Settings},
-    utils::failure::Failure,
+use crate::{
+    core::{
+        entities::{
+            settings::{ChannelSettings, GeneralSettings, Settings},
+            stream_stats::StreamStats,
+        },
+        utils::failure::Failure,
+    },
+    features::peercast::{
+        broadcasting::BroadcastChannel, peercast_backend::PeerCastBackendFactory,
+        peercast_endpoint::PeerCastEndpoint,
+    },
 };
 
 use self::logger_core::Logger;
@@ -16,13 +25,17 @@ type BoxedOnError = Box<dyn Send + Sync + Fn(Failure)>;
 pub struct LoggerController {
     logger: Mutex<Option<Logger>>,
     on_error: Arc<std::sync::Mutex<Option<BoxedOnError>>>,
+    stream_stats: Arc<std::sync::Mutex<StreamStats>>,
+    backend_factory: PeerCastBackendFactory,
 }
 
 impl LoggerController {
-    pub fn new() -> Self {
+    pub fn new(backend_factory: PeerCastBackendFactory) -> Self {
         Self {
             logger: Mutex::new(None),
             on_error: Arc::new(std::sync::Mutex::new(None)),
+            stream_stats: Default::default(),
+            backend_factory,
         }
     }
 
@@ -30,19 +43,19 @@ impl LoggerController {
         *self.on_error.lock().unwrap() = Some(on_error);
     }
 
-    fn spawn_logger(
-        &self,
-        ipv4_channel_id: Option<String>,
-        ipv6_channel_id: Option<String>,
-        settings: &Settings,
-    ) -> Logger {
+    /// 次に記録する行に使われる
+    pub fn set_stream_stats(&self, stream_stats: StreamStats) {
+        *self.stream_stats.lock().unwrap() = stream_stats;
+    }
+
+    fn spawn_logger(&self, channels: Vec<BroadcastChannel>, settings: &Settings) -> Logger {
         let on_error = self.on_error.clone();
         Logger::spawn(
             &settings.other_settings.log_output_directory,
-            ipv4_channel_id,
-            ipv6_channel_id,
-            &settings.general_settings.channel_name[0],
-            settings.general_settings.peer_cast_port,
+            channels,
+            &settings.general_settings,
+            self.stream_stats.clone(),
+            self.backend_factory.clone(),
             Box::new(move |err| {
                 if let Some(on_error) = on_error.lock().unwrap().as_ref() {
                     on_error(err);
@@ -53,13 +66,12 @@ impl LoggerController {
 
     pub async fn on_broadcast(
         &self,
-        ipv4_channel_id: Option<String>,
-        ipv6_channel_id: Option<String>,
+        channels: Vec<BroadcastChannel>,
         settings: &Settings,
     ) -> anyhow::Result<()> {
         let log_output_directory = &settings.other_settings.log_output_directory;
         if settings.other_settings.log_enabled && !log_output_directory.is_empty() {
-            let logger = self.spawn_logger(ipv4_channel_id, ipv6_channel_id, settings);
+            let logger = self.spawn_logger(channels, settings);
             let channel = &settings.channel_settings;
             logger
                 .put_info(&channel.genre, &channel.desc, &channel.comment[0])
@@ -71,7 +83,13 @@ impl LoggerController {
 
     pub async fn on_change_general_settings(&self, general_settings: &GeneralSettings) {
         if let Some(logger) = self.logger.lock().await.as_mut() {
-            logger.set_peer_cast_port(general_settings.peer_cast_port);
+            logger.set_peer_cast_endpoint(PeerCastEndpoint::new(general_settings));
+        }
+    }
+
+    pub async fn on_change_channels(&self, channels: Vec<BroadcastChannel>) {
+        if let Some(logger) = self.logger.lock().await.as_mut() {
+            logger.set_channels(channels);
         }
     }
 
@@ -89,8 +107,7 @@ impl LoggerController {
 
     pub async fn on_change_other_settings(
         &self,
-        ipv4_channel_id: Option<String>,
-        ipv6_channel_id: Option<String>,
+        channels: Vec<BroadcastChannel>,
         settings: &Settings,
         broadcasting: bool,
     ) -> anyhow::Result<()> {
@@ -104,7 +121,7 @@ impl LoggerController {
             return Ok(());
         }
         if logger_opt.is_none() {
-            let logger = self.spawn_logger(ipv4_channel_id, ipv6_channel_id, settings);
+            let logger = self.spawn_logger(channels, settings);
             *logger_opt = Some(logger);
             let channel = &settings.channel_settings;
             let logger = logger_opt.as_ref().unwrap();
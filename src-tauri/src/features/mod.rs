@@ -6,3 +6,4 @@ pub mod peercast;
 pub mod rtmp;
 pub mod terms_check;
 pub mod ui;
+pub mod yp_index;
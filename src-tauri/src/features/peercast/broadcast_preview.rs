@@ -0,0 +1,123 @@
+use serde::Serialize;
+
+use crate::core::entities::{
+    settings::{NetworkType, Settings, YPAnnouncement},
+    yp_config::YPConfig,
+};
+
+use super::{capabilities::rtmp_incompatible_message, peercast_endpoint::remote_problem};
+
+/// NOTE: YP はジャンルの先頭のこれらの記号を設定として読む
+const GENRE_OPTION_CHARS: [char; 4] = [':', '?', '@', '+'];
+
+/// 配信を開始した時に 1 つの YP のチャンネルに送る内容
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelPreview {
+    pub host: String,
+    /// YP の設定が見つからない場合はホスト
+    pub yp_name: String,
+    pub network_type: NetworkType,
+    pub name: String,
+    /// 接頭辞を付けたジャンル。YP の設定が見つからない場合は設定のまま
+    pub genre: String,
+    pub desc: String,
+    pub comment: String,
+    pub contact_url: String,
+    pub mime_type: String,
+    pub source_type: String,
+    /// ポート番号や配信元のチャンネル ID が配信開始時に決まる場合は None
+    pub source_uri: Option<String>,
+    pub problems: Vec<String>,
+}
+
+/// PeerCast に接続せずに作った、配信を開始した時に送る内容
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BroadcastPreview {
+    pub channels: Vec<ChannelPreview>,
+    /// 特定の YP に関係しない問題
+    pub problems: Vec<String>,
+}
+
+impl BroadcastPreview {
+    pub fn has_problems(&self) -> bool {
+        !self.problems.is_empty() || self.channels.iter().any(|x| !x.problems.is_empty())
+    }
+}
+
+pub fn common_problems(yp_configs: &[YPConfig], settings: &Settings) -> Vec<String> {
+    let mut problems = Vec::new();
+    if settings.general_settings.channel_name[0].trim().is_empty() {
+        problems.push("チャンネル名が空です。".to_owned());
+    }
+    if settings.yellow_pages_settings.announcements.is_empty() {
+        problems.push("掲載先の YP がありません。".to_owned());
+    }
+    problems.extend(remote_problem(&settings.general_settings));
+    let content_type = settings.general_settings.content_type;
+    if !content_type.is_rtmp_compatible() {
+        problems.push(rtmp_incompatible_message(content_type));
+    }
+    problems.extend(genre_problems(&settings.channel_settings.genre, yp_configs));
+    problems
+}
+
+/// 接頭辞は YP ごとに付くので、ジャンルに書かれていると二重になる
+fn genre_problems(genre: &str, yp_configs: &[YPConfig]) -> Vec<String> {
+    let mut problems = Vec::new();
+    if let Some(prefix) = yp_configs
+        .iter()
+        .map(|x| &x.prefix_header)
+        .find(|prefix| !prefix.is_empty() && genre.starts_with(prefix.as_str()))
+    {
+        problems.push(format!(
+            "ジャンルが YP の接頭辞「{}」で始まっています。接頭辞は YP ごとに自動で付くため、ジャンルには書かないでください。",
+            prefix
+        ));
+    }
+    if let Some(c) = genre
+        .chars()
+        .next()
+        .filter(|c| GENRE_OPTION_CHARS.contains(c))
+    {
+        problems.push(format!(
+            "ジャンルが「{}」で始まっているため、YP の設定として読まれることがあります。",
+            c
+        ));
+    }
+    problems
+}
+
+pub fn channel_problems(
+    yp_configs: &[YPConfig],
+    settings: &Settings,
+    announcement: &YPAnnouncement,
+) -> Vec<String> {
+    let host = &announcement.settings.host;
+    let mut problems = Vec::new();
+    if settings
+        .yellow_pages_settings
+        .hosts()
+        .filter(|x| x == host)
+        .count()
+        > 1
+    {
+        problems.push("同じ YP が複数の掲載先に指定されています。".to_owned());
+    }
+    let Some(yp_config) = yp_configs.iter().find(|x| &x.host == host) else {
+        problems.push(format!("YP「{}」の設定が見つかりません。", host));
+        return problems;
+    };
+    if announcement.network_type == NetworkType::Ipv6 && !yp_config.support_ipv6 {
+        problems.push(format!("{} は IPv6 に対応していません。", yp_config.name));
+    }
+    if !settings
+        .yellow_pages_settings
+        .agreed_terms
+        .contains_key(&yp_config.terms_url)
+    {
+        problems.push(format!("{} の利用規約に同意していません。", yp_config.name));
+    }
+    problems
+}
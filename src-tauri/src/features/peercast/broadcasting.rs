@@ -1,168 +1,506 @@
-use std::num::{NonZero, NonZeroU16};
+use std::{
+    num::{NonZeroU16, NonZeroU32},
+    time::Duration,
+};
 
+use futures::future::{join_all, try_join_all};
 use getset::Getters;
-use tokio::try_join;
-use versions::Version;
 
 use crate::{
     core::{
         entities::{
-            settings::{EachYellowPagesSettings, Settings, YellowPagesSettings},
+            now_playing::NowPlaying,
+            peercast_stats::PeerCastStats,
+            settings::{
+                ChannelSettings, EachYellowPagesSettings, GeneralSettings, NetworkType,
+                PeerCastType, Settings, YPAnnouncement,
+            },
+            stream_properties::StreamProperties,
             yp_config::YPConfig,
         },
         utils::{failure::Failure, tcp::find_free_port},
     },
-    features::peercast::{
-        channel_utils::{find_id, info, ipv6_channel_name, loopback, rtmp_source},
-        pecast_adapter::{Info, PeCaStAdapter, Track},
+    features::{
+        peercast::{
+            broadcast_preview::{
+                BroadcastPreview, ChannelPreview, channel_problems, common_problems,
+            },
+            capabilities::Capabilities,
+            channel_utils::{channel_name, info, loopback, track},
+            peercast_backend::{ChannelHealth, PeerCastBackend, PeerCastBackendFactory},
+            peercast_endpoint::{PeerCastEndpoint, remote_problem},
+            port_discovery::reusable_rtmp_port,
+        },
+        yp_index::ExpectedListing,
     },
 };
 
-const EMPTY_TRACK: Track = Track {
-    name: "",
-    creator: "",
-    genre: "",
-    album: "",
-    url: "",
-};
-
-async fn add_yellow_pages(adapter: &PeCaStAdapter, yp_host: &str) -> Result<i32, Failure> {
-    adapter
-        .add_yellow_page("pcp", yp_host, &format!("pcp://{}", yp_host))
-        .await
+fn genre(
+    yp_configs: &[YPConfig],
+    yp_settings: &EachYellowPagesSettings,
+    base_genre: &str,
+) -> Result<String, Failure> {
+    let yp_config = yp_configs
+        .iter()
+        .find(|x| x.host == yp_settings.host)
+        .ok_or_else(|| {
+            Failure::Error(format!(
+                "YP「{}」の設定が見つかりません。",
+                yp_settings.host
+            ))
+        })?;
+    Ok(yp_config.genre_full_text(base_genre, yp_settings))
 }
 
-async fn get_or_add_yellow_pages(
-    adapter: &PeCaStAdapter,
-    yp_list: &[(i32, String)],
-    yp_host: &str,
-) -> Result<Option<i32>, Failure> {
-    if yp_host.is_empty() {
-        Ok(None)
-    } else if let Some(yp_id) = find_id(yp_list, yp_host) {
-        Ok(Some(yp_id))
+/// 配信前の確認で掲載先に問題があれば、チャンネルを作ったり更新したりしない
+fn validate_announcements<'a>(
+    yp_configs: &[YPConfig],
+    settings: &Settings,
+    announcements: impl IntoIterator<Item = &'a YPAnnouncement>,
+) -> Result<(), Failure> {
+    let problems: Vec<_> = announcements
+        .into_iter()
+        .flat_map(|announcement| channel_problems(yp_configs, settings, announcement))
+        .collect();
+    if problems.is_empty() {
+        Ok(())
     } else {
-        add_yellow_pages(adapter, yp_host).await.map(Some)
+        Err(Failure::Error(problems.join("\n")))
     }
 }
 
-async fn prepare_yellow_pages(
-    adapter: &PeCaStAdapter,
-    yp_settings: &YellowPagesSettings,
-) -> Result<(Option<i32>, Option<i32>), Failure> {
-    log::trace!("get yp");
-    let yp_list = adapter.get_yellow_pages().await?;
-    log::trace!("get yp {:?}", yp_list);
-
-    Ok(tokio::try_join!(
-        get_or_add_yellow_pages(adapter, &yp_list, &yp_settings.ipv4.host),
-        get_or_add_yellow_pages(adapter, &yp_list, &yp_settings.ipv6.host)
-    )?)
+/// 配信中のチャンネル
+#[derive(Clone, Debug)]
+pub struct BroadcastChannel {
+    pub id: String,
+    /// チャンネルを作った時の掲載先
+    pub announcement: YPAnnouncement,
 }
 
-fn genre(
-    yp_configs: &[YPConfig],
-    yp_settings: &EachYellowPagesSettings,
-    base_genre: &str,
-) -> String {
-    yp_configs
-        .iter()
-        .find(|x| x.host == yp_settings.host)
-        .unwrap()
-        .genre_full_text(base_genre, yp_settings)
+impl BroadcastChannel {
+    pub fn host(&self) -> &str {
+        &self.announcement.settings.host
+    }
+
+    /// 配信中に掲載先の設定が変わっていれば新しい値を使う
+    fn yp_settings<'a>(&'a self, settings: &'a Settings) -> &'a EachYellowPagesSettings {
+        settings
+            .yellow_pages_settings
+            .find(self.host())
+            .map_or(&self.announcement.settings, |x| &x.settings)
+    }
 }
 
-async fn broadcast<'a>(
-    adapter: &PeCaStAdapter,
-    yp_id: i32,
-    (source_stream, source_uri): &(&'static str, String),
-    network_type: &str,
-    info: &'a Info<'a>,
-) -> Result<String, Failure> {
-    // WTF: IPv6 一時アドレスが正しく設定されないケースの対策
-    adapter.check_ports().await?;
-    adapter.get_external_ip_addresses().await?;
-    adapter
-        .broadcast_channel(
-            Some(yp_id),
-            source_uri,
-            source_stream,
-            "ASF(WMV or WMA)",
-            info,
-            &EMPTY_TRACK,
-            network_type,
-        )
-        .await
+/// NOTE: 自動の場合は、PeerCastStation で以前に使った配信元のポート番号が空いていれば使い回す
+async fn configured_rtmp_port(general_settings: &GeneralSettings) -> Option<NonZeroU16> {
+    match NonZeroU16::new(general_settings.peer_cast_rtmp_port) {
+        Some(port) => Some(port),
+        None => reusable_rtmp_port(general_settings).await,
+    }
+}
+
+/// 配信元のポート番号。自動の場合は空いているポート番号を使う
+pub async fn rtmp_conn_port(general_settings: &GeneralSettings) -> NonZeroU16 {
+    match configured_rtmp_port(general_settings).await {
+        Some(port) => port,
+        None => find_free_port().await.unwrap(),
+    }
+}
+
+fn includes_ipv4<'a>(announcements: impl IntoIterator<Item = &'a YPAnnouncement>) -> bool {
+    announcements
+        .into_iter()
+        .any(|x| x.network_type == NetworkType::Ipv4)
 }
 
 #[derive(Getters)]
 pub struct Broadcasting {
+    /// 先頭のチャンネルが配信元から受け取り、他のチャンネルはそれを中継する
     #[getset(get = "pub")]
-    ipv4_id: Option<String>,
-    #[getset(get = "pub")]
-    ipv6_id: Option<String>,
+    channels: Vec<BroadcastChannel>,
+    /// 受信ビットレート (kbps)
+    bitrate: Option<NonZeroU32>,
+    stream_properties: StreamProperties,
+    /// 再生中の曲。設定のトラック情報より優先する
+    now_playing: Option<NowPlaying>,
+    /// 配信中のチャンネルを作った PeerCast の種類
+    peer_cast_type: PeerCastType,
+    /// 配信開始時に調べた PeerCast の機能
+    capabilities: Option<Capabilities>,
+    backend_factory: PeerCastBackendFactory,
 }
 
 unsafe impl Send for Broadcasting {}
 unsafe impl Sync for Broadcasting {}
 
 impl Broadcasting {
-    pub fn new() -> Self {
+    pub fn new(backend_factory: PeerCastBackendFactory) -> Self {
         Self {
-            ipv4_id: None,
-            ipv6_id: None,
+            channels: Vec::new(),
+            bitrate: None,
+            stream_properties: Default::default(),
+            now_playing: None,
+            peer_cast_type: Default::default(),
+            capabilities: None,
+            backend_factory,
         }
     }
 
     pub fn is_broadcasting(&self) -> bool {
-        self.ipv4_id.is_some() || self.ipv6_id.is_some()
+        !self.channels.is_empty()
+    }
+
+    pub fn channel_ids(&self) -> Vec<&str> {
+        self.channels.iter().map(|x| x.id.as_str()).collect()
+    }
+
+    pub fn set_bitrate(&mut self, bitrate: Option<NonZeroU32>) {
+        self.bitrate = bitrate;
+    }
+
+    pub fn set_stream_properties(&mut self, stream_properties: StreamProperties) {
+        self.stream_properties = stream_properties;
+    }
+
+    pub fn set_now_playing(&mut self, now_playing: Option<NowPlaying>) {
+        self.now_playing = now_playing;
     }
 
-    pub async fn fetch_version(&self, peer_cast_port: NonZero<u16>) -> Result<Version, Failure> {
-        let adapter = PeCaStAdapter::new(peer_cast_port);
-        let agent_name = adapter.get_version_info().await?;
-        let version = agent_name
-            .split('/')
-            .nth(1)
-            .ok_or_else(|| Failure::Fatal("Invalid agentName format".to_owned()))?;
-        Version::new(version).ok_or_else(|| Failure::Fatal("Invalid agentName format".to_owned()))
+    /// 受信ビットレートが未計測の場合は onMetaData の値を使う
+    fn info_bitrate(&self) -> Option<NonZeroU32> {
+        self.bitrate.or_else(|| self.stream_properties.bitrate())
     }
 
+    /// 概要とコメントのプレースホルダーを置き換える
+    fn expand_channel_settings(&self, channel_settings: &ChannelSettings) -> ChannelSettings {
+        let mut comment = channel_settings.comment.clone();
+        comment[0] = self.stream_properties.expand(&comment[0]);
+        ChannelSettings {
+            desc: self.stream_properties.expand(&channel_settings.desc),
+            comment,
+            ..channel_settings.clone()
+        }
+    }
+
+    /// 配信元が切断してからチャンネルを終了するまでに待つ時間
+    pub async fn stop_delay(&self, endpoint: &PeerCastEndpoint) -> Result<Duration, Failure> {
+        if let Some(capabilities) = &self.capabilities {
+            return Ok(capabilities.stop_delay);
+        }
+        let backend = (self.backend_factory)(self.peer_cast_type, endpoint);
+        Ok(backend.capabilities().await?.stop_delay)
+    }
+
+    /// 配信中のチャンネルと PeerCast の状態
+    pub async fn fetch_peercast_stats(
+        &self,
+        endpoint: &PeerCastEndpoint,
+    ) -> Result<PeerCastStats, Failure> {
+        let view_xml = (self.backend_factory)(self.peer_cast_type, endpoint)
+            .view_xml()
+            .await?;
+        Ok(view_xml.peercast_stats(&self.channels))
+    }
+
+    /// 前回の配信のチャンネルが残っている場合は、先に stop で終了する必要がある。
+    /// 途中で失敗した場合は、この呼び出しで作ったチャンネルを終了する
     pub async fn broadcast(
         &mut self,
         yp_configs: &[YPConfig],
         settings: &Settings,
-    ) -> Result<NonZeroU16, Failure> {
-        let rtmp_conn_port = if settings.general_settings.peer_cast_rtmp_port != 0 {
-            NonZeroU16::new(settings.general_settings.peer_cast_rtmp_port).unwrap()
-        } else {
-            find_free_port().await.unwrap()
-        };
+        rtmp_conn_port: NonZeroU16,
+    ) -> Result<(), Failure> {
+        if self.is_broadcasting() {
+            return Err(Failure::Error(
+                "前回の配信のチャンネルを終了できていません。".to_owned(),
+            ));
+        }
+        if let Some(problem) = remote_problem(&settings.general_settings) {
+            return Err(Failure::Error(problem));
+        }
+        validate_announcements(
+            yp_configs,
+            settings,
+            &settings.yellow_pages_settings.announcements,
+        )?;
+        self.peer_cast_type = settings.general_settings.peer_cast_type;
+        let endpoint = PeerCastEndpoint::new(&settings.general_settings);
+        let backend = (self.backend_factory)(self.peer_cast_type, &endpoint);
+        let mut channels = Vec::new();
+        let result = self
+            .broadcast_channels(
+                &*backend,
+                yp_configs,
+                settings,
+                rtmp_conn_port,
+                &mut channels,
+            )
+            .await;
+        if let Err(err) = result {
+            // NOTE: 終了できなかったチャンネルは stop で終了できるように残す
+            for channel in channels {
+                if let Err(err) = backend.stop(&channel.id).await {
+                    log::warn!("{:?}", err);
+                    self.channels.push(channel);
+                }
+            }
+            return Err(err);
+        }
+        self.channels = channels;
+        Ok(())
+    }
 
-        let adapter = PeCaStAdapter::new(settings.general_settings.peer_cast_port);
-        let (ipv4_yp_id, ipv6_yp_id) =
-            prepare_yellow_pages(&adapter, &settings.yellow_pages_settings).await?;
-        let ipv4_channel_name = &settings.general_settings.channel_name[0];
+    /// 作ったチャンネルを順に channels に加える
+    async fn broadcast_channels(
+        &mut self,
+        backend: &dyn PeerCastBackend,
+        yp_configs: &[YPConfig],
+        settings: &Settings,
+        rtmp_conn_port: NonZeroU16,
+        channels: &mut Vec<BroadcastChannel>,
+    ) -> Result<(), Failure> {
+        // NOTE: 対応していない機能を RPC のエラーで知るより先に、分かりやすいエラーにする
+        let capabilities = backend.capabilities().await?;
+        capabilities.validate(
+            &settings.yellow_pages_settings,
+            settings.general_settings.content_type,
+        )?;
+        self.capabilities = Some(capabilities.clone());
+        let announcements = &settings.yellow_pages_settings.announcements;
+        let yp_ids = backend.prepare_yellow_pages(announcements).await?;
+        let has_ipv4 = includes_ipv4(announcements);
+        for (announcement, yp_id) in announcements.iter().zip(yp_ids) {
+            let source = match channels.first() {
+                Some(primary) => loopback(&primary.id),
+                None => backend.source(rtmp_conn_port),
+            };
+            let id = self
+                .broadcast_channel(
+                    backend,
+                    &capabilities,
+                    yp_configs,
+                    settings,
+                    announcement,
+                    &announcement.settings,
+                    has_ipv4,
+                    yp_id,
+                    &source,
+                )
+                .await?;
+            channels.push(BroadcastChannel {
+                id,
+                announcement: announcement.clone(),
+            });
+        }
+        Ok(())
+    }
+
+    /// PeerCast に接続せずに、配信を開始した時にそれぞれの YP のチャンネルに送る内容を作る
+    pub async fn preview(&self, yp_configs: &[YPConfig], settings: &Settings) -> BroadcastPreview {
+        let general_settings = &settings.general_settings;
+        let endpoint = PeerCastEndpoint::new(general_settings);
+        let backend = (self.backend_factory)(general_settings.peer_cast_type, &endpoint);
+        // NOTE: 配信元の種類は PeerCast の種類で決まるので、ポート番号が決まっていなくても取得する
+        let rtmp_conn_port = configured_rtmp_port(general_settings).await;
+        let (source_type, source_uri) = backend.source(rtmp_conn_port.unwrap_or(NonZeroU16::MAX));
+        let source_uri = rtmp_conn_port.map(|_| source_uri);
         let base_genre = &settings.channel_settings.genre;
+        let channel_settings = self.expand_channel_settings(&settings.channel_settings);
+        let content_type = general_settings.content_type;
+        let announcements = &settings.yellow_pages_settings.announcements;
+        let has_ipv4 = includes_ipv4(announcements);
+        let channels = announcements
+            .iter()
+            .enumerate()
+            .map(|(i, announcement)| {
+                let yp_config = yp_configs
+                    .iter()
+                    .find(|x| x.host == announcement.settings.host);
+                let name = channel_name(
+                    &general_settings.channel_name[0],
+                    announcement.network_type,
+                    has_ipv4,
+                );
+                let genre = yp_config.map_or_else(
+                    || base_genre.clone(),
+                    |x| x.genre_full_text(base_genre, &announcement.settings),
+                );
+                let info = info(
+                    &name,
+                    &genre,
+                    &channel_settings,
+                    self.info_bitrate(),
+                    content_type,
+                );
+                let (source_type, source_uri) = if i == 0 {
+                    (source_type, source_uri.clone())
+                } else {
+                    (loopback("").0, None)
+                };
+                ChannelPreview {
+                    host: announcement.settings.host.clone(),
+                    yp_name: yp_config
+                        .map_or_else(|| announcement.settings.host.clone(), |x| x.name.clone()),
+                    network_type: announcement.network_type,
+                    name: info.name.to_owned(),
+                    genre: info.genre.to_owned(),
+                    desc: info.desc.to_owned(),
+                    comment: info.comment.to_owned(),
+                    contact_url: info.url.to_owned(),
+                    mime_type: info.mime_type.to_owned(),
+                    source_type: source_type.to_owned(),
+                    source_uri,
+                    problems: channel_problems(yp_configs, settings, announcement),
+                }
+            })
+            .collect();
+        BroadcastPreview {
+            channels,
+            problems: common_problems(yp_configs, settings),
+        }
+    }
+
+    /// チャンネルを 1 つ作り、その ID を返す
+    #[allow(clippy::too_many_arguments)]
+    async fn broadcast_channel(
+        &self,
+        backend: &dyn PeerCastBackend,
+        capabilities: &Capabilities,
+        yp_configs: &[YPConfig],
+        settings: &Settings,
+        announcement: &YPAnnouncement,
+        yp_settings: &EachYellowPagesSettings,
+        has_ipv4: bool,
+        yp_id: i32,
+        source: &(&'static str, String),
+    ) -> Result<String, Failure> {
+        let channel_settings = self.expand_channel_settings(&settings.channel_settings);
+        let channel_name = channel_name(
+            &settings.general_settings.channel_name[0],
+            announcement.network_type,
+            has_ipv4,
+        );
+        let genre = genre(yp_configs, yp_settings, &settings.channel_settings.genre)?;
+        let content_type = settings.general_settings.content_type;
+        let content_reader = capabilities.content_reader(content_type.mime_type())?;
+        let info = info(
+            &channel_name,
+            &genre,
+            &channel_settings,
+            self.info_bitrate(),
+            content_type,
+        );
+        let track = track(&settings.channel_settings, self.now_playing.as_ref());
+        backend
+            .broadcast(
+                yp_id,
+                source,
+                announcement.network_type.as_str(),
+                content_reader,
+                &info,
+                &track,
+            )
+            .await
+    }
 
-        if let Some(ipv6_yp_id) = ipv6_yp_id {
-            let stream = rtmp_source(rtmp_conn_port);
-            let ipv6_channel_name = &ipv6_channel_name(ipv4_channel_name, &ipv4_yp_id) as &str;
-            let genre = genre(yp_configs, &settings.yellow_pages_settings.ipv6, base_genre);
-            let info = info(ipv6_channel_name, &genre, &settings.channel_settings);
-            self.ipv6_id = Some(broadcast(&adapter, ipv6_yp_id, &stream, "ipv6", &info).await?);
+    /// 配信中のチャンネルのうち、YP の index.txt で掲載を確認できるもの
+    pub fn expected_listings(
+        &self,
+        yp_configs: &[YPConfig],
+        settings: &Settings,
+    ) -> Vec<ExpectedListing> {
+        let base_genre = &settings.channel_settings.genre;
+        self.channels
+            .iter()
+            .filter_map(|channel| {
+                let yp_config = yp_configs.iter().find(|x| x.host == channel.host())?;
+                Some(ExpectedListing {
+                    yp_name: yp_config.name.clone(),
+                    index_txt_url: yp_config.index_txt_url.clone()?,
+                    channel_id: channel.id.clone(),
+                    genre: yp_config.genre_full_text(base_genre, channel.yp_settings(settings)),
+                })
+            })
+            .collect()
+    }
+
+    /// 正常でないチャンネルを、掲載先の YP のホストと一緒に返す
+    pub async fn check_health(
+        &self,
+        endpoint: &PeerCastEndpoint,
+    ) -> Result<Vec<(String, ChannelHealth)>, Failure> {
+        let backend = (self.backend_factory)(self.peer_cast_type, endpoint);
+        let mut unhealthy = Vec::new();
+        for channel in &self.channels {
+            let health = backend.channel_health(&channel.id).await?;
+            if health != ChannelHealth::Healthy {
+                unhealthy.push((channel.host().to_owned(), health));
+            }
+        }
+        Ok(unhealthy)
+    }
+
+    /// 指定した YP のチャンネルを終了し、同じ情報で作り直す。
+    /// 他のチャンネルは先頭のチャンネルを配信元にしているので、先頭のチャンネルを作り直す場合は全て作り直す。
+    /// 配信元から直接受け取るチャンネルを作り直した場合は true を返す
+    pub async fn rebroadcast(
+        &mut self,
+        yp_configs: &[YPConfig],
+        settings: &Settings,
+        rtmp_conn_port: NonZeroU16,
+        hosts: &[String],
+    ) -> Result<bool, Failure> {
+        let endpoint = PeerCastEndpoint::new(&settings.general_settings);
+        let backend = (self.backend_factory)(self.peer_cast_type, &endpoint);
+        let primary = self
+            .channels
+            .first()
+            .is_some_and(|x| hosts.iter().any(|host| host == x.host()));
+        let targets: Vec<_> = (0..self.channels.len())
+            .filter(|&i| primary || hosts.iter().any(|host| host == self.channels[i].host()))
+            .collect();
+        // NOTE: 作り直しに失敗しても次の確認で再び作り直せるよう、ID は作り直すまで残す
+        for &i in &targets {
+            if let Err(err) = backend.stop(&self.channels[i].id).await {
+                // 既に無くなっているチャンネルの終了には失敗することがある
+                log::warn!("{:?}", err);
+            }
         }
-        if let Some(ipv4_yp_id) = ipv4_yp_id {
-            let stream = if let Some(ipv6_id) = &self.ipv6_id {
-                loopback(ipv6_id)
+
+        // NOTE: YP の設定が消えていれば登録し直す
+        let announcements: Vec<_> = targets
+            .iter()
+            .map(|&i| self.channels[i].announcement.clone())
+            .collect();
+        let yp_ids = backend.prepare_yellow_pages(&announcements).await?;
+        let capabilities = match &self.capabilities {
+            Some(capabilities) => capabilities.clone(),
+            None => backend.capabilities().await?,
+        };
+        let has_ipv4 = includes_ipv4(self.channels.iter().map(|x| &x.announcement));
+        for (i, yp_id) in targets.into_iter().zip(yp_ids) {
+            let source = if i == 0 {
+                backend.source(rtmp_conn_port)
             } else {
-                rtmp_source(rtmp_conn_port)
+                loopback(&self.channels[0].id)
             };
-            let genre = genre(yp_configs, &settings.yellow_pages_settings.ipv4, base_genre);
-            let info = info(ipv4_channel_name, &genre, &settings.channel_settings);
-            self.ipv4_id = Some(broadcast(&adapter, ipv4_yp_id, &stream, "ipv4", &info).await?);
+            let channel = &self.channels[i];
+            let id = self
+                .broadcast_channel(
+                    &*backend,
+                    &capabilities,
+                    yp_configs,
+                    settings,
+                    &channel.announcement,
+                    channel.yp_settings(settings),
+                    has_ipv4,
+                    yp_id,
+                    &source,
+                )
+                .await?;
+            self.channels[i].id = id;
         }
-        Ok(rtmp_conn_port)
+        Ok(primary)
     }
 
     pub async fn update(
@@ -170,61 +508,520 @@ impl Broadcasting {
         yp_configs: &[YPConfig],
         settings: &Settings,
     ) -> Result<(), Failure> {
-        let adapter = PeCaStAdapter::new(settings.general_settings.peer_cast_port);
-        let ipv4_channel_name = &settings.general_settings.channel_name[0];
+        validate_announcements(
+            yp_configs,
+            settings,
+            self.channels.iter().map(|x| &x.announcement),
+        )?;
+        let endpoint = PeerCastEndpoint::new(&settings.general_settings);
+        let backend = (self.backend_factory)(self.peer_cast_type, &endpoint);
         let base_genre = &settings.channel_settings.genre;
-        try_join!(
-            async {
-                if let Some(yp_id) = &self.ipv6_id {
-                    let ipv6_channel_name = &ipv6_channel_name(ipv4_channel_name, &self.ipv4_id);
-                    let genre = genre(yp_configs, &settings.yellow_pages_settings.ipv6, base_genre);
-                    let info = info(ipv6_channel_name, &genre, &settings.channel_settings);
-                    adapter.set_channel_info(yp_id, &info, &EMPTY_TRACK).await?;
-                }
-                Ok(())
-            },
-            async {
-                if let Some(yp_id) = &self.ipv4_id {
-                    let genre = genre(yp_configs, &settings.yellow_pages_settings.ipv4, base_genre);
-                    let info = info(ipv4_channel_name, &genre, &settings.channel_settings);
-                    adapter.set_channel_info(yp_id, &info, &EMPTY_TRACK).await?;
-                }
-                Ok(())
+        let channel_settings = self.expand_channel_settings(&settings.channel_settings);
+        let bitrate = self.info_bitrate();
+        let content_type = settings.general_settings.content_type;
+        let track = track(&settings.channel_settings, self.now_playing.as_ref());
+        let has_ipv4 = includes_ipv4(self.channels.iter().map(|x| &x.announcement));
+        try_join_all(self.channels.iter().map(|channel| {
+            let channel_name = channel_name(
+                &settings.general_settings.channel_name[0],
+                channel.announcement.network_type,
+                has_ipv4,
+            );
+            let genre = genre(yp_configs, channel.yp_settings(settings), base_genre);
+            let backend = &backend;
+            let channel_settings = &channel_settings;
+            let track = &track;
+            async move {
+                let genre = genre?;
+                let info = info(
+                    &channel_name,
+                    &genre,
+                    channel_settings,
+                    bitrate,
+                    content_type,
+                );
+                backend.update(&channel.id, &info, track).await
             }
-        )?;
+        }))
+        .await?;
         Ok(())
     }
 
-    pub async fn stop(&mut self, port: NonZeroU16) -> Result<(), Failure> {
-        try_join!(
-            async {
-                log::trace!("stop ipv6");
-                if let Some(yp_id) = &self.ipv6_id {
-                    PeCaStAdapter::new(port).stop_channel(yp_id).await?;
-                    self.ipv6_id = None;
-                }
-                log::trace!("stop ipv6 done");
-                Ok(())
-            },
-            async {
-                log::trace!("stop ipv4");
-                if let Some(yp_id) = &self.ipv4_id {
-                    let res = PeCaStAdapter::new(port).stop_channel(yp_id).await;
-                    match &res {
-                        Ok(_) => {}
-                        Err(Failure::Error(message)) => {
-                            if message != "Channel not found" {
-                                return res;
-                            }
-                        }
-                        Err(_) => return res,
-                    }
-                    self.ipv4_id = None;
-                }
-                log::trace!("stop ipv4 done");
-                Ok(())
+    /// 終了できなかったチャンネルは残す
+    pub async fn stop(&mut self, endpoint: &PeerCastEndpoint) -> Result<(), Failure> {
+        self.bitrate = None;
+        self.stream_properties = Default::default();
+        self.now_playing = None;
+        self.capabilities = None;
+        let backend = (self.backend_factory)(self.peer_cast_type, endpoint);
+        let results = join_all(self.channels.iter().map(|channel| {
+            let backend = &backend;
+            async move {
+                log::trace!("stop {}", channel.host());
+                let result = backend.stop(&channel.id).await;
+                log::trace!("stop {} done", channel.host());
+                result
             }
-        )?;
-        Ok(())
+        }))
+        .await;
+        let mut first_error = None;
+        let mut results = results.into_iter();
+        self.channels.retain(|_| match results.next().unwrap() {
+            Ok(()) => false,
+            Err(err) => {
+                first_error.get_or_insert(err);
+                true
+            }
+        });
+        first_error.map_or(Ok(()), Err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        core::entities::settings::{
+            ContentType, EachYellowPagesSettings, NetworkType, YPAnnouncement,
+        },
+        features::peercast::simulated_backend::{
+            SimulatedBackend, SimulatedCall, SimulatedOperation,
+        },
+    };
+
+    use super::*;
+
+    fn yp_configs() -> Vec<YPConfig> {
+        ["yp4", "yp6"]
+            .into_iter()
+            .map(|host| YPConfig {
+                host: host.to_owned(),
+                support_ipv6: host == "yp6",
+                ..Default::default()
+            })
+            .collect()
+    }
+
+    /// IPv6 の YP を先に並べる
+    fn settings(ipv4: bool, ipv6: bool) -> Settings {
+        let mut settings = Settings::default();
+        settings.general_settings.channel_name = vec!["ch".to_owned()];
+        settings.general_settings.peer_cast_rtmp_port = 9999;
+        settings
+            .yellow_pages_settings
+            .agreed_terms
+            .insert(String::new(), String::new());
+        let announcement = |network_type, host: &str| YPAnnouncement {
+            network_type,
+            settings: EachYellowPagesSettings {
+                host: host.to_owned(),
+                ..Default::default()
+            },
+        };
+        let announcements = &mut settings.yellow_pages_settings.announcements;
+        if ipv6 {
+            announcements.push(announcement(NetworkType::Ipv6, "yp6"));
+        }
+        if ipv4 {
+            announcements.push(announcement(NetworkType::Ipv4, "yp4"));
+        }
+        settings
+    }
+
+    fn endpoint() -> PeerCastEndpoint {
+        PeerCastEndpoint::new(&settings(true, true).general_settings)
+    }
+
+    fn broadcast_call(yp_id: i32, source: &str, network_type: &str, name: &str) -> SimulatedCall {
+        SimulatedCall::Broadcast {
+            yp_id,
+            source: source.to_owned(),
+            network_type: network_type.to_owned(),
+            content_reader: "FLV".to_owned(),
+            name: name.to_owned(),
+        }
+    }
+
+    const RTMP_CONN_PORT: NonZeroU16 = NonZeroU16::new(9999).unwrap();
+    const RTMP_SOURCE: &str = "rtmp://localhost:9999/live/livestream";
+
+    #[tokio::test]
+    async fn broadcast_ipv6_first_then_loopback_ipv4() {
+        let backend = SimulatedBackend::new();
+        let mut broadcasting = Broadcasting::new(backend.factory());
+        let settings = settings(true, true);
+
+        let port = rtmp_conn_port(&settings.general_settings).await;
+        assert_eq!(port, RTMP_CONN_PORT);
+        broadcasting
+            .broadcast(&yp_configs(), &settings, port)
+            .await
+            .unwrap();
+        let ipv6 = broadcasting.channels()[0].id.clone();
+        assert_eq!(broadcasting.channels()[0].host(), "yp6");
+        assert_eq!(broadcasting.channels()[1].host(), "yp4");
+        assert_eq!(
+            backend.calls(),
+            vec![
+                SimulatedCall::Capabilities,
+                SimulatedCall::PrepareYellowPages,
+                broadcast_call(1, RTMP_SOURCE, "ipv6", "ch (IPv6)"),
+                broadcast_call(2, &format!("loopback:{}", ipv6), "ipv4", "ch"),
+            ]
+        );
+        assert_eq!(backend.channels().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn broadcast_single_stack() {
+        for (ipv4, ipv6, host, network_type) in
+            [(true, false, "yp4", "ipv4"), (false, true, "yp6", "ipv6")]
+        {
+            let backend = SimulatedBackend::new();
+            let mut broadcasting = Broadcasting::new(backend.factory());
+            let settings = settings(ipv4, ipv6);
+            broadcasting
+                .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+                .await
+                .unwrap();
+            assert_eq!(broadcasting.channels().len(), 1);
+            assert_eq!(broadcasting.channels()[0].host(), host);
+            assert_eq!(
+                backend.calls()[2],
+                broadcast_call(1, RTMP_SOURCE, network_type, "ch")
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn update_and_stop() {
+        let backend = SimulatedBackend::new();
+        let mut broadcasting = Broadcasting::new(backend.factory());
+        let mut settings = settings(true, true);
+        broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await
+            .unwrap();
+        let ids: Vec<_> = broadcasting
+            .channel_ids()
+            .into_iter()
+            .map(|x| x.to_owned())
+            .collect();
+
+        backend.clear_calls();
+        settings.general_settings.channel_name = vec!["renamed".to_owned()];
+        settings.channel_settings.track_name = vec!["track".to_owned()];
+        broadcasting.update(&yp_configs(), &settings).await.unwrap();
+        let mut calls = backend.calls();
+        calls.sort_by(|a, b| format!("{:?}", a).cmp(&format!("{:?}", b)));
+        assert_eq!(
+            calls,
+            vec![
+                SimulatedCall::Update {
+                    channel_id: ids[0].clone(),
+                    name: "renamed (IPv6)".to_owned(),
+                    track_name: "track".to_owned(),
+                },
+                SimulatedCall::Update {
+                    channel_id: ids[1].clone(),
+                    name: "renamed".to_owned(),
+                    track_name: "track".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(backend.channels()[0].1, "renamed (IPv6)");
+
+        broadcasting.stop(&endpoint()).await.unwrap();
+        assert!(!broadcasting.is_broadcasting());
+        assert!(backend.channels().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rebroadcast() {
+        let backend = SimulatedBackend::new();
+        let mut broadcasting = Broadcasting::new(backend.factory());
+        let settings = settings(true, true);
+        let port = RTMP_CONN_PORT;
+        broadcasting
+            .broadcast(&yp_configs(), &settings, port)
+            .await
+            .unwrap();
+        assert!(
+            broadcasting
+                .check_health(&endpoint())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        let ipv6 = broadcasting.channels()[0].id.clone();
+        let ipv4 = broadcasting.channels()[1].id.clone();
+
+        // IPv4 のチャンネルだけを作り直す
+        backend.set_channel_health(&ipv4, ChannelHealth::NotAnnounced);
+        assert_eq!(
+            broadcasting.check_health(&endpoint()).await.unwrap(),
+            vec![("yp4".to_owned(), ChannelHealth::NotAnnounced)]
+        );
+        backend.clear_calls();
+        let hosts = ["yp4".to_owned()];
+        let primary = broadcasting
+            .rebroadcast(&yp_configs(), &settings, port, &hosts)
+            .await
+            .unwrap();
+        assert!(!primary);
+        assert_eq!(broadcasting.channels()[0].id, ipv6);
+        assert_eq!(
+            backend.calls(),
+            vec![
+                SimulatedCall::Stop {
+                    channel_id: ipv4.clone()
+                },
+                SimulatedCall::PrepareYellowPages,
+                broadcast_call(1, &format!("loopback:{}", ipv6), "ipv4", "ch"),
+            ]
+        );
+        assert!(
+            broadcasting
+                .check_health(&endpoint())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+
+        // 先頭のチャンネルが無くなった場合は全て作り直す
+        backend.drop_channel(&ipv6);
+        assert_eq!(
+            broadcasting.check_health(&endpoint()).await.unwrap(),
+            vec![("yp6".to_owned(), ChannelHealth::Missing)]
+        );
+        backend.clear_calls();
+        let hosts = ["yp6".to_owned()];
+        let primary = broadcasting
+            .rebroadcast(&yp_configs(), &settings, port, &hosts)
+            .await
+            .unwrap();
+        assert!(primary);
+        let ipv6 = broadcasting.channels()[0].id.clone();
+        let calls = backend.calls();
+        assert_eq!(
+            calls[3],
+            broadcast_call(1, RTMP_SOURCE, "ipv6", "ch (IPv6)")
+        );
+        assert_eq!(
+            calls[4],
+            broadcast_call(2, &format!("loopback:{}", ipv6), "ipv4", "ch")
+        );
+        assert_eq!(backend.channels().len(), 2);
+
+        // 失敗しても ID を残し、次の確認で作り直せる
+        backend.drop_channel(&ipv6);
+        backend.fail_next(SimulatedOperation::Broadcast, Failure::Error("".to_owned()));
+        let result = broadcasting
+            .rebroadcast(&yp_configs(), &settings, port, &hosts)
+            .await;
+        assert!(result.is_err());
+        assert_eq!(broadcasting.channels()[0].id, ipv6);
+        assert!(
+            !broadcasting
+                .check_health(&endpoint())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+        broadcasting
+            .rebroadcast(&yp_configs(), &settings, port, &hosts)
+            .await
+            .unwrap();
+        assert!(
+            broadcasting
+                .check_health(&endpoint())
+                .await
+                .unwrap()
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn broadcast_failures() {
+        let backend = SimulatedBackend::new();
+        let mut broadcasting = Broadcasting::new(backend.factory());
+        let settings = settings(true, true);
+
+        backend.fail_next(
+            SimulatedOperation::Capabilities,
+            Failure::Fatal("".to_owned()),
+        );
+        let result = broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        assert_eq!(backend.calls(), vec![SimulatedCall::Capabilities]);
+
+        backend.clear_calls();
+        backend.fail_next(
+            SimulatedOperation::PrepareYellowPages,
+            Failure::Error("".to_owned()),
+        );
+        let result = broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        assert_eq!(backend.calls().len(), 2);
+        assert!(!broadcasting.is_broadcasting());
+
+        // NOTE: RTMP の配信元から MKV や WebM のチャンネルは作れない
+        backend.clear_calls();
+        let mut mkv = settings.clone();
+        mkv.general_settings.content_type = ContentType::Mkv;
+        let result = broadcasting
+            .broadcast(&yp_configs(), &mkv, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        assert_eq!(backend.calls(), vec![SimulatedCall::Capabilities]);
+
+        backend.set_capabilities(Capabilities {
+            network_type: false,
+            ..Capabilities::original()
+        });
+        let result = broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        assert!(backend.channels().is_empty());
+    }
+
+    #[tokio::test]
+    async fn broadcast_cleans_up_partial_channels() {
+        let backend = SimulatedBackend::new();
+        let mut broadcasting = Broadcasting::new(backend.factory());
+        let settings = settings(true, true);
+
+        // IPv4 のチャンネルを作れなければ、作った IPv6 のチャンネルを終了する
+        backend.fail_after(
+            SimulatedOperation::Broadcast,
+            1,
+            Failure::Error("".to_owned()),
+        );
+        let result = broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        assert!(!broadcasting.is_broadcasting());
+        assert!(backend.channels().is_empty());
+
+        // 終了できなかったチャンネルは残り、stop するまで配信を始めない
+        backend.fail_after(
+            SimulatedOperation::Broadcast,
+            1,
+            Failure::Error("".to_owned()),
+        );
+        backend.fail_next(SimulatedOperation::Stop, Failure::Error("".to_owned()));
+        let result = broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        assert_eq!(broadcasting.channels().len(), 1);
+        backend.clear_calls();
+        let result = broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        assert!(backend.calls().is_empty());
+        broadcasting.stop(&endpoint()).await.unwrap();
+        assert!(backend.channels().is_empty());
+        broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await
+            .unwrap();
+        let ipv6 = &broadcasting.channels()[0].id;
+        assert_eq!(
+            backend.calls().last().unwrap(),
+            &broadcast_call(2, &format!("loopback:{}", ipv6), "ipv4", "ch")
+        );
+    }
+
+    #[tokio::test]
+    async fn broadcast_remote_peer_cast() {
+        let backend = SimulatedBackend::new();
+        let mut broadcasting = Broadcasting::new(backend.factory());
+        let mut settings = settings(true, false);
+        settings.general_settings.peer_cast_host = "192.0.2.1".to_owned();
+
+        // NOTE: 別の PC で空いているポート番号は調べられない
+        settings.general_settings.peer_cast_rtmp_port = 0;
+        let result = broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        settings.general_settings.peer_cast_type = PeerCastType::PeerCastOriginal;
+        settings.general_settings.peer_cast_rtmp_port = 9999;
+        let result = broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        assert!(backend.calls().is_empty());
+
+        settings.general_settings.peer_cast_type = PeerCastType::PeerCastStation;
+        broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await
+            .unwrap();
+        assert_eq!(backend.channels().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn broadcast_invalid_announcements() {
+        let backend = SimulatedBackend::new();
+        let mut broadcasting = Broadcasting::new(backend.factory());
+        let settings = settings(true, false);
+        let mut unagreed = settings.clone();
+        unagreed.yellow_pages_settings.agreed_terms.clear();
+        let result = broadcasting
+            .broadcast(&yp_configs(), &unagreed, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        let result = broadcasting
+            .broadcast(&yp_configs()[1..], &settings, RTMP_CONN_PORT)
+            .await;
+        assert!(result.is_err());
+        assert!(backend.calls().is_empty());
+
+        broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await
+            .unwrap();
+        let calls = backend.calls().len();
+        let result = broadcasting.update(&yp_configs()[1..], &settings).await;
+        assert!(result.is_err());
+        assert_eq!(backend.calls().len(), calls);
+    }
+
+    #[tokio::test]
+    async fn update_and_stop_failures() {
+        let backend = SimulatedBackend::new();
+        let mut broadcasting = Broadcasting::new(backend.factory());
+        let settings = settings(true, true);
+        broadcasting
+            .broadcast(&yp_configs(), &settings, RTMP_CONN_PORT)
+            .await
+            .unwrap();
+
+        backend.fail_next(SimulatedOperation::Update, Failure::Error("".to_owned()));
+        let result = broadcasting.update(&yp_configs(), &settings).await;
+        assert!(result.is_err());
+
+        backend.fail_next(
+            SimulatedOperation::ChannelHealth,
+            Failure::Error("".to_owned()),
+        );
+        assert!(broadcasting.check_health(&endpoint()).await.is_err());
+
+        // NOTE: 終了できなかったチャンネルは残し、もう一度終了できる
+        backend.fail_next(SimulatedOperation::Stop, Failure::Error("".to_owned()));
+        assert!(broadcasting.stop(&endpoint()).await.is_err());
+        assert!(broadcasting.is_broadcasting());
+        assert_eq!(backend.channels().len(), 1);
+        broadcasting.stop(&endpoint()).await.unwrap();
+        assert!(!broadcasting.is_broadcasting());
+        assert!(backend.channels().is_empty());
     }
 }
@@ -0,0 +1,154 @@
+use std::time::Duration;
+
+use versions::Version;
+
+use crate::core::{
+    entities::settings::{ContentType, YellowPagesSettings},
+    utils::failure::Failure,
+};
+
+use super::pecast_rpc_types::{ContentReader, Plugin};
+
+/// NOTE: networkType 付きの broadcastChannel (IPv6 対応) は 2.0.0.0 から
+const STATION_MINIMUM_VERSION: &str = "2.0.0.0";
+/// NOTE: If the channel is deleted within 3 seconds of the stream closed,
+///       a tcp listener on PeerCastStation will remain.
+///       https://github.com/kumaryu/peercaststation/issues/490
+const STATION_STOP_DELAY_FIXED_VERSION: &str = "3.1.0.0";
+const STATION_STOP_DELAY: Duration = Duration::from_secs(6);
+
+/// 接続先の PeerCast が対応している機能
+#[derive(Clone, Debug)]
+pub struct Capabilities {
+    /// PeerCastStation/3.1.0.0 のような形式
+    pub agent_name: String,
+    /// 配信元が切断してからチャンネルを終了するまでに待つ時間
+    pub stop_delay: Duration,
+    /// IPv6 の YP に掲載できる
+    pub network_type: bool,
+    /// RTMP の配信元を受け取れる。本家由来の PeerCast は HTTP で受け取るため true とする
+    pub rtmp_source: bool,
+    /// コンテントリーダーの名前。None の場合は確認しない
+    pub content_readers: Option<Vec<String>>,
+    /// 対応していないバージョンの場合の警告
+    pub unsupported: Option<String>,
+}
+
+impl Capabilities {
+    /// 本家由来の PeerCast はバージョンを取得できないため、対応しているものとして扱う
+    pub fn original() -> Self {
+        Self {
+            agent_name: "PeerCast".to_owned(),
+            stop_delay: Duration::ZERO,
+            network_type: false,
+            rtmp_source: true,
+            content_readers: None,
+            unsupported: None,
+        }
+    }
+
+    /// 対応していないバージョンの場合は他の値を調べずに返す
+    pub fn unsupported_station(agent_name: &str) -> Option<Self> {
+        let unsupported = match station_version(agent_name) {
+            None => format!(
+                "PeerCastStation のバージョンを判別できません。({})",
+                agent_name
+            ),
+            Some(version) if version < Version::new(STATION_MINIMUM_VERSION).unwrap() => format!(
+                "{} には対応していません。PeerCastStation {} 以降を使ってください。",
+                agent_name, STATION_MINIMUM_VERSION
+            ),
+            Some(_) => return None,
+        };
+        Some(Self {
+            agent_name: agent_name.to_owned(),
+            stop_delay: STATION_STOP_DELAY,
+            network_type: false,
+            rtmp_source: false,
+            content_readers: None,
+            unsupported: Some(unsupported),
+        })
+    }
+
+    pub fn station(
+        agent_name: &str,
+        plugins: &[Plugin],
+        content_readers: Vec<ContentReader>,
+    ) -> Self {
+        if let Some(zelf) = Self::unsupported_station(agent_name) {
+            return zelf;
+        }
+        let version = station_version(agent_name).unwrap();
+        Self {
+            agent_name: agent_name.to_owned(),
+            stop_delay: if version < Version::new(STATION_STOP_DELAY_FIXED_VERSION).unwrap() {
+                STATION_STOP_DELAY
+            } else {
+                Duration::ZERO
+            },
+            network_type: true,
+            rtmp_source: plugins
+                .iter()
+                .any(|x| x.is_usable && x.name.to_uppercase().contains("RTMP")),
+            content_readers: Some(content_readers.into_iter().map(|x| x.name).collect()),
+            unsupported: None,
+        }
+    }
+
+    /// 名前に mime_type を含むコンテントリーダーを探す。
+    /// 例えば MKV と WEBM はどちらも Matroska (MKV or WebM) になる。
+    /// 確認しない場合は mime_type をそのまま返す
+    pub fn content_reader<'a>(&'a self, mime_type: &'a str) -> Result<&'a str, Failure> {
+        let Some(content_readers) = &self.content_readers else {
+            return Ok(mime_type);
+        };
+        content_readers
+            .iter()
+            .find(|name| name.to_uppercase().contains(mime_type))
+            .map(|name| name.as_str())
+            .ok_or_else(|| {
+                Failure::Error(format!(
+                    "{} が {} に対応していません。",
+                    self.agent_name, mime_type
+                ))
+            })
+    }
+
+    /// チャンネルを作る前に、設定が使える機能だけで配信できるか確かめる
+    pub fn validate(
+        &self,
+        yp_settings: &YellowPagesSettings,
+        content_type: ContentType,
+    ) -> Result<(), Failure> {
+        if let Some(unsupported) = &self.unsupported {
+            return Err(Failure::Error(unsupported.clone()));
+        }
+        if !self.rtmp_source {
+            return Err(Failure::Error(format!(
+                "{} で RTMP の配信元が使えません。RTMP のプラグインを確認してください。",
+                self.agent_name
+            )));
+        }
+        if !self.network_type && yp_settings.has_ipv6() {
+            return Err(Failure::Error(format!(
+                "{} は IPv6 の YP への掲載に対応していません。",
+                self.agent_name
+            )));
+        }
+        if !content_type.is_rtmp_compatible() {
+            return Err(Failure::Error(rtmp_incompatible_message(content_type)));
+        }
+        self.content_reader(content_type.mime_type()).map(|_| ())
+    }
+}
+
+pub fn rtmp_incompatible_message(content_type: ContentType) -> String {
+    format!(
+        "配信元は RTMP (FLV) のため、{} のチャンネルは作れません。チャンネルの形式を FLV にしてください。",
+        content_type.mime_type()
+    )
+}
+
+fn station_version(agent_name: &str) -> Option<Version> {
+    agent_name.split('/').nth(1).and_then(Version::new)
+}
@@ -1,43 +1,82 @@
-use std::num::NonZeroU16;
+use std::num::{NonZeroU16, NonZeroU32};
 
-use crate::{core::entities::settings::ChannelSettings, features::peercast::pecast_adapter::Info};
+use crate::{
+    core::entities::{
+        now_playing::NowPlaying,
+        settings::{ChannelSettings, ContentType, NetworkType},
+    },
+    features::peercast::{
+        pecast_adapter::{Info, Track},
+        pecast_rpc_types::YellowPage,
+    },
+};
 
-pub fn find_id(yp_list: &[(i32, String)], yp_host: &str) -> Option<i32> {
+pub fn find_id(yp_list: &[YellowPage], yp_host: &str) -> Option<i32> {
     let yp_host_pecast = format!("pcp://{}/", yp_host);
     yp_list
         .iter()
-        .find(|(_, host)| host == &yp_host_pecast)
-        .map(|&(id, _)| id)
+        .find(|yp| yp.uri == yp_host_pecast)
+        .map(|yp| yp.yellow_page_id)
 }
 
 pub fn info<'a>(
     channel_name: &'a str,
     genre: &'a str,
     channel_settings: &'a ChannelSettings,
+    bitrate: Option<NonZeroU32>,
+    content_type: ContentType,
 ) -> Info<'a> {
     Info {
         name: channel_name,
         url: &channel_settings.contact_url[0],
-        bitrate: None,
-        mime_type: "FLV",
+        bitrate,
+        mime_type: content_type.mime_type(),
         genre,
         desc: &channel_settings.desc,
         comment: &channel_settings.comment[0],
     }
 }
 
-pub fn ipv6_channel_name<T>(channel_name: &str, ipv4_id: &Option<T>) -> String {
+/// 再生中の曲が分かっていればそれを使い、無ければ設定の値を使う
+pub fn track<'a>(
+    channel_settings: &'a ChannelSettings,
+    now_playing: Option<&'a NowPlaying>,
+) -> Track<'a> {
+    match now_playing {
+        Some(now_playing) => Track {
+            name: &now_playing.name,
+            creator: &now_playing.creator,
+            genre: "",
+            album: &now_playing.album,
+            url: &now_playing.url,
+        },
+        None => Track {
+            name: &channel_settings.track_name[0],
+            creator: &channel_settings.track_creator[0],
+            genre: "",
+            album: &channel_settings.track_album[0],
+            url: &channel_settings.track_url[0],
+        },
+    }
+}
+
+/// IPv4 のチャンネルと名前が重ならないよう、IPv6 のチャンネルには (IPv6) を付ける
+pub fn channel_name(channel_name: &str, network_type: NetworkType, has_ipv4: bool) -> String {
     format!(
         "{}{}",
         channel_name,
-        if ipv4_id.is_none() { "" } else { " (IPv6)" }
+        if network_type == NetworkType::Ipv6 && has_ipv4 {
+            " (IPv6)"
+        } else {
+            ""
+        }
     )
 }
 
-pub fn rtmp_source(rtmp_conn_port: NonZeroU16) -> (&'static str, String) {
+pub fn rtmp_source(host: &str, rtmp_conn_port: NonZeroU16) -> (&'static str, String) {
     (
         "RTMP Source",
-        format!("rtmp://localhost:{}/live/livestream", rtmp_conn_port),
+        format!("rtmp://{}:{}/live/livestream", host, rtmp_conn_port),
     )
 }
 
@@ -1,4 +1,13 @@
+pub mod broadcast_preview;
 pub mod broadcasting;
+pub mod capabilities;
 mod channel_utils;
 pub mod pecast_adapter;
+pub mod pecast_rpc_types;
+pub mod peercast_backend;
+pub mod peercast_endpoint;
+mod peercast_original_adapter;
+pub mod port_discovery;
+#[cfg(test)]
+pub mod simulated_backend;
 mod view_xml;
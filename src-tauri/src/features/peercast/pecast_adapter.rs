@@ -1,13 +1,26 @@
-use std::num::{NonZeroU16, NonZeroU32};
+use std::{
+    fmt::{self, Display},
+    num::{NonZeroU16, NonZeroU32},
+};
 
+use async_trait::async_trait;
+use futures::future::try_join_all;
 use log::{error, trace};
-use reqwest::Client;
-use serde::Serialize;
-use serde_json::Value;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use serde_json::{Map, Value};
 
-use crate::core::utils::failure::Failure;
+use crate::core::{entities::settings::YPAnnouncement, utils::failure::Failure};
 
-use super::view_xml::ViewXml;
+use super::{
+    capabilities::Capabilities,
+    channel_utils::{find_id, rtmp_source},
+    pecast_rpc_types::{
+        Channel, ChannelDetail, ChannelStatus, ContentReader, Plugin, VersionInfo, YellowPage,
+    },
+    peercast_backend::{ChannelHealth, PeerCastBackend},
+    peercast_endpoint::{PeerCastEndpoint, UNAUTHORIZED_MESSAGE, is_unauthorized},
+    view_xml::ViewXml,
+};
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
@@ -44,58 +57,129 @@ where
     params: Option<T>,
 }
 
-pub struct PeCaStAdapter {
-    port: NonZeroU16,
+#[derive(Debug, Deserialize)]
+struct RpcErrorObject {
+    code: i64,
+    message: String,
 }
 
-impl PeCaStAdapter {
-    pub fn new(port: NonZeroU16) -> Self {
-        Self { port }
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct ChannelIdParams<'a> {
+    channel_id: &'a str,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct YellowPageIdParams {
+    yellow_page_id: i32,
+}
+
+/// PeerCastStation との通信の失敗
+#[derive(Debug)]
+pub enum RpcError {
+    /// 接続できないなど、HTTP の通信に失敗した
+    Transport(reqwest::Error),
+    /// 応答が JSON-RPC の応答として解釈できない
+    Protocol(String),
+    /// PeerCastStation がエラーを返した
+    Rpc { code: i64, message: String },
+    /// 認証情報が間違っている
+    Unauthorized,
+}
+
+impl Display for RpcError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RpcError::Transport(err) => write!(f, "{}", err),
+            RpcError::Protocol(message) => write!(f, "invalid response: {}", message),
+            RpcError::Rpc { code, message } => write!(f, "{} ({})", message, code),
+            RpcError::Unauthorized => write!(f, "unauthorized"),
+        }
     }
+}
 
-    pub async fn get_version_info(&self) -> Result<String, Failure> {
-        let result_json = request_rpc::<()>(self.port, "getVersionInfo", None).await?;
-        Ok(result_json
-            .as_object()
-            .ok_or_else(|| {
-                error!("Result is not object.");
-                Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-            })?
-            .get("agentName")
-            .ok_or_else(|| {
-                error!("agentName is undefined.");
-                Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-            })?
-            .as_str()
-            .ok_or_else(|| {
-                error!("agentName is not string.");
+impl std::error::Error for RpcError {}
+
+impl From<RpcError> for Failure {
+    fn from(err: RpcError) -> Self {
+        match err {
+            RpcError::Transport(err) => Failure::Error(err.to_string()),
+            RpcError::Protocol(_) => {
                 Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-            })?
-            .to_owned())
+            }
+            RpcError::Rpc { message, .. } => Failure::Error(message),
+            RpcError::Unauthorized => Failure::Error(UNAUTHORIZED_MESSAGE.to_owned()),
+        }
     }
+}
 
-    pub async fn get_yellow_pages(&self) -> Result<Vec<(i32, String)>, Failure> {
-        let result_json = request_rpc::<()>(self.port, "getYellowPages", None).await?;
-        let list = result_json.as_array().ok_or_else(|| {
-            error!("Result is not array.");
-            Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
+fn protocol_error(message: impl Display) -> RpcError {
+    error!("{}", message);
+    RpcError::Protocol(message.to_string())
+}
+
+pub struct PeCaStAdapter {
+    endpoint: PeerCastEndpoint,
+}
+
+impl PeCaStAdapter {
+    pub fn new(endpoint: PeerCastEndpoint) -> Self {
+        Self { endpoint }
+    }
+
+    async fn request<P, R>(&self, method: &'static str, params: Option<P>) -> Result<R, RpcError>
+    where
+        P: Serialize,
+        R: DeserializeOwned,
+    {
+        let request = self
+            .endpoint
+            .post("/api/1/")
+            .header("X-Requested-With", "XMLHttpRequest")
+            .json(&Rpc {
+                jsonrpc: "2.0".to_owned(),
+                id: 0,
+                method: method.to_owned(),
+                params,
+            });
+        let res = request.send().await.map_err(|e| {
+            error!("{}", e);
+            RpcError::Transport(e)
         })?;
-        let list: Vec<_> = list
-            .iter()
-            .map(|yp| {
-                let obj = yp.as_object()?;
-                Some((
-                    obj.get("yellowPageId")?.as_i64()? as i32,
-                    obj.get("uri")?.as_str()?.to_owned(),
-                ))
-            })
-            .collect();
-        if list.iter().any(|x| x.is_none()) {
-            return Err(Failure::Fatal(
-                "Failure communicating with PeerCastStation.".to_owned(),
-            ));
+        if is_unauthorized(res.status()) {
+            error!("{}: {}", method, res.status());
+            return Err(RpcError::Unauthorized);
         }
-        Ok(list.into_iter().map(|x| x.unwrap()).collect())
+        let body = res.bytes().await.map_err(|e| {
+            error!("{}", e);
+            RpcError::Transport(e)
+        })?;
+
+        let mut payload: Map<String, Value> =
+            serde_json::from_slice(&body).map_err(protocol_error)?;
+        if let Some(err) = payload.remove("error")
+            && !err.is_null()
+        {
+            trace!("{}: {:?}", method, err);
+            let err: RpcErrorObject = serde_json::from_value(err).map_err(protocol_error)?;
+            return Err(RpcError::Rpc {
+                code: err.code,
+                message: err.message,
+            });
+        }
+        let result = payload
+            .remove("result")
+            .ok_or_else(|| protocol_error(format!("{}: result is undefined", method)))?;
+        serde_json::from_value(result).map_err(|e| protocol_error(format!("{}: {}", method, e)))
+    }
+
+    pub async fn get_version_info(&self) -> Result<VersionInfo, RpcError> {
+        self.request::<(), _>("getVersionInfo", None).await
+    }
+
+    pub async fn get_yellow_pages(&self) -> Result<Vec<YellowPage>, RpcError> {
+        self.request::<(), _>("getYellowPages", None).await
     }
 
     pub async fn add_yellow_page(
@@ -103,51 +187,33 @@ impl PeCaStAdapter {
         protocol: &str,
         name: &str,
         announce_uri: &str,
-    ) -> Result<i32, Failure> {
-        let params = {
-            #[derive(Serialize)]
-            #[serde(rename_all = "camelCase")]
-            struct Params<'a> {
-                protocol: &'a str,
-                name: &'a str,
-                announce_uri: &'a str,
-            }
-            Params {
-                protocol,
-                name,
-                announce_uri,
-            }
+    ) -> Result<i32, RpcError> {
+        #[derive(Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params<'a> {
+            protocol: &'a str,
+            name: &'a str,
+            announce_uri: &'a str,
+        }
+        let params = Params {
+            protocol,
+            name,
+            announce_uri,
         };
-        let result_json = request_rpc(self.port, "addYellowPage", Some(params)).await?;
-
-        (|| result_json.as_object()?.get("yellowPageId")?.as_i64())()
-            .map(|x| x as i32)
-            .ok_or_else(|| {
-                error!("Result is not array.");
-                Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-            })
+        let result: YellowPageIdParams = self.request("addYellowPage", Some(params)).await?;
+        Ok(result.yellow_page_id)
     }
 
-    pub async fn check_ports(&self) -> Result<(), Failure> {
-        request_rpc::<()>(self.port, "checkPorts", None)
-            .await?
-            .as_array()
-            .ok_or_else(|| {
-                error!("Result is not array.");
-                Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-            })?;
-        Ok(())
+    pub async fn check_ports(&self) -> Result<(), RpcError> {
+        self.request::<(), Vec<Value>>("checkPorts", None)
+            .await
+            .map(|_| ())
     }
 
-    pub async fn get_external_ip_addresses(&self) -> Result<(), Failure> {
-        request_rpc::<()>(self.port, "getExternalIPAddresses", None)
-            .await?
-            .as_array()
-            .ok_or_else(|| {
-                error!("Result is not array.");
-                Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-            })?;
-        Ok(())
+    pub async fn get_external_ip_addresses(&self) -> Result<(), RpcError> {
+        self.request::<(), Vec<Value>>("getExternalIPAddresses", None)
+            .await
+            .map(|_| ())
     }
 
     #[allow(clippy::too_many_arguments)]
@@ -160,39 +226,30 @@ impl PeCaStAdapter {
         info: &Info<'a>,
         track: &Track<'a>,
         network_type: &str,
-    ) -> Result<String, Failure> {
-        let params = {
-            #[derive(Debug, Serialize)]
-            #[serde(rename_all = "camelCase")]
-            struct Params<'a> {
-                yellow_page_id: Option<i32>,
-                source_uri: &'a str,
-                source_stream: &'a str,
-                content_reader: &'a str,
-                info: &'a Info<'a>,
-                track: &'a Track<'a>,
-                network_type: &'a str,
-            }
-            Params {
-                yellow_page_id,
-                source_uri,
-                source_stream,
-                content_reader,
-                info,
-                track,
-                network_type,
-            }
+    ) -> Result<String, RpcError> {
+        #[derive(Debug, Serialize)]
+        #[serde(rename_all = "camelCase")]
+        struct Params<'a> {
+            yellow_page_id: Option<i32>,
+            source_uri: &'a str,
+            source_stream: &'a str,
+            content_reader: &'a str,
+            info: &'a Info<'a>,
+            track: &'a Track<'a>,
+            network_type: &'a str,
+        }
+        let params = Params {
+            yellow_page_id,
+            source_uri,
+            source_stream,
+            content_reader,
+            info,
+            track,
+            network_type,
         };
         trace!("broadcast_channel {:?}", params);
 
-        Ok(request_rpc(self.port, "broadcastChannel", Some(params))
-            .await?
-            .as_str()
-            .ok_or_else(|| {
-                error!("Result is not string.");
-                Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-            })?
-            .to_owned())
+        self.request("broadcastChannel", Some(params)).await
     }
 
     pub async fn set_channel_info<'a>(
@@ -200,106 +257,505 @@ impl PeCaStAdapter {
         channel_id: &'a str,
         info: &'a Info<'a>,
         track: &'a Track<'a>,
-    ) -> Result<(), Failure> {
-        let params = {
-            #[derive(Serialize)]
-            #[serde(rename_all = "camelCase")]
-            struct Params<'a> {
-                channel_id: &'a str,
-                info: &'a Info<'a>,
-                track: &'a Track<'a>,
-            }
-            Params {
-                channel_id,
-                info,
-                track,
-            }
-        };
-
-        request_rpc(self.port, "setChannelInfo", Some(params))
-            .await
-            .map(|_| ())
-    }
-
-    pub async fn stop_channel(&self, channel_id: &str) -> Result<(), Failure> {
+    ) -> Result<(), RpcError> {
         #[derive(Serialize)]
         #[serde(rename_all = "camelCase")]
         struct Params<'a> {
             channel_id: &'a str,
+            info: &'a Info<'a>,
+            track: &'a Track<'a>,
         }
-        let params = Params { channel_id };
+        let params = Params {
+            channel_id,
+            info,
+            track,
+        };
 
-        request_rpc(self.port, "stopChannel", Some(params))
+        self.request::<_, Value>("setChannelInfo", Some(params))
             .await
             .map(|_| ())
     }
 
+    pub async fn stop_channel(&self, channel_id: &str) -> Result<(), RpcError> {
+        let params = ChannelIdParams { channel_id };
+        self.request::<_, Value>("stopChannel", Some(params))
+            .await
+            .map(|_| ())
+    }
+
+    pub async fn get_channels(&self) -> Result<Vec<Channel>, RpcError> {
+        self.request::<(), _>("getChannels", None).await
+    }
+
+    pub async fn get_channel_info(&self, channel_id: &str) -> Result<ChannelDetail, RpcError> {
+        let params = ChannelIdParams { channel_id };
+        self.request("getChannelInfo", Some(params)).await
+    }
+
+    pub async fn get_channel_status(&self, channel_id: &str) -> Result<ChannelStatus, RpcError> {
+        let params = ChannelIdParams { channel_id };
+        self.request("getChannelStatus", Some(params)).await
+    }
+
+    pub async fn get_content_readers(&self) -> Result<Vec<ContentReader>, RpcError> {
+        self.request::<(), _>("getContentReaders", None).await
+    }
+
+    pub async fn get_plugins(&self) -> Result<Vec<Plugin>, RpcError> {
+        self.request::<(), _>("getPlugins", None).await
+    }
+
     pub async fn view_xml(&self) -> Result<ViewXml, Failure> {
-        let res = reqwest::get(&format!("http://localhost:{}/admin?cmd=viewxml", self.port))
+        let res = self
+            .endpoint
+            .get("/admin?cmd=viewxml")
+            .send()
             .await
             .map_err(|e| {
                 error!("{}", e);
                 Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
             })?;
+        if is_unauthorized(res.status()) {
+            return Err(Failure::Error(UNAUTHORIZED_MESSAGE.to_owned()));
+        }
         let xml = res.text().await.map_err(|e| {
             error!("{}", e);
             Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
         })?;
 
-        Ok(serde_xml_rs::from_str(&xml).unwrap())
+        ViewXml::parse(&xml)
     }
 }
 
-async fn request_rpc<T>(
-    port: NonZeroU16,
-    method: &'static str,
-    params: Option<T>,
-) -> Result<Value, Failure>
-where
-    T: Serialize,
-{
-    let request = Client::new()
-        .post(format!("http://localhost:{}/api/1/", port))
-        .header("X-Requested-With", "XMLHttpRequest")
-        .json(&Rpc {
-            jsonrpc: "2.0".to_owned(),
-            id: 0,
-            method: method.to_owned(),
-            params,
-        });
-    let response = request.send().await.map_err(|e| {
-        error!("{}", e);
-        Failure::Error(e.to_string())
-    })?;
-
-    let payload = response.json::<Value>().await.map_err(|e| {
-        error!("{}", e);
-        Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-    })?;
-
-    let payload_obj_ref = payload.as_object().ok_or_else(|| {
-        error!("Result is not json object.");
-        Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-    })?;
-    if payload_obj_ref.contains_key("error") {
-        log::trace!("{:?}", payload_obj_ref);
-        return Err(Failure::Error(
-            payload_obj_ref
-                .get("error")
-                .and_then(|x| x.get("message"))
-                .and_then(|x| x.as_str())
-                .ok_or_else(|| {
-                    error!("Result is not json object.");
-                    Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-                })?
-                .to_owned(),
+fn is_channel_not_found(err: &RpcError) -> bool {
+    matches!(err, RpcError::Rpc { message, .. } if message == "Channel not found")
+}
+
+async fn get_or_add_yellow_page(
+    adapter: &PeCaStAdapter,
+    yp_list: &[YellowPage],
+    yp_host: &str,
+) -> Result<i32, Failure> {
+    if let Some(yp_id) = find_id(yp_list, yp_host) {
+        Ok(yp_id)
+    } else {
+        Ok(adapter
+            .add_yellow_page("pcp", yp_host, &format!("pcp://{}", yp_host))
+            .await?)
+    }
+}
+
+#[async_trait]
+impl PeerCastBackend for PeCaStAdapter {
+    async fn prepare_yellow_pages(
+        &self,
+        announcements: &[YPAnnouncement],
+    ) -> Result<Vec<i32>, Failure> {
+        log::trace!("get yp");
+        let yp_list = self.get_yellow_pages().await?;
+        log::trace!("get yp {:?}", yp_list);
+
+        try_join_all(
+            announcements
+                .iter()
+                .map(|x| get_or_add_yellow_page(self, &yp_list, &x.settings.host)),
+        )
+        .await
+    }
+
+    fn source(&self, rtmp_conn_port: NonZeroU16) -> (&'static str, String) {
+        rtmp_source(&self.endpoint.host(), rtmp_conn_port)
+    }
+
+    async fn broadcast(
+        &self,
+        yp_id: i32,
+        (source_stream, source_uri): &(&'static str, String),
+        network_type: &str,
+        content_reader: &str,
+        info: &Info<'_>,
+        track: &Track<'_>,
+    ) -> Result<String, Failure> {
+        // WTF: IPv6 一時アドレスが正しく設定されないケースの対策
+        self.check_ports().await?;
+        self.get_external_ip_addresses().await?;
+        Ok(self
+            .broadcast_channel(
+                Some(yp_id),
+                source_uri,
+                source_stream,
+                content_reader,
+                info,
+                track,
+                network_type,
+            )
+            .await?)
+    }
+
+    async fn update(
+        &self,
+        channel_id: &str,
+        info: &Info<'_>,
+        track: &Track<'_>,
+    ) -> Result<(), Failure> {
+        Ok(self.set_channel_info(channel_id, info, track).await?)
+    }
+
+    async fn stop(&self, channel_id: &str) -> Result<(), Failure> {
+        match self.stop_channel(channel_id).await {
+            Ok(_) => Ok(()),
+            Err(err) if is_channel_not_found(&err) => Ok(()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    /// 対応していないバージョンではプラグインなどの API が無いことがあるため、先にバージョンを調べる
+    async fn capabilities(&self) -> Result<Capabilities, Failure> {
+        let version_info = self.get_version_info().await?;
+        if let Some(capabilities) = Capabilities::unsupported_station(&version_info.agent_name) {
+            return Ok(capabilities);
+        }
+        let (plugins, content_readers) =
+            tokio::try_join!(self.get_plugins(), self.get_content_readers())?;
+        Ok(Capabilities::station(
+            &version_info.agent_name,
+            &plugins,
+            content_readers,
+        ))
+    }
+
+    async fn view_xml(&self) -> Result<ViewXml, Failure> {
+        PeCaStAdapter::view_xml(self).await
+    }
+
+    async fn find_channel_id(&self, channel_name: &str) -> Result<Option<String>, Failure> {
+        Ok(self
+            .get_channels()
+            .await?
+            .into_iter()
+            .find(|x| x.info.name == channel_name)
+            .map(|x| x.channel_id))
+    }
+
+    fn stream_url(&self, channel_id: &str) -> String {
+        self.endpoint.stream_url(channel_id)
+    }
+
+    async fn channel_health(&self, channel_id: &str) -> Result<ChannelHealth, Failure> {
+        let (status, detail) = match tokio::try_join!(
+            self.get_channel_status(channel_id),
+            self.get_channel_info(channel_id)
+        ) {
+            Ok(ok) => ok,
+            Err(err) if is_channel_not_found(&err) => return Ok(ChannelHealth::Missing),
+            Err(err) => return Err(err.into()),
+        };
+        Ok(match status.status.as_str() {
+            "Error" | "Finished" => ChannelHealth::Errored(status.status),
+            _ if detail.yellow_pages.is_empty() => ChannelHealth::NotAnnounced,
+            _ => ChannelHealth::Healthy,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use serde_json::json;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::{TcpListener, TcpStream},
+        spawn,
+    };
+
+    use crate::core::entities::settings::GeneralSettings;
+
+    use super::*;
+
+    /// 受け取ったリクエストの本文
+    type Requests = Arc<Mutex<Vec<Value>>>;
+
+    /// JSON-RPC の代わりに応答する。status と本文を返す
+    type Respond = fn(&str, &Value) -> (u16, String);
+
+    fn result(result: Value) -> (u16, String) {
+        let body = json!({ "jsonrpc": "2.0", "id": 0, "result": result });
+        (200, body.to_string())
+    }
+
+    fn respond(method: &str, params: &Value) -> (u16, String) {
+        match method {
+            "getVersionInfo" => result(json!({
+                "agentName": "PeerCastStation/3.1.0.0",
+                "apiVersion": "1.0.0",
+                "jsonrpc": "2.0",
+            })),
+            "getYellowPages" => result(json!([{
+                "yellowPageId": 1,
+                "name": "yp",
+                "uri": "pcp://yp.example/",
+                "announceUri": "pcp://yp.example/",
+                "channelsUri": null,
+                "protocol": "pcp",
+                "channels": [],
+            }])),
+            "addYellowPage" => result(json!({ "yellowPageId": 7, "name": "n", "protocol": "pcp" })),
+            "broadcastChannel" => result(json!("0123456789ABCDEF")),
+            "getChannels" => result(json!([{
+                "channelId": "ABC",
+                "status": { "status": "Receiving", "totalDirects": 4 },
+                "info": { "name": "ch", "bitrate": 1500 },
+                "yellowPages": [{ "yellowPageId": 1, "name": "yp", "protocol": "pcp" }],
+            }])),
+            "getChannelInfo" if params["channelId"] == "NOYP" => {
+                result(json!({ "info": { "name": "ch" }, "track": {}, "yellowPages": [] }))
+            }
+            "getChannelInfo" => result(json!({
+                "info": { "name": "ch", "bitrate": 1500 },
+                "track": {},
+                "yellowPages": [{ "yellowPageId": 1, "name": "yp", "protocol": "pcp" }],
+            })),
+            "getChannelStatus" if params["channelId"] == "ERROR" => {
+                result(json!({ "status": "Error" }))
+            }
+            "getChannelStatus" => result(json!({
+                "status": "Receiving",
+                "uptime": 5,
+                "totalDirects": 9,
+                "isReceiving": true,
+            })),
+            "getPlugins" => result(json!([{ "name": "RTMP Source", "isUsable": true }])),
+            "getContentReaders" => result(json!([{ "name": "Flash Video (FLV)", "desc": "" }])),
+            "checkPorts" => result(json!([7144])),
+            "getExternalIPAddresses" => result(json!(["192.0.2.1"])),
+            "setChannelInfo" => result(Value::Null),
+            _ => channel_not_found(method, params),
+        }
+    }
+
+    fn channel_not_found(_method: &str, _params: &Value) -> (u16, String) {
+        let error = json!({ "code": -32602, "message": "Channel not found" });
+        (
+            200,
+            json!({ "jsonrpc": "2.0", "id": 0, "error": error }).to_string(),
+        )
+    }
+
+    async fn read_request(stream: &mut TcpStream) -> Value {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 4096];
+        loop {
+            let len = stream.read(&mut chunk).await.unwrap();
+            assert_ne!(len, 0);
+            buf.extend_from_slice(&chunk[..len]);
+            let Some(pos) = buf.windows(4).position(|x| x == b"\r\n\r\n") else {
+                continue;
+            };
+            let head = String::from_utf8_lossy(&buf[..pos]).to_lowercase();
+            let content_length: usize = head
+                .lines()
+                .find_map(|x| x.strip_prefix("content-length:"))
+                .map_or(0, |x| x.trim().parse().unwrap());
+            let body = &buf[pos + 4..];
+            if body.len() >= content_length {
+                return serde_json::from_slice(&body[..content_length]).unwrap();
+            }
+        }
+    }
+
+    async fn serve(listener: TcpListener, respond: Respond, requests: Requests) {
+        loop {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let requests = requests.clone();
+            spawn(async move {
+                let request = read_request(&mut stream).await;
+                let method = request["method"].as_str().unwrap().to_owned();
+                let (status, body) = respond(&method, &request["params"]);
+                requests.lock().unwrap().push(request);
+                let response = format!(
+                    "HTTP/1.1 {} X\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                stream.write_all(response.as_bytes()).await.unwrap();
+            });
+        }
+    }
+
+    fn endpoint(port: u16) -> PeerCastEndpoint {
+        PeerCastEndpoint::new(&GeneralSettings {
+            peer_cast_host: "127.0.0.1".to_owned(),
+            peer_cast_port: NonZeroU16::new(port).unwrap(),
+            ..Default::default()
+        })
+    }
+
+    async fn stand_in(respond: Respond) -> (PeCaStAdapter, Requests) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+        let requests = Requests::default();
+        spawn(serve(listener, respond, requests.clone()));
+        (PeCaStAdapter::new(endpoint(port)), requests)
+    }
+
+    fn last_request(requests: &Requests) -> Value {
+        let request = requests.lock().unwrap().last().unwrap().clone();
+        assert_eq!(request["jsonrpc"], "2.0");
+        request
+    }
+
+    #[tokio::test]
+    async fn typed_responses() {
+        let (adapter, _) = stand_in(respond).await;
+        let version_info = adapter.get_version_info().await.unwrap();
+        assert_eq!(version_info.agent_name, "PeerCastStation/3.1.0.0");
+        let yellow_pages = adapter.get_yellow_pages().await.unwrap();
+        assert_eq!(yellow_pages[0].uri, "pcp://yp.example/");
+        let channels = adapter.get_channels().await.unwrap();
+        assert_eq!(channels[0].channel_id, "ABC");
+        assert_eq!(channels[0].info.name, "ch");
+        let detail = adapter.get_channel_info("ABC").await.unwrap();
+        assert_eq!(detail.yellow_pages.len(), 1);
+        let status = adapter.get_channel_status("ABC").await.unwrap();
+        assert_eq!(status.status, "Receiving");
+        assert!(adapter.get_plugins().await.unwrap()[0].is_usable);
+        assert_eq!(adapter.get_content_readers().await.unwrap().len(), 1);
+        adapter.check_ports().await.unwrap();
+        adapter.get_external_ip_addresses().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn request_bodies() {
+        let (adapter, requests) = stand_in(respond).await;
+
+        let yellow_page_id = adapter
+            .add_yellow_page("pcp", "yp", "pcp://yp.example/")
+            .await
+            .unwrap();
+        assert_eq!(yellow_page_id, 7);
+        let request = last_request(&requests);
+        assert_eq!(request["method"], "addYellowPage");
+        assert_eq!(
+            request["params"],
+            json!({ "protocol": "pcp", "name": "yp", "announceUri": "pcp://yp.example/" })
+        );
+
+        adapter.get_version_info().await.unwrap();
+        assert!(last_request(&requests).get("params").is_none());
+
+        let info = Info {
+            name: "ch",
+            url: "",
+            bitrate: NonZeroU32::new(1500),
+            mime_type: "FLV",
+            genre: "g",
+            desc: "",
+            comment: "",
+        };
+        let track = Track {
+            name: "",
+            creator: "",
+            genre: "",
+            album: "",
+            url: "",
+        };
+        let channel_id = adapter
+            .broadcast_channel(
+                Some(1),
+                "rtmp://localhost:1935/live/livestream",
+                "RTMP Source",
+                "Flash Video (FLV)",
+                &info,
+                &track,
+                "ipv6",
+            )
+            .await
+            .unwrap();
+        assert_eq!(channel_id, "0123456789ABCDEF");
+        let request = last_request(&requests);
+        assert_eq!(request["method"], "broadcastChannel");
+        let params = &request["params"];
+        assert_eq!(params["yellowPageId"], 1);
+        assert_eq!(params["sourceStream"], "RTMP Source");
+        assert_eq!(params["contentReader"], "Flash Video (FLV)");
+        assert_eq!(params["networkType"], "ipv6");
+        assert_eq!(params["info"]["mimeType"], "FLV");
+        assert_eq!(params["info"]["bitrate"], 1500);
+    }
+
+    #[tokio::test]
+    async fn rpc_error() {
+        let (adapter, _) = stand_in(channel_not_found).await;
+        let err = adapter.stop_channel("ABC").await.unwrap_err();
+        assert!(
+            matches!(&err, RpcError::Rpc { code: -32602, message } if message == "Channel not found"),
+            "{:?}",
+            err
+        );
+        // NOTE: 既に無いチャンネルの停止は成功とする
+        PeerCastBackend::stop(&adapter, "ABC").await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn protocol_error() {
+        let (adapter, _) = stand_in(|_, _| (200, "<html></html>".to_owned())).await;
+        let err = adapter.get_channels().await.unwrap_err();
+        assert!(matches!(err, RpcError::Protocol(_)), "{:?}", err);
+
+        let (adapter, _) = stand_in(|_, _| (200, json!({ "jsonrpc": "2.0" }).to_string())).await;
+        let err = adapter.get_channels().await.unwrap_err();
+        assert!(matches!(err, RpcError::Protocol(_)), "{:?}", err);
+
+        let (adapter, _) = stand_in(|_, _| result(json!({ "unexpected": true }))).await;
+        let err = adapter.get_channels().await.unwrap_err();
+        assert!(matches!(err, RpcError::Protocol(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn unauthorized() {
+        let (adapter, _) = stand_in(|_, _| (401, String::new())).await;
+        let err = adapter.get_channels().await.unwrap_err();
+        assert!(matches!(err, RpcError::Unauthorized), "{:?}", err);
+        let (adapter, _) = stand_in(|_, _| (403, String::new())).await;
+        let err = adapter.get_version_info().await.unwrap_err();
+        assert!(matches!(err, RpcError::Unauthorized), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn transport_error() {
+        let port = {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            listener.local_addr().unwrap().port()
+        };
+        let err = PeCaStAdapter::new(endpoint(port))
+            .get_channels()
+            .await
+            .unwrap_err();
+        assert!(matches!(err, RpcError::Transport(_)), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn channel_health() {
+        let (adapter, _) = stand_in(respond).await;
+        let health = |id| adapter.channel_health(id);
+        assert!(matches!(
+            health("ABC").await.unwrap(),
+            ChannelHealth::Healthy
+        ));
+        assert!(matches!(
+            health("NOYP").await.unwrap(),
+            ChannelHealth::NotAnnounced
+        ));
+        assert!(matches!(
+            health("ERROR").await.unwrap(),
+            ChannelHealth::Errored(status) if status == "Error"
+        ));
+        let (adapter, _) = stand_in(channel_not_found).await;
+        assert!(matches!(
+            adapter.channel_health("ABC").await.unwrap(),
+            ChannelHealth::Missing
         ));
     }
-    Ok(payload_obj_ref
-        .get("result")
-        .ok_or_else(|| {
-            error!("Result is not json object.");
-            Failure::Fatal("Failure communicating with PeerCastStation.".to_owned())
-        })?
-        .to_owned())
 }
@@ -0,0 +1,66 @@
+//! PeerCastStation の JSON-RPC (/api/1/) の応答のうち、使う項目だけ
+
+use serde::{Deserialize, de::IgnoredAny};
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct VersionInfo {
+    /// PeerCastStation/3.1.0.0 のような形式
+    pub agent_name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YellowPage {
+    pub yellow_page_id: i32,
+    /// pcp://host/ のような形式
+    #[serde(default)]
+    pub uri: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelInfo {
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, rename_all = "camelCase")]
+pub struct ChannelStatus {
+    /// Idle, Searching, Connecting, Receiving, Error, Finished のいずれか
+    pub status: String,
+}
+
+/// getChannelInfo の応答
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ChannelDetail {
+    /// チャンネルを掲載している YP。有無だけを見る
+    #[serde(default)]
+    pub yellow_pages: Vec<IgnoredAny>,
+}
+
+/// getChannels の応答の要素
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Channel {
+    pub channel_id: String,
+    #[serde(default)]
+    pub info: ChannelInfo,
+}
+
+/// getContentReaders の応答の要素
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ContentReader {
+    /// Flash Video (FLV) のような形式
+    pub name: String,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Plugin {
+    pub name: String,
+    #[serde(default)]
+    pub is_usable: bool,
+}
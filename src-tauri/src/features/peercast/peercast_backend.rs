@@ -0,0 +1,109 @@
+use std::{
+    fmt::{self, Display},
+    num::NonZeroU16,
+    sync::Arc,
+};
+
+use async_trait::async_trait;
+
+use crate::core::{
+    entities::settings::{PeerCastType, YPAnnouncement},
+    utils::failure::Failure,
+};
+
+use super::{
+    capabilities::Capabilities,
+    pecast_adapter::{Info, PeCaStAdapter, Track},
+    peercast_endpoint::PeerCastEndpoint,
+    peercast_original_adapter::PeerCastOriginalAdapter,
+    view_xml::ViewXml,
+};
+
+/// 配信中のチャンネルの状態
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ChannelHealth {
+    Healthy,
+    /// PeerCast にチャンネルが無い
+    Missing,
+    /// チャンネルがエラーになっている
+    Errored(String),
+    /// YP に掲載されていない
+    NotAnnounced,
+}
+
+impl Display for ChannelHealth {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ChannelHealth::Healthy => write!(f, "正常"),
+            ChannelHealth::Missing => write!(f, "チャンネルが無い"),
+            ChannelHealth::Errored(status) => write!(f, "エラー ({})", status),
+            ChannelHealth::NotAnnounced => write!(f, "YP に掲載されていない"),
+        }
+    }
+}
+
+/// Broadcasting と Logger が使う PeerCast の操作
+#[async_trait]
+pub trait PeerCastBackend: Send + Sync {
+    /// announcements と同じ順で YP の ID を返す
+    async fn prepare_yellow_pages(
+        &self,
+        announcements: &[YPAnnouncement],
+    ) -> Result<Vec<i32>, Failure>;
+
+    /// PeerCast が配信を受け取る配信元の種類と URI
+    fn source(&self, rtmp_conn_port: NonZeroU16) -> (&'static str, String);
+
+    /// チャンネル ID を返す。content_reader は Capabilities::content_reader で選んだもの
+    async fn broadcast(
+        &self,
+        yp_id: i32,
+        source: &(&'static str, String),
+        network_type: &str,
+        content_reader: &str,
+        info: &Info<'_>,
+        track: &Track<'_>,
+    ) -> Result<String, Failure>;
+
+    async fn update(
+        &self,
+        channel_id: &str,
+        info: &Info<'_>,
+        track: &Track<'_>,
+    ) -> Result<(), Failure>;
+
+    /// チャンネルが既に無くなっている場合も Ok を返す
+    async fn stop(&self, channel_id: &str) -> Result<(), Failure>;
+
+    /// バージョンなどから対応している機能を調べる
+    async fn capabilities(&self) -> Result<Capabilities, Failure>;
+
+    async fn view_xml(&self) -> Result<ViewXml, Failure>;
+
+    /// 配信中のチャンネルから名前が一致するものを探す
+    async fn find_channel_id(&self, channel_name: &str) -> Result<Option<String>, Failure>;
+
+    /// リスナーがチャンネルを再生する URL
+    fn stream_url(&self, channel_id: &str) -> String;
+
+    async fn channel_health(&self, channel_id: &str) -> Result<ChannelHealth, Failure>;
+}
+
+/// PeerCast の種類と接続先から PeerCastBackend を作る
+pub type PeerCastBackendFactory =
+    Arc<dyn Fn(PeerCastType, &PeerCastEndpoint) -> Box<dyn PeerCastBackend> + Send + Sync>;
+
+pub fn default_backend_factory() -> PeerCastBackendFactory {
+    Arc::new(peercast_backend)
+}
+
+fn peercast_backend(
+    peer_cast_type: PeerCastType,
+    endpoint: &PeerCastEndpoint,
+) -> Box<dyn PeerCastBackend> {
+    let endpoint = endpoint.clone();
+    match peer_cast_type {
+        PeerCastType::PeerCastStation => Box::new(PeCaStAdapter::new(endpoint)),
+        PeerCastType::PeerCastOriginal => Box::new(PeerCastOriginalAdapter::new(endpoint)),
+    }
+}
@@ -0,0 +1,128 @@
+use std::{
+    net::{IpAddr, Ipv6Addr},
+    num::NonZeroU16,
+    time::Duration,
+};
+
+use once_cell::sync::Lazy;
+use reqwest::{Client, RequestBuilder, StatusCode};
+
+use crate::core::entities::settings::{GeneralSettings, PeerCastType};
+
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+pub const UNAUTHORIZED_MESSAGE: &str =
+    "PeerCast の認証に失敗しました。ユーザー名とパスワード、または認証トークンを確認してください。";
+
+/// PeerCast との通信で共有する HTTP クライアント
+static CLIENT: Lazy<Client> = Lazy::new(|| {
+    Client::builder()
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .unwrap()
+});
+
+/// 別の PC の PeerCast で配信できない設定の場合はその理由
+pub fn remote_problem(general_settings: &GeneralSettings) -> Option<String> {
+    if PeerCastEndpoint::new(general_settings).is_local() {
+        return None;
+    }
+    match general_settings.peer_cast_type {
+        // NOTE: PeerCast が PeCa Starter から 127.0.0.1 で FLV を取得するため
+        PeerCastType::PeerCastOriginal => {
+            Some("PeerCast (VP, IM など) は PeCa Starter と同じ PC で動かしてください。".to_owned())
+        }
+        // NOTE: 別の PC で空いているポート番号は調べられない
+        PeerCastType::PeerCastStation if general_settings.peer_cast_rtmp_port == 0 => Some(
+            "別の PC の PeerCastStation を使う場合は、RTMP TCP ポート番号を設定してください。"
+                .to_owned(),
+        ),
+        PeerCastType::PeerCastStation => None,
+    }
+}
+
+pub fn is_unauthorized(status: StatusCode) -> bool {
+    status == StatusCode::UNAUTHORIZED || status == StatusCode::FORBIDDEN
+}
+
+/// PeerCast の接続先と認証情報
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct PeerCastEndpoint {
+    host: String,
+    port: NonZeroU16,
+    user: String,
+    password: String,
+    auth_token: String,
+}
+
+impl PeerCastEndpoint {
+    pub fn new(general_settings: &GeneralSettings) -> Self {
+        let host = general_settings.peer_cast_host.trim();
+        Self {
+            host: if host.is_empty() { "localhost" } else { host }.to_owned(),
+            port: general_settings.peer_cast_port,
+            user: general_settings.peer_cast_user.clone(),
+            password: general_settings.peer_cast_password.clone(),
+            auth_token: general_settings.peer_cast_auth_token.clone(),
+        }
+    }
+
+    pub fn port(&self) -> NonZeroU16 {
+        self.port
+    }
+
+    /// PeerCast が同じ PC で動いている
+    pub fn is_local(&self) -> bool {
+        self.host == "localhost"
+            || self
+                .host
+                .parse::<IpAddr>()
+                .is_ok_and(|addr| addr.is_loopback())
+    }
+
+    /// URL に埋め込むホスト名。IPv6 アドレスは [] で囲む
+    pub fn host(&self) -> String {
+        if self.host.parse::<Ipv6Addr>().is_ok() {
+            format!("[{}]", self.host)
+        } else {
+            self.host.clone()
+        }
+    }
+
+    pub fn url(&self, path: &str) -> String {
+        format!("http://{}:{}{}", self.host(), self.port, path)
+    }
+
+    /// リスナーがチャンネルを再生する URL
+    pub fn stream_url(&self, channel_id: &str) -> String {
+        let url = self.url(&format!("/stream/{}.flv", channel_id));
+        if self.auth_token.is_empty() {
+            url
+        } else {
+            format!("{}?auth={}", url, self.auth_token)
+        }
+    }
+
+    pub fn get(&self, path: &str) -> RequestBuilder {
+        self.authorize(CLIENT.get(self.url(path)))
+    }
+
+    pub fn post(&self, path: &str) -> RequestBuilder {
+        self.authorize(CLIENT.post(self.url(path)))
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        let request = if self.user.is_empty() {
+            request
+        } else {
+            request.basic_auth(&self.user, Some(&self.password))
+        };
+        if self.auth_token.is_empty() {
+            request
+        } else {
+            request.query(&[("auth", &self.auth_token)])
+        }
+    }
+}
@@ -0,0 +1,190 @@
+use std::{num::NonZeroU16, time::Duration};
+
+use async_trait::async_trait;
+use log::{error, trace};
+use tokio::time::sleep;
+
+use crate::core::{
+    entities::settings::{NetworkType, YPAnnouncement},
+    utils::failure::Failure,
+};
+
+use super::{
+    capabilities::Capabilities,
+    pecast_adapter::{Info, Track},
+    peercast_backend::{ChannelHealth, PeerCastBackend},
+    peercast_endpoint::{PeerCastEndpoint, UNAUTHORIZED_MESSAGE, is_unauthorized},
+    view_xml::ViewXml,
+};
+
+/// fetch してから viewxml にチャンネルが現れるまで待つ回数
+const FIND_CHANNEL_RETRIES: u32 = 5;
+const FIND_CHANNEL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// PeerCast (VP, IM, YT などの本家由来の PeerCast) を /admin?cmd= で操作する
+pub struct PeerCastOriginalAdapter {
+    endpoint: PeerCastEndpoint,
+}
+
+impl PeerCastOriginalAdapter {
+    pub fn new(endpoint: PeerCastEndpoint) -> Self {
+        Self { endpoint }
+    }
+
+    async fn admin(&self, query: &[(&str, &str)]) -> Result<String, Failure> {
+        trace!("admin {:?}", query);
+        let res = self
+            .endpoint
+            .get("/admin")
+            .query(query)
+            .send()
+            .await
+            .map_err(|e| {
+                error!("{}", e);
+                Failure::Error(e.to_string())
+            })?;
+        if is_unauthorized(res.status()) {
+            error!("{}", res.status());
+            return Err(Failure::Error(UNAUTHORIZED_MESSAGE.to_owned()));
+        }
+        if !res.status().is_success() {
+            error!("{}", res.status());
+            return Err(Failure::Error(format!(
+                "PeerCast がエラーを返しました。({})",
+                res.status()
+            )));
+        }
+        res.text().await.map_err(|e| {
+            error!("{}", e);
+            Failure::Fatal("Failure communicating with PeerCast.".to_owned())
+        })
+    }
+
+    fn info_query<'a>(info: &'a Info<'a>, bitrate: &'a str) -> [(&'static str, &'a str); 7] {
+        [
+            ("name", info.name),
+            ("genre", info.genre),
+            ("desc", info.desc),
+            ("comment", info.comment),
+            ("contact", info.url),
+            ("bitrate", bitrate),
+            ("type", info.mime_type),
+        ]
+    }
+
+    fn track_query<'a>(track: &'a Track<'a>) -> [(&'static str, &'a str); 5] {
+        [
+            ("t_title", track.name),
+            ("t_artist", track.creator),
+            ("t_genre", track.genre),
+            ("t_album", track.album),
+            ("t_contact", track.url),
+        ]
+    }
+}
+
+#[async_trait]
+impl PeerCastBackend for PeerCastOriginalAdapter {
+    /// 掲載先の YP は PeerCast 側の設定で決まるため、ID は使わない
+    async fn prepare_yellow_pages(
+        &self,
+        announcements: &[YPAnnouncement],
+    ) -> Result<Vec<i32>, Failure> {
+        if announcements
+            .iter()
+            .any(|x| x.network_type == NetworkType::Ipv6)
+        {
+            return Err(Failure::Error(
+                "PeerCast は IPv6 の YP への掲載に対応していません。".to_owned(),
+            ));
+        }
+        if announcements.len() > 1 {
+            return Err(Failure::Error(
+                "PeerCast は複数の YP への掲載に対応していません。".to_owned(),
+            ));
+        }
+        Ok(announcements.iter().map(|_| 0).collect())
+    }
+
+    /// PeerCast には RTMP の待ち受けがないため、HTTP で FLV を取得させる。
+    /// そのため PeerCast は同じ PC で動いている必要がある
+    fn source(&self, rtmp_conn_port: NonZeroU16) -> (&'static str, String) {
+        ("HTTP", format!("http://127.0.0.1:{}/", rtmp_conn_port))
+    }
+
+    async fn broadcast(
+        &self,
+        _yp_id: i32,
+        (_, source_uri): &(&'static str, String),
+        _network_type: &str,
+        _content_reader: &str,
+        info: &Info<'_>,
+        track: &Track<'_>,
+    ) -> Result<String, Failure> {
+        let bitrate = info.bitrate.map(|x| x.to_string()).unwrap_or_default();
+        let mut query = vec![("cmd", "fetch"), ("url", source_uri.as_str())];
+        query.extend(Self::info_query(info, &bitrate));
+        query.extend(Self::track_query(track));
+        self.admin(&query).await?;
+
+        // NOTE: fetch はチャンネル ID を返さないので viewxml から探す
+        for _ in 0..FIND_CHANNEL_RETRIES {
+            if let Some(channel_id) = self.find_channel_id(info.name).await? {
+                return Ok(channel_id);
+            }
+            sleep(FIND_CHANNEL_INTERVAL).await;
+        }
+        Err(Failure::Error(
+            "PeerCast でチャンネルを作成できませんでした。".to_owned(),
+        ))
+    }
+
+    async fn update(
+        &self,
+        channel_id: &str,
+        info: &Info<'_>,
+        track: &Track<'_>,
+    ) -> Result<(), Failure> {
+        let bitrate = info.bitrate.map(|x| x.to_string()).unwrap_or_default();
+        let mut query = vec![("cmd", "edit_update"), ("id", channel_id)];
+        query.extend(Self::info_query(info, &bitrate));
+        query.extend(Self::track_query(track));
+        self.admin(&query).await.map(|_| ())
+    }
+
+    async fn stop(&self, channel_id: &str) -> Result<(), Failure> {
+        self.admin(&[("cmd", "stop"), ("id", channel_id)])
+            .await
+            .map(|_| ())
+    }
+
+    async fn capabilities(&self) -> Result<Capabilities, Failure> {
+        Ok(Capabilities::original())
+    }
+
+    async fn view_xml(&self) -> Result<ViewXml, Failure> {
+        let xml = self.admin(&[("cmd", "viewxml")]).await?;
+        ViewXml::parse(&xml)
+    }
+
+    async fn find_channel_id(&self, channel_name: &str) -> Result<Option<String>, Failure> {
+        Ok(self
+            .view_xml()
+            .await?
+            .find_channel_id(channel_name)
+            .map(|x| x.to_owned()))
+    }
+
+    fn stream_url(&self, channel_id: &str) -> String {
+        self.endpoint.stream_url(channel_id)
+    }
+
+    /// YP への掲載状況は viewxml に無いため、NotAnnounced は返さない
+    async fn channel_health(&self, channel_id: &str) -> Result<ChannelHealth, Failure> {
+        Ok(match self.view_xml().await?.relay_status(channel_id) {
+            None => ChannelHealth::Missing,
+            Some("ERROR") => ChannelHealth::Errored("ERROR".to_owned()),
+            Some(_) => ChannelHealth::Healthy,
+        })
+    }
+}
@@ -0,0 +1,162 @@
+use std::{env, num::NonZeroU16, path::PathBuf};
+
+use futures::future::join_all;
+use regex::Regex;
+use serde::Serialize;
+
+use crate::core::{
+    entities::settings::{GeneralSettings, PeerCastType},
+    utils::{failure::Failure, tcp::is_free_port},
+};
+
+use super::{
+    peercast_backend::PeerCastBackendFactory,
+    peercast_endpoint::{PeerCastEndpoint, UNAUTHORIZED_MESSAGE},
+};
+
+/// 設定ファイルが読めない場合に試すポート番号
+const COMMON_PORTS: [u16; 3] = [7144, 7145, 8144];
+
+/// 見つかったポート番号。見つからない場合は None
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct DiscoveredPorts {
+    pub peer_cast_port: Option<NonZeroU16>,
+    /// PeerCastStation で以前に使った RTMP の配信元のポート番号のうち、空いているもの
+    pub peer_cast_rtmp_port: Option<NonZeroU16>,
+}
+
+/// PeerCastStation の設定ファイルに書かれているポート番号
+#[derive(Debug, Default, Eq, PartialEq)]
+pub struct StationSettingsPorts {
+    /// 待ち受けのポート番号
+    pub listeners: Vec<NonZeroU16>,
+    /// 配信履歴などにある RTMP の配信元のポート番号
+    pub rtmp_sources: Vec<NonZeroU16>,
+}
+
+impl StationSettingsPorts {
+    /// NOTE: 設定ファイルの形式はバージョンによって違うため、XML として読まずに
+    ///       EndPoint の値 (0.0.0.0:7144 など) と rtmp:// の URL だけを探す
+    pub fn parse(xml: &str) -> Self {
+        let listener = Regex::new(r"(?s)EndPoint.{0,200}?[0-9\]]:([0-9]{1,5})\b").unwrap();
+        let rtmp_source = Regex::new(r#"rtmp://[^/\s"<>]*:([0-9]{1,5})/"#).unwrap();
+        Self {
+            listeners: unique_ports(&listener, xml),
+            rtmp_sources: unique_ports(&rtmp_source, xml),
+        }
+    }
+}
+
+fn unique_ports(regex: &Regex, text: &str) -> Vec<NonZeroU16> {
+    let mut ports = Vec::new();
+    for port in regex
+        .captures_iter(text)
+        .filter_map(|c| c[1].parse::<NonZeroU16>().ok())
+    {
+        if !ports.contains(&port) {
+            ports.push(port);
+        }
+    }
+    ports
+}
+
+/// Windows では %APPDATA%、それ以外では ~/.config の下にある
+fn station_settings_path() -> Option<PathBuf> {
+    let dir = if cfg!(target_os = "windows") {
+        PathBuf::from(env::var_os("APPDATA")?)
+    } else {
+        PathBuf::from(env::var_os("HOME")?).join(".config")
+    };
+    Some(dir.join("PeerCastStation").join("PecaSettings.xml"))
+}
+
+/// 同じ PC で動いている PeerCastStation の設定ファイルを読む
+async fn read_station_settings_ports(endpoint: &PeerCastEndpoint) -> StationSettingsPorts {
+    if !endpoint.is_local() {
+        return Default::default();
+    }
+    let Some(path) = station_settings_path() else {
+        return Default::default();
+    };
+    match tokio::fs::read_to_string(&path).await {
+        Ok(xml) => StationSettingsPorts::parse(&xml),
+        Err(err) => {
+            log::trace!("{:?} {}", path, err);
+            Default::default()
+        }
+    }
+}
+
+/// 認証に失敗した場合も PeerCast は動いているものとする
+async fn answers(
+    backend_factory: &PeerCastBackendFactory,
+    peer_cast_type: PeerCastType,
+    endpoint: &PeerCastEndpoint,
+) -> bool {
+    match (backend_factory)(peer_cast_type, endpoint).view_xml().await {
+        Ok(_) => true,
+        Err(Failure::Error(message)) => message == UNAUTHORIZED_MESSAGE,
+        Err(_) => false,
+    }
+}
+
+/// 配信元のポート番号として使える、以前に PeerCastStation で使ったポート番号
+pub async fn reusable_rtmp_port(general_settings: &GeneralSettings) -> Option<NonZeroU16> {
+    if general_settings.peer_cast_type != PeerCastType::PeerCastStation {
+        return None;
+    }
+    let endpoint = PeerCastEndpoint::new(general_settings);
+    for port in read_station_settings_ports(&endpoint).await.rtmp_sources {
+        if is_free_port(port).await {
+            return Some(port);
+        }
+    }
+    None
+}
+
+/// 設定されたポート番号、PeerCastStation の設定ファイルにあるポート番号、よく使われるポート番号の順に、
+/// PeerCast が応答するものを探す
+pub async fn discover_ports(
+    backend_factory: &PeerCastBackendFactory,
+    general_settings: &GeneralSettings,
+) -> DiscoveredPorts {
+    let peer_cast_type = general_settings.peer_cast_type;
+    let station_settings_ports = if peer_cast_type == PeerCastType::PeerCastStation {
+        read_station_settings_ports(&PeerCastEndpoint::new(general_settings)).await
+    } else {
+        Default::default()
+    };
+    let mut candidates = vec![general_settings.peer_cast_port];
+    for port in station_settings_ports
+        .listeners
+        .into_iter()
+        .chain(COMMON_PORTS.into_iter().filter_map(NonZeroU16::new))
+    {
+        if !candidates.contains(&port) {
+            candidates.push(port);
+        }
+    }
+    let endpoints: Vec<_> = candidates
+        .iter()
+        .map(|&peer_cast_port| {
+            PeerCastEndpoint::new(&GeneralSettings {
+                peer_cast_port,
+                ..general_settings.clone()
+            })
+        })
+        .collect();
+    let answered = join_all(
+        endpoints
+            .iter()
+            .map(|endpoint| answers(backend_factory, peer_cast_type, endpoint)),
+    )
+    .await;
+    DiscoveredPorts {
+        peer_cast_port: candidates
+            .into_iter()
+            .zip(answered)
+            .find_map(|(port, answered)| answered.then_some(port)),
+        peer_cast_rtmp_port: reusable_rtmp_port(general_settings).await,
+    }
+}
@@ -0,0 +1,284 @@
+//! PeerCast を使わずに Broadcasting などを動かすための PeerCastBackend。テストでのみ使う
+
+use std::{
+    collections::HashMap,
+    num::NonZeroU16,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+
+use crate::core::{entities::settings::YPAnnouncement, utils::failure::Failure};
+
+use super::{
+    capabilities::Capabilities,
+    channel_utils::rtmp_source,
+    pecast_adapter::{Info, Track},
+    peercast_backend::{ChannelHealth, PeerCastBackend, PeerCastBackendFactory},
+    view_xml::ViewXml,
+};
+
+/// 記録された操作
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum SimulatedCall {
+    PrepareYellowPages,
+    Broadcast {
+        yp_id: i32,
+        source: String,
+        network_type: String,
+        content_reader: String,
+        name: String,
+    },
+    Update {
+        channel_id: String,
+        name: String,
+        track_name: String,
+    },
+    Stop {
+        channel_id: String,
+    },
+    Capabilities,
+    ViewXml,
+    FindChannelId {
+        channel_name: String,
+    },
+    ChannelHealth {
+        channel_id: String,
+    },
+}
+
+/// 失敗させる操作の種類
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum SimulatedOperation {
+    PrepareYellowPages,
+    Broadcast,
+    Update,
+    Stop,
+    Capabilities,
+    ViewXml,
+    FindChannelId,
+    ChannelHealth,
+}
+
+#[derive(Default)]
+struct State {
+    calls: Vec<SimulatedCall>,
+    /// 配信中のチャンネルの ID と名前
+    channels: Vec<(String, String)>,
+    next_channel_id: u32,
+    /// Healthy 以外の状態にしたチャンネル
+    health: HashMap<String, ChannelHealth>,
+    /// None の場合は全ての機能に対応しているものとして扱う
+    capabilities: Option<Capabilities>,
+    /// 指定した回数だけ成功させた後、1 回だけ失敗させる
+    failures: HashMap<SimulatedOperation, (usize, Failure)>,
+}
+
+impl State {
+    fn call(&mut self, operation: SimulatedOperation, call: SimulatedCall) -> Result<(), Failure> {
+        self.calls.push(call);
+        match self.failures.get_mut(&operation) {
+            Some((0, _)) => Err(self.failures.remove(&operation).unwrap().1),
+            Some((skip, _)) => {
+                *skip -= 1;
+                Ok(())
+            }
+            None => Ok(()),
+        }
+    }
+}
+
+/// 呼び出しを記録し、チャンネルをメモリ上で管理する PeerCastBackend。
+/// clone したものは状態を共有する
+#[derive(Clone, Default)]
+pub struct SimulatedBackend {
+    state: Arc<Mutex<State>>,
+}
+
+impl SimulatedBackend {
+    pub fn new() -> Self {
+        Default::default()
+    }
+
+    /// 常にこの SimulatedBackend を返す
+    pub fn factory(&self) -> PeerCastBackendFactory {
+        let zelf = self.clone();
+        Arc::new(move |_, _| Box::new(zelf.clone()))
+    }
+
+    pub fn calls(&self) -> Vec<SimulatedCall> {
+        self.state.lock().unwrap().calls.clone()
+    }
+
+    pub fn clear_calls(&self) {
+        self.state.lock().unwrap().calls.clear();
+    }
+
+    /// 配信中のチャンネルの ID と名前
+    pub fn channels(&self) -> Vec<(String, String)> {
+        self.state.lock().unwrap().channels.clone()
+    }
+
+    /// PeerCast がチャンネルを勝手に終了した状態にする
+    pub fn drop_channel(&self, channel_id: &str) {
+        let mut state = self.state.lock().unwrap();
+        state.channels.retain(|(id, _)| id != channel_id);
+    }
+
+    /// チャンネルの状態を変える。チャンネルを作り直すと Healthy に戻る
+    pub fn set_channel_health(&self, channel_id: &str, health: ChannelHealth) {
+        let mut state = self.state.lock().unwrap();
+        state.health.insert(channel_id.to_owned(), health);
+    }
+
+    /// 対応している機能を変える
+    pub fn set_capabilities(&self, capabilities: Capabilities) {
+        self.state.lock().unwrap().capabilities = Some(capabilities);
+    }
+
+    /// 次にその操作が呼ばれたときに failure を返す
+    pub fn fail_next(&self, operation: SimulatedOperation, failure: Failure) {
+        self.fail_after(operation, 0, failure);
+    }
+
+    /// その操作が skip 回成功した後に failure を返す
+    pub fn fail_after(&self, operation: SimulatedOperation, skip: usize, failure: Failure) {
+        let mut state = self.state.lock().unwrap();
+        state.failures.insert(operation, (skip, failure));
+    }
+}
+
+#[async_trait]
+impl PeerCastBackend for SimulatedBackend {
+    async fn prepare_yellow_pages(
+        &self,
+        announcements: &[YPAnnouncement],
+    ) -> Result<Vec<i32>, Failure> {
+        let mut state = self.state.lock().unwrap();
+        state.call(
+            SimulatedOperation::PrepareYellowPages,
+            SimulatedCall::PrepareYellowPages,
+        )?;
+        // NOTE: YP の ID は設定の順に 1 から振る
+        Ok((1..).take(announcements.len()).collect())
+    }
+
+    fn source(&self, rtmp_conn_port: NonZeroU16) -> (&'static str, String) {
+        rtmp_source("localhost", rtmp_conn_port)
+    }
+
+    async fn broadcast(
+        &self,
+        yp_id: i32,
+        (_, source_uri): &(&'static str, String),
+        network_type: &str,
+        content_reader: &str,
+        info: &Info<'_>,
+        _track: &Track<'_>,
+    ) -> Result<String, Failure> {
+        let mut state = self.state.lock().unwrap();
+        let call = SimulatedCall::Broadcast {
+            yp_id,
+            source: source_uri.clone(),
+            network_type: network_type.to_owned(),
+            content_reader: content_reader.to_owned(),
+            name: info.name.to_owned(),
+        };
+        state.call(SimulatedOperation::Broadcast, call)?;
+        state.next_channel_id += 1;
+        let channel_id = format!("{:032X}", state.next_channel_id);
+        state
+            .channels
+            .push((channel_id.clone(), info.name.to_owned()));
+        Ok(channel_id)
+    }
+
+    async fn update(
+        &self,
+        channel_id: &str,
+        info: &Info<'_>,
+        track: &Track<'_>,
+    ) -> Result<(), Failure> {
+        let mut state = self.state.lock().unwrap();
+        let call = SimulatedCall::Update {
+            channel_id: channel_id.to_owned(),
+            name: info.name.to_owned(),
+            track_name: track.name.to_owned(),
+        };
+        state.call(SimulatedOperation::Update, call)?;
+        let Some(channel) = state.channels.iter_mut().find(|(id, _)| id == channel_id) else {
+            return Err(Failure::Error("Channel not found".to_owned()));
+        };
+        channel.1 = info.name.to_owned();
+        Ok(())
+    }
+
+    async fn stop(&self, channel_id: &str) -> Result<(), Failure> {
+        let mut state = self.state.lock().unwrap();
+        let call = SimulatedCall::Stop {
+            channel_id: channel_id.to_owned(),
+        };
+        state.call(SimulatedOperation::Stop, call)?;
+        state.channels.retain(|(id, _)| id != channel_id);
+        state.health.remove(channel_id);
+        Ok(())
+    }
+
+    async fn capabilities(&self) -> Result<Capabilities, Failure> {
+        let mut state = self.state.lock().unwrap();
+        state.call(
+            SimulatedOperation::Capabilities,
+            SimulatedCall::Capabilities,
+        )?;
+        Ok(state.capabilities.clone().unwrap_or_else(|| Capabilities {
+            agent_name: "SimulatedBackend".to_owned(),
+            stop_delay: Duration::ZERO,
+            network_type: true,
+            rtmp_source: true,
+            content_readers: None,
+            unsupported: None,
+        }))
+    }
+
+    async fn view_xml(&self) -> Result<ViewXml, Failure> {
+        let mut state = self.state.lock().unwrap();
+        state.call(SimulatedOperation::ViewXml, SimulatedCall::ViewXml)?;
+        Ok(ViewXml::from_channels(
+            state.channels.iter().map(|(id, name)| (&id[..], &name[..])),
+        ))
+    }
+
+    async fn find_channel_id(&self, channel_name: &str) -> Result<Option<String>, Failure> {
+        let mut state = self.state.lock().unwrap();
+        let call = SimulatedCall::FindChannelId {
+            channel_name: channel_name.to_owned(),
+        };
+        state.call(SimulatedOperation::FindChannelId, call)?;
+        Ok(state
+            .channels
+            .iter()
+            .find(|(_, name)| name == channel_name)
+            .map(|(id, _)| id.clone()))
+    }
+
+    fn stream_url(&self, channel_id: &str) -> String {
+        format!("http://127.0.0.1/stream/{}.flv", channel_id)
+    }
+
+    async fn channel_health(&self, channel_id: &str) -> Result<ChannelHealth, Failure> {
+        let mut state = self.state.lock().unwrap();
+        let call = SimulatedCall::ChannelHealth {
+            channel_id: channel_id.to_owned(),
+        };
+        state.call(SimulatedOperation::ChannelHealth, call)?;
+        if !state.channels.iter().any(|(id, _)| id == channel_id) {
+            return Ok(ChannelHealth::Missing);
+        }
+        Ok(state
+            .health
+            .get(channel_id)
+            .cloned()
+            .unwrap_or(ChannelHealth::Healthy))
+    }
+}
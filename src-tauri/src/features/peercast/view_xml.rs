@@ -1,65 +1,190 @@
 use serde::Deserialize;
 
-#[derive(Debug, Deserialize)]
+use crate::{
+    core::{
+        entities::peercast_stats::{AnnouncedChannelStats, ChannelStats, PeerCastStats},
+        utils::failure::Failure,
+    },
+    features::peercast::broadcasting::BroadcastChannel,
+};
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 struct Servent {
-    // uptime: u64,
+    /// 秒
+    uptime: u64,
 }
 
-#[derive(Debug, Deserialize)]
-struct Bandwidth {}
-
-#[derive(Debug, Deserialize)]
-struct Connections {}
+/// kbps
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Bandwidth {
+    #[serde(rename = "in")]
+    input: u32,
+    #[serde(rename = "out")]
+    output: u32,
+}
 
-#[derive(Debug, Deserialize)]
-struct ChannelsRelayed {}
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct Connections {
+    total: u32,
+}
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 struct Hits {
-    // pub hosts: u32,
+    hosts: u32,
     listeners: u32,
     relays: u32,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
 struct Relay {
     listeners: u32,
     relays: u32,
+    hosts: u32,
+    /// RECEIVE, BROADCAST, ERROR など
+    status: String,
 }
 
 #[derive(Debug, Deserialize)]
 struct Channel {
     id: String,
+    #[serde(default)]
     hits: Hits,
+    #[serde(default)]
     relay: Relay,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 struct ChannelsFound {
-    #[serde(rename = "channel")]
+    #[serde(rename = "channel", default)]
     channels: Vec<Channel>,
 }
 
+/// この PeerCast が配信または中継しているチャンネル
+#[derive(Debug, Deserialize)]
+struct RelayedChannel {
+    id: String,
+    name: String,
+    #[serde(default)]
+    relay: Relay,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ChannelsRelayed {
+    #[serde(rename = "channel", default)]
+    channels: Vec<RelayedChannel>,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ViewXml {
-    // session: String,
-    // servent: Servent,
-    // connections: Connections,
-    // channels_relayed: ChannelsRelayed,
+    #[serde(default)]
+    servent: Servent,
+    #[serde(default)]
+    bandwidth: Bandwidth,
+    #[serde(default)]
+    connections: Connections,
+    #[serde(default)]
+    channels_relayed: ChannelsRelayed,
+    #[serde(default)]
     channels_found: ChannelsFound,
 }
 
 impl ViewXml {
-    pub fn find_listeners_relays(&self, channel_id: &str) -> Option<(u32, u32)> {
-        self.channels_found
+    pub fn parse(xml: &str) -> Result<Self, Failure> {
+        serde_xml_rs::from_str(xml).map_err(|e| {
+            log::error!("{}", e);
+            Failure::Fatal("Failure parsing viewxml.".to_owned())
+        })
+    }
+
+    /// 指定したチャンネルだけを配信している状態。リスナー数などは 0 になる
+    #[cfg(test)]
+    pub fn from_channels<'a>(channels: impl IntoIterator<Item = (&'a str, &'a str)>) -> Self {
+        let (relayed, found) = channels
+            .into_iter()
+            .map(|(id, name)| {
+                let relayed = RelayedChannel {
+                    id: id.to_owned(),
+                    name: name.to_owned(),
+                    relay: Default::default(),
+                };
+                let found = Channel {
+                    id: id.to_owned(),
+                    hits: Default::default(),
+                    relay: Default::default(),
+                };
+                (relayed, found)
+            })
+            .unzip();
+        Self {
+            servent: Default::default(),
+            bandwidth: Default::default(),
+            connections: Default::default(),
+            channels_relayed: ChannelsRelayed { channels: relayed },
+            channels_found: ChannelsFound { channels: found },
+        }
+    }
+
+    pub fn find_channel_id(&self, channel_name: &str) -> Option<&str> {
+        self.channels_relayed
+            .channels
+            .iter()
+            .find(|x| x.name == channel_name)
+            .map(|x| x.id.as_str())
+    }
+
+    /// 配信または中継しているチャンネルの状態。チャンネルが無ければ None
+    pub fn relay_status(&self, channel_id: &str) -> Option<&str> {
+        self.channels_relayed
+            .channels
+            .iter()
+            .find(|x| x.id == channel_id)
+            .map(|x| x.relay.status.as_str())
+    }
+
+    /// YP に掲載されている値を優先し、無ければこの PeerCast が中継している値を使う
+    pub fn find_channel_stats(&self, channel_id: &str) -> Option<ChannelStats> {
+        if let Some(channel) = self
+            .channels_found
             .channels
             .iter()
             .find(|x| x.id == channel_id)
-            .map(|channel| {
-                (
-                    channel.hits.listeners + channel.relay.listeners,
-                    channel.hits.relays + channel.relay.relays,
-                )
+        {
+            return Some(ChannelStats {
+                listeners: channel.hits.listeners + channel.relay.listeners,
+                relays: channel.hits.relays + channel.relay.relays,
+                hosts: channel.hits.hosts + channel.relay.hosts,
+            });
+        }
+        self.channels_relayed
+            .channels
+            .iter()
+            .find(|x| x.id == channel_id)
+            .map(|channel| ChannelStats {
+                listeners: channel.relay.listeners,
+                relays: channel.relay.relays,
+                hosts: channel.relay.hosts,
             })
     }
+
+    pub fn peercast_stats(&self, channels: &[BroadcastChannel]) -> PeerCastStats {
+        PeerCastStats {
+            uptime: self.servent.uptime,
+            bandwidth_in: self.bandwidth.input,
+            bandwidth_out: self.bandwidth.output,
+            total_connections: self.connections.total,
+            channels: channels
+                .iter()
+                .map(|x| AnnouncedChannelStats {
+                    host: x.host().to_owned(),
+                    network_type: x.announcement.network_type,
+                    stats: self.find_channel_stats(&x.id),
+                })
+                .collect(),
+        }
+    }
 }
@@ -0,0 +1,203 @@
+use anyhow::{Result, bail};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Amf0Value {
+    Number(f64),
+    Boolean(bool),
+    String(String),
+    Object(Vec<(String, Amf0Value)>),
+    Null,
+    Undefined,
+    EcmaArray(Vec<(String, Amf0Value)>),
+    StrictArray(Vec<Amf0Value>),
+    Date(f64),
+}
+
+impl Amf0Value {
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Amf0Value::String(x) => Some(x),
+            _ => None,
+        }
+    }
+
+    pub fn as_f64(&self) -> Option<f64> {
+        match self {
+            Amf0Value::Number(x) => Some(*x),
+            _ => None,
+        }
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Amf0Value> {
+        match self {
+            Amf0Value::Object(props) | Amf0Value::EcmaArray(props) => {
+                props.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+            }
+            _ => None,
+        }
+    }
+}
+
+pub fn object(props: &[(&str, Amf0Value)]) -> Amf0Value {
+    Amf0Value::Object(
+        props
+            .iter()
+            .map(|(k, v)| ((*k).to_owned(), v.clone()))
+            .collect(),
+    )
+}
+
+struct Decoder<'a> {
+    buf: &'a [u8],
+    pos: usize,
+}
+
+impl Decoder<'_> {
+    fn take(&mut self, len: usize) -> Result<&[u8]> {
+        if self.buf.len() - self.pos < len {
+            bail!("AMF0: unexpected end of data");
+        }
+        let slice = &self.buf[self.pos..self.pos + len];
+        self.pos += len;
+        Ok(slice)
+    }
+
+    fn u8(&mut self) -> Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u16(&mut self) -> Result<u16> {
+        Ok(u16::from_be_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> Result<u32> {
+        Ok(u32::from_be_bytes(self.take(4)?.try_into().unwrap()))
+    }
+
+    fn f64(&mut self) -> Result<f64> {
+        Ok(f64::from_be_bytes(self.take(8)?.try_into().unwrap()))
+    }
+
+    fn string(&mut self, len: usize) -> Result<String> {
+        Ok(String::from_utf8_lossy(self.take(len)?).into_owned())
+    }
+
+    fn properties(&mut self) -> Result<Vec<(String, Amf0Value)>> {
+        let mut props = Vec::new();
+        loop {
+            let len = self.u16()? as usize;
+            let key = self.string(len)?;
+            if key.is_empty() && self.buf.get(self.pos) == Some(&0x09) {
+                self.pos += 1;
+                return Ok(props);
+            }
+            props.push((key, self.value()?));
+        }
+    }
+
+    fn value(&mut self) -> Result<Amf0Value> {
+        Ok(match self.u8()? {
+            0x00 => Amf0Value::Number(self.f64()?),
+            0x01 => Amf0Value::Boolean(self.u8()? != 0),
+            0x02 => {
+                let len = self.u16()? as usize;
+                Amf0Value::String(self.string(len)?)
+            }
+            0x03 => Amf0Value::Object(self.properties()?),
+            0x05 => Amf0Value::Null,
+            0x06 => Amf0Value::Undefined,
+            0x08 => {
+                let _approximate_len = self.u32()?;
+                Amf0Value::EcmaArray(self.properties()?)
+            }
+            0x0a => {
+                let len = self.u32()?;
+                let mut items = Vec::new();
+                for _ in 0..len {
+                    items.push(self.value()?);
+                }
+                Amf0Value::StrictArray(items)
+            }
+            0x0b => {
+                let date = self.f64()?;
+                let _time_zone = self.u16()?;
+                Amf0Value::Date(date)
+            }
+            0x0c => {
+                let len = self.u32()? as usize;
+                Amf0Value::String(self.string(len)?)
+            }
+            marker => bail!("AMF0: unsupported marker {:#04x}", marker),
+        })
+    }
+}
+
+pub fn decode_all(buf: &[u8]) -> Result<Vec<Amf0Value>> {
+    let mut decoder = Decoder { buf, pos: 0 };
+    let mut values = Vec::new();
+    while decoder.pos < buf.len() {
+        values.push(decoder.value()?);
+    }
+    Ok(values)
+}
+
+fn encode_key(buf: &mut Vec<u8>, key: &str) {
+    buf.extend((key.len() as u16).to_be_bytes());
+    buf.extend(key.as_bytes());
+}
+
+fn encode_properties(buf: &mut Vec<u8>, props: &[(String, Amf0Value)]) {
+    for (key, value) in props {
+        encode_key(buf, key);
+        encode_value(buf, value);
+    }
+    buf.extend([0x00, 0x00, 0x09]);
+}
+
+fn encode_value(buf: &mut Vec<u8>, value: &Amf0Value) {
+    match value {
+        Amf0Value::Number(x) => {
+            buf.push(0x00);
+            buf.extend(x.to_be_bytes());
+        }
+        Amf0Value::Boolean(x) => buf.extend([0x01, *x as u8]),
+        Amf0Value::String(x) if x.len() > u16::MAX as usize => {
+            buf.push(0x0c);
+            buf.extend((x.len() as u32).to_be_bytes());
+            buf.extend(x.as_bytes());
+        }
+        Amf0Value::String(x) => {
+            buf.push(0x02);
+            encode_key(buf, x);
+        }
+        Amf0Value::Object(props) => {
+            buf.push(0x03);
+            encode_properties(buf, props);
+        }
+        Amf0Value::Null => buf.push(0x05),
+        Amf0Value::Undefined => buf.push(0x06),
+        Amf0Value::EcmaArray(props) => {
+            buf.push(0x08);
+            buf.extend((props.len() as u32).to_be_bytes());
+            encode_properties(buf, props);
+        }
+        Amf0Value::StrictArray(items) => {
+            buf.push(0x0a);
+            buf.extend((items.len() as u32).to_be_bytes());
+            items.iter().for_each(|item| encode_value(buf, item));
+        }
+        Amf0Value::Date(x) => {
+            buf.push(0x0b);
+            buf.extend(x.to_be_bytes());
+            buf.extend([0x00, 0x00]);
+        }
+    }
+}
+
+pub fn encode_all(values: &[Amf0Value]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    values
+        .iter()
+        .for_each(|value| encode_value(&mut buf, value));
+    buf
+}
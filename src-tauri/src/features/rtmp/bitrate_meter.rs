@@ -0,0 +1,47 @@
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const WINDOW: Duration = Duration::from_secs(10);
+
+/// 直近 10 秒間の受信量から受信ビットレートを算出する
+#[derive(Default)]
+pub struct BitrateMeter {
+    started_at: Option<Instant>,
+    samples: VecDeque<(Instant, usize)>,
+    bytes_in_window: usize,
+}
+
+impl BitrateMeter {
+    pub fn add(&mut self, bytes: usize) {
+        let now = Instant::now();
+        self.started_at.get_or_insert(now);
+        self.samples.push_back((now, bytes));
+        self.bytes_in_window += bytes;
+        self.evict(now);
+    }
+
+    /// kbps
+    pub fn kbps(&mut self) -> u32 {
+        let now = Instant::now();
+        self.evict(now);
+        let Some(started_at) = self.started_at else {
+            return 0;
+        };
+        let elapsed = (now - started_at).min(WINDOW);
+        if elapsed < Duration::from_secs(1) {
+            return 0;
+        }
+        (self.bytes_in_window as f64 * 8.0 / 1000.0 / elapsed.as_secs_f64()).round() as u32
+    }
+
+    fn evict(&mut self, now: Instant) {
+        while let Some(&(time, bytes)) = self.samples.front()
+            && now - time > WINDOW
+        {
+            self.samples.pop_front();
+            self.bytes_in_window -= bytes;
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use std::{
+    collections::HashMap,
+    io::{Error, ErrorKind},
+};
+
+use anyhow::{Result, bail};
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+use super::amf0::{self, Amf0Value};
+
+pub const MSG_SET_CHUNK_SIZE: u8 = 1;
+pub const MSG_USER_CONTROL: u8 = 4;
+pub const MSG_WINDOW_ACK_SIZE: u8 = 5;
+pub const MSG_SET_PEER_BANDWIDTH: u8 = 6;
+pub const MSG_AUDIO: u8 = 8;
+pub const MSG_VIDEO: u8 = 9;
+pub const MSG_DATA_AMF3: u8 = 15;
+pub const MSG_COMMAND_AMF3: u8 = 17;
+pub const MSG_DATA_AMF0: u8 = 18;
+pub const MSG_COMMAND_AMF0: u8 = 20;
+
+const DEFAULT_CHUNK_SIZE: usize = 128;
+const MAX_MESSAGE_LENGTH: usize = 16 * 1024 * 1024;
+/// NOTE: チャンクストリームごとに MAX_MESSAGE_LENGTH までバッファするので、数を制限する
+const MAX_CHUNK_STREAMS: usize = 64;
+
+#[derive(Clone, Debug)]
+pub struct RtmpMessage {
+    pub csid: u32,
+    pub timestamp: u32,
+    pub type_id: u8,
+    pub stream_id: u32,
+    pub payload: Vec<u8>,
+}
+
+impl RtmpMessage {
+    pub fn command(csid: u32, stream_id: u32, values: &[Amf0Value]) -> Self {
+        Self {
+            csid,
+            timestamp: 0,
+            type_id: MSG_COMMAND_AMF0,
+            stream_id,
+            payload: amf0::encode_all(values),
+        }
+    }
+
+    fn control(type_id: u8, payload: Vec<u8>) -> Self {
+        Self {
+            csid: 2,
+            timestamp: 0,
+            type_id,
+            stream_id: 0,
+            payload,
+        }
+    }
+
+    pub fn window_ack_size(size: u32) -> Self {
+        Self::control(MSG_WINDOW_ACK_SIZE, size.to_be_bytes().to_vec())
+    }
+
+    pub fn set_peer_bandwidth(size: u32) -> Self {
+        let mut payload = size.to_be_bytes().to_vec();
+        payload.push(2); // dynamic
+        Self::control(MSG_SET_PEER_BANDWIDTH, payload)
+    }
+
+    pub fn stream_begin(stream_id: u32) -> Self {
+        let mut payload = vec![0x00, 0x00];
+        payload.extend(stream_id.to_be_bytes());
+        Self::control(MSG_USER_CONTROL, payload)
+    }
+
+    pub fn is_media(&self) -> bool {
+        matches!(
+            self.type_id,
+            MSG_AUDIO | MSG_VIDEO | MSG_DATA_AMF0 | MSG_DATA_AMF3
+        )
+    }
+
+    /// 映像 (AVC, HEVC, Enhanced RTMP) と AAC のシーケンスヘッダー
+    pub fn is_sequence_header(&self) -> bool {
+        let Some(&first) = self.payload.first() else {
+            return false;
+        };
+        match self.type_id {
+            // Enhanced RTMP の SequenceStart
+            MSG_VIDEO if first & 0x80 != 0 => first & 0x0f == 0,
+            MSG_VIDEO => matches!(first & 0x0f, 7 | 12) && self.payload.get(1) == Some(&0x00),
+            MSG_AUDIO => first >> 4 == 10 && self.payload.get(1) == Some(&0x00),
+            _ => false,
+        }
+    }
+
+    /// シーケンスヘッダーも含む
+    pub fn is_keyframe(&self) -> bool {
+        self.type_id == MSG_VIDEO && self.payload.first().is_some_and(|x| (x >> 4) & 0x07 == 1)
+    }
+
+    /// AMF0 (もしくは AMF3 コマンドに埋め込まれた AMF0) のコマンドを解釈する
+    pub fn decode_command(&self) -> Result<Vec<Amf0Value>> {
+        match self.type_id {
+            MSG_COMMAND_AMF0 => amf0::decode_all(&self.payload),
+            MSG_COMMAND_AMF3 if !self.payload.is_empty() => amf0::decode_all(&self.payload[1..]),
+            _ => bail!("not a command message: {}", self.type_id),
+        }
+    }
+}
+
+#[derive(Default)]
+struct ChunkStreamState {
+    timestamp: u32,
+    timestamp_delta: u32,
+    length: usize,
+    type_id: u8,
+    stream_id: u32,
+    extended_timestamp: bool,
+    buf: Vec<u8>,
+}
+
+fn invalid_data(message: String) -> anyhow::Error {
+    Error::new(ErrorKind::InvalidData, message).into()
+}
+
+pub struct ChunkReader<R> {
+    reader: R,
+    chunk_size: usize,
+    streams: HashMap<u32, ChunkStreamState>,
+}
+
+impl<R: AsyncRead + Unpin> ChunkReader<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+            streams: HashMap::new(),
+        }
+    }
+
+    async fn read_u24(&mut self) -> Result<u32> {
+        let mut buf = [0u8; 4];
+        self.reader.read_exact(&mut buf[1..]).await?;
+        Ok(u32::from_be_bytes(buf))
+    }
+
+    async fn read_csid(&mut self, first: u8) -> Result<u32> {
+        Ok(match first & 0x3f {
+            0 => 64 + self.reader.read_u8().await? as u32,
+            1 => 64 + self.reader.read_u16_le().await? as u32,
+            csid => csid as u32,
+        })
+    }
+
+    /// 1 つのメッセージが揃うまでチャンクを読み進める
+    pub async fn read_message(&mut self) -> Result<RtmpMessage> {
+        loop {
+            let first = self.reader.read_u8().await?;
+            let fmt = first >> 6;
+            let csid = self.read_csid(first).await?;
+
+            let mut timestamp_field = None;
+            let mut header = None;
+            match fmt {
+                0 => {
+                    let timestamp = self.read_u24().await?;
+                    let length = self.read_u24().await? as usize;
+                    let type_id = self.reader.read_u8().await?;
+                    let stream_id = self.reader.read_u32_le().await?;
+                    timestamp_field = Some(timestamp);
+                    header = Some((length, type_id, Some(stream_id)));
+                }
+                1 => {
+                    let delta = self.read_u24().await?;
+                    let length = self.read_u24().await? as usize;
+                    let type_id = self.reader.read_u8().await?;
+                    timestamp_field = Some(delta);
+                    header = Some((length, type_id, None));
+                }
+                2 => {
+                    timestamp_field = Some(self.read_u24().await?);
+                }
+                _ => {}
+            }
+
+            if !self.streams.contains_key(&csid) && self.streams.len() >= MAX_CHUNK_STREAMS {
+                return Err(invalid_data(format!("too many chunk streams: {}", csid)));
+            }
+            let state = self.streams.entry(csid).or_default();
+            if let Some(field) = timestamp_field {
+                state.extended_timestamp = field == 0xffffff;
+            }
+            let timestamp_value = if state.extended_timestamp {
+                self.reader.read_u32().await?
+            } else {
+                timestamp_field.unwrap_or(state.timestamp_delta)
+            };
+
+            let state = self.streams.get_mut(&csid).unwrap();
+            if let Some((length, type_id, stream_id)) = header {
+                if length > MAX_MESSAGE_LENGTH {
+                    bail!("message too long: {}", length);
+                }
+                // NOTE: メッセージの途中で、受信済みより短い長さのヘッダーが来ることはない
+                if length < state.buf.len() {
+                    return Err(invalid_data(format!(
+                        "message length {} is shorter than received {}",
+                        length,
+                        state.buf.len()
+                    )));
+                }
+                state.length = length;
+                state.type_id = type_id;
+                if let Some(stream_id) = stream_id {
+                    state.stream_id = stream_id;
+                }
+            }
+            if state.buf.is_empty() {
+                match fmt {
+                    0 => {
+                        state.timestamp_delta = timestamp_value;
+                        state.timestamp = timestamp_value;
+                    }
+                    1 | 2 => {
+                        state.timestamp_delta = timestamp_value;
+                        state.timestamp = state.timestamp.wrapping_add(timestamp_value);
+                    }
+                    _ => state.timestamp = state.timestamp.wrapping_add(state.timestamp_delta),
+                }
+            }
+
+            let remaining = state.length - state.buf.len();
+            let len = remaining.min(self.chunk_size);
+            let start = state.buf.len();
+            state.buf.resize(start + len, 0);
+            self.reader.read_exact(&mut state.buf[start..]).await?;
+            if state.buf.len() < state.length {
+                continue;
+            }
+
+            let message = RtmpMessage {
+                csid,
+                timestamp: state.timestamp,
+                type_id: state.type_id,
+                stream_id: state.stream_id,
+                payload: std::mem::take(&mut state.buf),
+            };
+            if message.type_id == MSG_SET_CHUNK_SIZE && message.payload.len() >= 4 {
+                let size = u32::from_be_bytes(message.payload[..4].try_into().unwrap());
+                self.chunk_size = (size & 0x7fffffff).max(1) as usize;
+            }
+            return Ok(message);
+        }
+    }
+}
+
+pub struct ChunkWriter<W> {
+    writer: W,
+    chunk_size: usize,
+}
+
+impl<W: AsyncWrite + Unpin> ChunkWriter<W> {
+    pub fn new(writer: W) -> Self {
+        Self {
+            writer,
+            chunk_size: DEFAULT_CHUNK_SIZE,
+        }
+    }
+
+    pub async fn set_chunk_size(&mut self, chunk_size: u32) -> Result<()> {
+        let message = RtmpMessage::control(MSG_SET_CHUNK_SIZE, chunk_size.to_be_bytes().to_vec());
+        self.write_message(&message).await?;
+        self.chunk_size = chunk_size as usize;
+        Ok(())
+    }
+
+    fn basic_header(fmt: u8, csid: u32) -> Vec<u8> {
+        match csid {
+            2..=63 => vec![(fmt << 6) | csid as u8],
+            64..=319 => vec![fmt << 6, (csid - 64) as u8],
+            _ => {
+                let id = ((csid - 64) as u16).to_le_bytes();
+                vec![(fmt << 6) | 1, id[0], id[1]]
+            }
+        }
+    }
+
+    pub async fn write_message(&mut self, message: &RtmpMessage) -> Result<()> {
+        let extended = message.timestamp >= 0xffffff;
+        let mut buf = Self::basic_header(0, message.csid);
+        buf.extend(&message.timestamp.min(0xffffff).to_be_bytes()[1..]);
+        buf.extend(&(message.payload.len() as u32).to_be_bytes()[1..]);
+        buf.push(message.type_id);
+        buf.extend(message.stream_id.to_le_bytes());
+        if extended {
+            buf.extend(message.timestamp.to_be_bytes());
+        }
+        for (i, chunk) in message.payload.chunks(self.chunk_size).enumerate() {
+            if i > 0 {
+                buf.extend(Self::basic_header(3, message.csid));
+                if extended {
+                    buf.extend(message.timestamp.to_be_bytes());
+                }
+            }
+            buf.extend(chunk);
+        }
+        self.writer.write_all(&buf).await?;
+        Ok(())
+    }
+
+    pub async fn shutdown(&mut self) {
+        let _ = self.writer.shutdown().await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn fmt0(basic_header: &[u8], length: usize, payload_len: usize) -> Vec<u8> {
+        let mut buf = basic_header.to_vec();
+        buf.extend([0, 0, 0]);
+        buf.extend(&(length as u32).to_be_bytes()[1..]);
+        buf.push(MSG_VIDEO);
+        buf.extend(1u32.to_le_bytes());
+        buf.extend(vec![0; payload_len]);
+        buf
+    }
+
+    fn assert_invalid_data(err: anyhow::Error) {
+        let err = err.downcast::<Error>().unwrap();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[tokio::test]
+    async fn reject_shorter_header_in_message() {
+        let mut buf = fmt0(&[3], 200, 128);
+        // fmt 1 で長さを 10 にする
+        buf.extend([(1 << 6) | 3, 0, 0, 0, 0, 0, 10, MSG_VIDEO]);
+        buf.extend([0; 10]);
+        let err = ChunkReader::new(buf.as_slice()).read_message().await;
+        assert_invalid_data(err.unwrap_err());
+    }
+
+    #[tokio::test]
+    async fn limit_chunk_streams() {
+        let buf: Vec<_> = (0..=MAX_CHUNK_STREAMS as u8)
+            .flat_map(|i| fmt0(&[0, i], 200, 128))
+            .collect();
+        let err = ChunkReader::new(buf.as_slice()).read_message().await;
+        assert_invalid_data(err.unwrap_err());
+    }
+
+    #[tokio::test]
+    async fn read_split_message() {
+        let mut buf = fmt0(&[3], 200, 128);
+        buf.push((3 << 6) | 3);
+        buf.extend([1; 72]);
+        let message = ChunkReader::new(buf.as_slice())
+            .read_message()
+            .await
+            .unwrap();
+        assert_eq!(message.payload.len(), 200);
+        assert_eq!(message.stream_id, 1);
+    }
+}
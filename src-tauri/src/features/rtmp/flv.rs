@@ -0,0 +1,42 @@
+use super::chunk::{MSG_AUDIO, MSG_DATA_AMF0, MSG_VIDEO, RtmpMessage};
+
+pub const FLV_HEADER: [u8; 13] = [
+    b'F', b'L', b'V', 0x01, 0x05, 0x00, 0x00, 0x00, 0x09, // header (audio + video)
+    0x00, 0x00, 0x00, 0x00, // PreviousTagSize0
+];
+const FLV_TAG_SCRIPT_DATA: u8 = 18;
+
+/// AMF0 文字列 "@setDataFrame"
+const SET_DATA_FRAME: &[u8] = b"\x02\x00\x0d@setDataFrame";
+
+/// FLV タグのデータ部分を返す。FLV に含めないメッセージは None
+fn tag_data(message: &RtmpMessage) -> Option<(u8, &[u8])> {
+    match message.type_id {
+        MSG_AUDIO | MSG_VIDEO => Some((message.type_id, &message.payload)),
+        MSG_DATA_AMF0 => {
+            // @setDataFrame を取り除くと onMetaData のスクリプトデータになる
+            let data = message
+                .payload
+                .strip_prefix(SET_DATA_FRAME)
+                .unwrap_or(&message.payload);
+            Some((FLV_TAG_SCRIPT_DATA, data))
+        }
+        _ => None,
+    }
+}
+
+/// FLV タグと PreviousTagSize を返す。FLV に含めないメッセージは None
+pub fn encode_tag(message: &RtmpMessage) -> Option<Vec<u8>> {
+    let (tag_type, data) = tag_data(message)?;
+    let data_size = (data.len() as u32).to_be_bytes();
+    let timestamp = message.timestamp.to_be_bytes();
+    let mut tag = vec![tag_type];
+    tag.extend(&data_size[1..]);
+    tag.extend(&timestamp[1..]);
+    tag.push(timestamp[0]); // TimestampExtended
+    tag.extend([0x00, 0x00, 0x00]); // StreamID
+    tag.extend(data);
+    let tag_size = tag.len() as u32;
+    tag.extend(tag_size.to_be_bytes());
+    Some(tag)
+}
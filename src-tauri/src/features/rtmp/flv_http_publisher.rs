@@ -0,0 +1,85 @@
+use std::{net::Ipv4Addr, num::NonZeroU16, time::Duration};
+
+use anyhow::{Result, bail};
+use async_trait::async_trait;
+use log::{debug, trace};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt, BufWriter},
+    net::{TcpListener, TcpStream},
+    time::timeout,
+};
+
+use super::{
+    chunk::RtmpMessage,
+    flv::{FLV_HEADER, encode_tag},
+    rtmp_relay::RelayDestination,
+};
+
+/// PeerCast は接続に失敗すると数秒おきに再試行するので、その間隔より長く待つ
+const ACCEPT_TIMEOUT: Duration = Duration::from_secs(30);
+const MAX_REQUEST_HEADER_LENGTH: usize = 8192;
+
+/// リクエストヘッダーを読み捨てる
+async fn read_request_header(stream: &mut TcpStream) -> Result<()> {
+    let mut header = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !header.ends_with(b"\r\n\r\n") {
+        let len = stream.read(&mut buf).await?;
+        if len == 0 {
+            bail!("connection closed");
+        }
+        header.extend(&buf[..len]);
+        if header.len() > MAX_REQUEST_HEADER_LENGTH {
+            bail!("request header too long");
+        }
+    }
+    trace!("{}", String::from_utf8_lossy(&header));
+    Ok(())
+}
+
+/// PeerCast (Original) が FLV を取得しに来るのを待ち受ける。
+/// PeerCast はチャンネルを作るとすぐに取得しに来るので、チャンネルを作る前に待ち受けを始める
+pub struct FlvHttpListener {
+    listener: TcpListener,
+}
+
+impl FlvHttpListener {
+    pub async fn bind(port: NonZeroU16) -> Result<Self> {
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, port.get())).await?;
+        Ok(Self { listener })
+    }
+
+    /// PeerCast からの接続を待つ。チャンネルを作り直した場合は、もう一度呼ぶ
+    pub async fn accept(&self) -> Result<FlvHttpPublisher> {
+        let Ok(accepted) = timeout(ACCEPT_TIMEOUT, self.listener.accept()).await else {
+            bail!("PeerCast did not connect");
+        };
+        let (mut stream, addr) = accepted?;
+        debug!("flv http connection from {}", addr);
+        read_request_header(&mut stream).await?;
+
+        let mut writer = BufWriter::new(stream);
+        writer
+            .write_all(b"HTTP/1.0 200 OK\r\nContent-Type: video/x-flv\r\n\r\n")
+            .await?;
+        writer.write_all(&FLV_HEADER).await?;
+        writer.flush().await?;
+        Ok(FlvHttpPublisher { writer })
+    }
+}
+
+/// PeerCast (Original) が取得しに来る HTTP で FLV を送る
+pub struct FlvHttpPublisher {
+    writer: BufWriter<TcpStream>,
+}
+
+#[async_trait]
+impl RelayDestination for FlvHttpPublisher {
+    async fn send(&mut self, message: &RtmpMessage) -> Result<()> {
+        if let Some(tag) = encode_tag(message) {
+            self.writer.write_all(&tag).await?;
+            self.writer.flush().await?;
+        }
+        Ok(())
+    }
+}
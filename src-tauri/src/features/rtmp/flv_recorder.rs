@@ -0,0 +1,47 @@
+use anyhow::Result;
+use chrono::Local;
+use tokio::{
+    fs::File,
+    io::{AsyncWriteExt, BufWriter},
+};
+
+use super::{
+    chunk::RtmpMessage,
+    flv::{FLV_HEADER, encode_tag},
+};
+
+/// 中継中の RTMP メッセージを FLV ファイルに記録する
+pub struct FlvRecorder {
+    path: String,
+    writer: BufWriter<File>,
+}
+
+impl FlvRecorder {
+    pub async fn create(directory: &str, channel_name: &str) -> Result<Self> {
+        let path = format!(
+            "{}/{}_{}.flv",
+            directory,
+            Local::now().format("%Y%m%dT%H%M%S"),
+            channel_name
+        );
+        let mut writer = BufWriter::new(File::create(&path).await?);
+        writer.write_all(&FLV_HEADER).await?;
+        Ok(Self { path, writer })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub async fn write(&mut self, message: &RtmpMessage) -> Result<()> {
+        if let Some(tag) = encode_tag(message) {
+            self.writer.write_all(&tag).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn finish(mut self) -> Result<()> {
+        self.writer.flush().await?;
+        Ok(())
+    }
+}
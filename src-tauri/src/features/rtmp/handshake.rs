@@ -0,0 +1,45 @@
+use anyhow::{Result, bail};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+const RTMP_VERSION: u8 = 3;
+const HANDSHAKE_SIZE: usize = 1536;
+
+fn check_version(version: u8) -> Result<()> {
+    if version != RTMP_VERSION {
+        bail!("unsupported RTMP version: {}", version);
+    }
+    Ok(())
+}
+
+/// 暗号化無しの単純なハンドシェイク (サーバー側)
+pub async fn accept(stream: &mut TcpStream) -> Result<()> {
+    check_version(stream.read_u8().await?)?;
+    let mut c1 = vec![0u8; HANDSHAKE_SIZE];
+    stream.read_exact(&mut c1).await?;
+
+    let mut s0_s1_s2 = vec![RTMP_VERSION];
+    s0_s1_s2.resize(1 + HANDSHAKE_SIZE, 0);
+    s0_s1_s2.extend(&c1);
+    stream.write_all(&s0_s1_s2).await?;
+
+    let mut c2 = vec![0u8; HANDSHAKE_SIZE];
+    stream.read_exact(&mut c2).await?;
+    Ok(())
+}
+
+/// 暗号化無しの単純なハンドシェイク (クライアント側)
+pub async fn connect(stream: &mut TcpStream) -> Result<()> {
+    let mut c0_c1 = vec![RTMP_VERSION];
+    c0_c1.resize(1 + HANDSHAKE_SIZE, 0);
+    stream.write_all(&c0_c1).await?;
+
+    check_version(stream.read_u8().await?)?;
+    let mut s1 = vec![0u8; HANDSHAKE_SIZE];
+    stream.read_exact(&mut s1).await?;
+    stream.write_all(&s1).await?;
+
+    let mut s2 = vec![0u8; HANDSHAKE_SIZE];
+    stream.read_exact(&mut s2).await?;
+    Ok(())
+}
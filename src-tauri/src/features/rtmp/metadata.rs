@@ -0,0 +1,92 @@
+use crate::core::entities::stream_properties::StreamProperties;
+
+use super::{
+    amf0::{self, Amf0Value},
+    chunk::{MSG_DATA_AMF0, RtmpMessage},
+};
+
+/// @setDataFrame onMetaData {...} もしくは onMetaData {...} の {...} を返す
+fn metadata_object(message: &RtmpMessage) -> Option<Amf0Value> {
+    if message.type_id != MSG_DATA_AMF0 {
+        return None;
+    }
+    let mut values = amf0::decode_all(&message.payload).ok()?.into_iter();
+    let mut name = values.next()?;
+    if name.as_str() == Some("@setDataFrame") {
+        name = values.next()?;
+    }
+    if name.as_str() != Some("onMetaData") {
+        return None;
+    }
+    values.next()
+}
+
+pub fn is_metadata(message: &RtmpMessage) -> bool {
+    metadata_object(message).is_some()
+}
+
+/// Enhanced RTMP では FourCC が数値で入っている
+fn four_cc(id: f64) -> Option<String> {
+    String::from_utf8((id as u32).to_be_bytes().to_vec()).ok()
+}
+
+fn video_codec(value: &Amf0Value) -> Option<String> {
+    let name = match value {
+        Amf0Value::Number(id) if *id == 2.0 => return Some("H.263".to_owned()),
+        Amf0Value::Number(id) if *id == 4.0 => return Some("VP6".to_owned()),
+        Amf0Value::Number(id) if *id == 7.0 => return Some("H.264".to_owned()),
+        Amf0Value::Number(id) if *id == 12.0 => return Some("HEVC".to_owned()),
+        Amf0Value::Number(id) if *id > 255.0 => four_cc(*id)?,
+        Amf0Value::String(name) => name.clone(),
+        _ => return None,
+    };
+    Some(
+        match name.as_str() {
+            "avc1" => "H.264",
+            "hvc1" | "hev1" => "HEVC",
+            "av01" => "AV1",
+            "vp09" => "VP9",
+            name => name,
+        }
+        .to_owned(),
+    )
+}
+
+fn audio_codec(value: &Amf0Value) -> Option<String> {
+    let name = match value {
+        Amf0Value::Number(id) if *id == 2.0 => return Some("MP3".to_owned()),
+        Amf0Value::Number(id) if *id == 10.0 => return Some("AAC".to_owned()),
+        Amf0Value::Number(id) if *id == 11.0 => return Some("Speex".to_owned()),
+        Amf0Value::Number(id) if *id > 255.0 => four_cc(*id)?,
+        Amf0Value::String(name) => name.clone(),
+        _ => return None,
+    };
+    Some(
+        match name.as_str() {
+            "mp4a" => "AAC",
+            "Opus" | "opus" => "Opus",
+            name => name,
+        }
+        .to_owned(),
+    )
+}
+
+/// onMetaData から配信のプロパティを取り出す
+pub fn stream_properties(message: &RtmpMessage) -> Option<StreamProperties> {
+    let metadata = metadata_object(message)?;
+    let number = |key: &str| metadata.get(key).and_then(|x| x.as_f64());
+    let positive_u32 = |key: &str| number(key).filter(|x| *x > 0.0).map(|x| x.round() as u32);
+    Some(StreamProperties {
+        width: positive_u32("width"),
+        height: positive_u32("height"),
+        framerate: number("framerate").filter(|x| *x > 0.0),
+        video_codec: metadata.get("videocodecid").and_then(video_codec),
+        audio_codec: metadata.get("audiocodecid").and_then(audio_codec),
+        video_bitrate: positive_u32("videodatarate"),
+        audio_bitrate: positive_u32("audiodatarate"),
+        encoder: metadata
+            .get("encoder")
+            .and_then(|x| x.as_str())
+            .map(|x| x.to_owned()),
+    })
+}
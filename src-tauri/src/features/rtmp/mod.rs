@@ -1,4 +1,18 @@
+mod amf0;
+mod bitrate_meter;
+mod chunk;
+mod flv;
+pub mod flv_http_publisher;
+pub mod flv_recorder;
+mod handshake;
+mod metadata;
+pub mod publish_session;
+pub mod rtmp_fan_out;
 mod rtmp_listener;
+pub mod rtmp_publisher;
+pub mod rtmp_relay;
 pub mod rtmp_server;
+pub mod spliced_destination;
+pub mod stream_analyzer;
 
 pub use self::rtmp_listener::RtmpListenerDelegate;
@@ -0,0 +1,210 @@
+use std::{fmt::Display, sync::Arc};
+
+use anyhow::{Result, bail};
+use log::trace;
+use tokio::{
+    net::{
+        TcpStream,
+        tcp::{OwnedReadHalf, OwnedWriteHalf},
+    },
+    select,
+    sync::Notify,
+};
+
+use super::{
+    amf0::{Amf0Value, object},
+    chunk::{ChunkReader, ChunkWriter, MSG_COMMAND_AMF0, MSG_COMMAND_AMF3, RtmpMessage},
+    handshake,
+};
+
+pub const PUBLISH_BAD_NAME: &str = "NetStream.Publish.BadName";
+pub const PUBLISH_FAILED: &str = "NetStream.Publish.Failed";
+
+const WINDOW_ACK_SIZE: u32 = 2500000;
+const CHUNK_SIZE: u32 = 4096;
+const STREAM_ID: u32 = 1;
+
+/// 他の配信元に切り替えるために中断された
+#[derive(Debug)]
+pub struct Interrupted;
+
+impl Display for Interrupted {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "interrupted")
+    }
+}
+
+impl std::error::Error for Interrupted {}
+
+fn command_result(transaction_id: f64, values: &[Amf0Value]) -> RtmpMessage {
+    let mut all = vec![
+        Amf0Value::String("_result".to_owned()),
+        Amf0Value::Number(transaction_id),
+    ];
+    all.extend_from_slice(values);
+    RtmpMessage::command(3, 0, &all)
+}
+
+fn on_status(stream_id: u32, level: &str, code: &str, description: &str) -> RtmpMessage {
+    let info = object(&[
+        ("level", Amf0Value::String(level.to_owned())),
+        ("code", Amf0Value::String(code.to_owned())),
+        ("description", Amf0Value::String(description.to_owned())),
+    ]);
+    let values = [
+        Amf0Value::String("onStatus".to_owned()),
+        Amf0Value::Number(0.0),
+        Amf0Value::Null,
+        info,
+    ];
+    RtmpMessage::command(5, stream_id, &values)
+}
+
+/// publish コマンドまで受け取った段階の接続
+pub struct PublishRequest {
+    reader: ChunkReader<OwnedReadHalf>,
+    writer: ChunkWriter<OwnedWriteHalf>,
+    stream_key: String,
+    stream_id: u32,
+    interrupter: Arc<Notify>,
+}
+
+impl PublishRequest {
+    /// ハンドシェイクを行い、publish コマンドが届くまで読み進める
+    pub async fn read(mut stream: TcpStream) -> Result<Self> {
+        handshake::accept(&mut stream).await?;
+        let (read_half, write_half) = stream.into_split();
+        let mut reader = ChunkReader::new(read_half);
+        let mut writer = ChunkWriter::new(write_half);
+        let mut connected = false;
+        loop {
+            let message = reader.read_message().await?;
+            if !matches!(message.type_id, MSG_COMMAND_AMF0 | MSG_COMMAND_AMF3) {
+                continue;
+            }
+            let values = message.decode_command()?;
+            let name = values.first().and_then(|x| x.as_str()).unwrap_or_default();
+            let transaction_id = values.get(1).and_then(|x| x.as_f64()).unwrap_or_default();
+            trace!("rtmp command: {} {}", name, transaction_id);
+            match name {
+                "connect" => {
+                    connected = true;
+                    let properties = object(&[
+                        ("fmsVer", Amf0Value::String("FMS/3,0,1,123".to_owned())),
+                        ("capabilities", Amf0Value::Number(31.0)),
+                    ]);
+                    let information = object(&[
+                        ("level", Amf0Value::String("status".to_owned())),
+                        (
+                            "code",
+                            Amf0Value::String("NetConnection.Connect.Success".to_owned()),
+                        ),
+                        (
+                            "description",
+                            Amf0Value::String("Connection succeeded.".to_owned()),
+                        ),
+                        ("objectEncoding", Amf0Value::Number(0.0)),
+                    ]);
+                    let window_ack_size = RtmpMessage::window_ack_size(WINDOW_ACK_SIZE);
+                    writer.write_message(&window_ack_size).await?;
+                    let set_peer_bandwidth = RtmpMessage::set_peer_bandwidth(WINDOW_ACK_SIZE);
+                    writer.write_message(&set_peer_bandwidth).await?;
+                    writer.set_chunk_size(CHUNK_SIZE).await?;
+                    let result = command_result(transaction_id, &[properties, information]);
+                    writer.write_message(&result).await?;
+                }
+                "createStream" => {
+                    let stream_id = Amf0Value::Number(STREAM_ID as f64);
+                    let result = command_result(transaction_id, &[Amf0Value::Null, stream_id]);
+                    writer.write_message(&result).await?;
+                }
+                "publish" => {
+                    if !connected {
+                        bail!("publish before connect");
+                    }
+                    let stream_key = values.get(3).and_then(|x| x.as_str()).unwrap_or_default();
+                    return Ok(Self {
+                        reader,
+                        writer,
+                        stream_key: stream_key.to_owned(),
+                        stream_id: message.stream_id,
+                        interrupter: Default::default(),
+                    });
+                }
+                _ => {
+                    if transaction_id != 0.0 {
+                        let values = [Amf0Value::Null, Amf0Value::Undefined];
+                        writer
+                            .write_message(&command_result(transaction_id, &values))
+                            .await?;
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn stream_key(&self) -> &str {
+        &self.stream_key
+    }
+
+    /// notify_one すると配信中の PublishSession::read_media が Interrupted を返す
+    pub fn interrupter(&self) -> Arc<Notify> {
+        self.interrupter.clone()
+    }
+
+    pub async fn reject(mut self, code: &str, description: &str) {
+        let status = on_status(self.stream_id, "error", code, description);
+        if let Err(err) = self.writer.write_message(&status).await {
+            trace!("reject error: {}", err);
+        }
+        self.writer.shutdown().await;
+    }
+
+    pub async fn accept(mut self) -> Result<PublishSession> {
+        let stream_begin = RtmpMessage::stream_begin(self.stream_id);
+        self.writer.write_message(&stream_begin).await?;
+        let status = on_status(
+            self.stream_id,
+            "status",
+            "NetStream.Publish.Start",
+            "Start publishing.",
+        );
+        self.writer.write_message(&status).await?;
+        Ok(PublishSession {
+            reader: self.reader,
+            _writer: self.writer,
+            interrupter: self.interrupter,
+        })
+    }
+}
+
+/// 配信が開始された接続
+pub struct PublishSession {
+    reader: ChunkReader<OwnedReadHalf>,
+    _writer: ChunkWriter<OwnedWriteHalf>,
+    interrupter: Arc<Notify>,
+}
+
+impl PublishSession {
+    /// 映像、音声、データのメッセージを返す。配信が終了した場合は None を返す
+    pub async fn read_media(&mut self) -> Result<Option<RtmpMessage>> {
+        loop {
+            let message = select! {
+                message = self.reader.read_message() => message?,
+                _ = self.interrupter.notified() => return Err(Interrupted.into()),
+            };
+            if message.is_media() {
+                return Ok(Some(message));
+            }
+            if !matches!(message.type_id, MSG_COMMAND_AMF0 | MSG_COMMAND_AMF3) {
+                continue;
+            }
+            let values = message.decode_command()?;
+            let name = values.first().and_then(|x| x.as_str()).unwrap_or_default();
+            trace!("rtmp command: {}", name);
+            if matches!(name, "FCUnpublish" | "deleteStream" | "closeStream") {
+                return Ok(None);
+            }
+        }
+    }
+}
@@ -0,0 +1,232 @@
+use std::{sync::Arc, time::Duration};
+
+use anyhow::{Result, anyhow, bail};
+use log::{info, warn};
+use tokio::{
+    spawn,
+    sync::broadcast::{self, error::RecvError},
+    task::JoinHandle,
+    time::sleep,
+};
+
+use crate::core::utils::failure::Failure;
+
+use super::{
+    chunk::{MSG_AUDIO, MSG_DATA_AMF0, MSG_VIDEO, RtmpMessage},
+    metadata::is_metadata,
+    rtmp_publisher::RtmpPublisher,
+};
+
+const CHANNEL_CAPACITY: usize = 1024;
+const RECONNECT_INTERVAL: Duration = Duration::from_secs(5);
+
+type OnStatus = Arc<dyn Send + Sync + Fn(Failure)>;
+
+/// rtmp://host[:port]/app/stream_name
+struct RtmpUrl {
+    url: String,
+    addr: String,
+    app: String,
+    stream_name: String,
+}
+
+impl RtmpUrl {
+    fn parse(url: &str) -> Result<Self> {
+        let Some(rest) = url.strip_prefix("rtmp://") else {
+            bail!("rtmp:// で始まる URL のみ対応しています");
+        };
+        let (authority, path) = rest
+            .split_once('/')
+            .ok_or_else(|| anyhow!("パスがありません"))?;
+        let (app, stream_name) = path
+            .rsplit_once('/')
+            .ok_or_else(|| anyhow!("ストリームキーがありません"))?;
+        if authority.is_empty() || app.is_empty() || stream_name.is_empty() {
+            bail!("不正な URL です");
+        }
+        let addr = if authority.contains(':') && !authority.ends_with(']') {
+            authority.to_owned()
+        } else {
+            format!("{}:1935", authority)
+        };
+        Ok(Self {
+            url: url.to_owned(),
+            addr,
+            app: app.to_owned(),
+            stream_name: stream_name.to_owned(),
+        })
+    }
+
+    /// ストリームキーを伏せた URL
+    fn masked(&self) -> String {
+        let url = self
+            .url
+            .strip_suffix(&self.stream_name)
+            .unwrap_or(&self.url);
+        format!("{}***", url)
+    }
+}
+
+/// 途中から接続した配信先に最初に送るメッセージ
+#[derive(Default)]
+pub(super) struct StreamHeaders {
+    metadata: Option<RtmpMessage>,
+    video_sequence_header: Option<RtmpMessage>,
+    audio_sequence_header: Option<RtmpMessage>,
+}
+
+impl StreamHeaders {
+    pub(super) fn update(&mut self, message: &RtmpMessage) {
+        let slot = match message.type_id {
+            MSG_DATA_AMF0 if is_metadata(message) => &mut self.metadata,
+            MSG_VIDEO if message.is_sequence_header() => &mut self.video_sequence_header,
+            MSG_AUDIO if message.is_sequence_header() => &mut self.audio_sequence_header,
+            _ => return,
+        };
+        *slot = Some(message.clone());
+    }
+
+    pub(super) fn messages(&self) -> Vec<RtmpMessage> {
+        [
+            &self.metadata,
+            &self.video_sequence_header,
+            &self.audio_sequence_header,
+        ]
+        .into_iter()
+        .flatten()
+        .cloned()
+        .collect()
+    }
+}
+
+async fn forward_messages(
+    publisher: &mut RtmpPublisher,
+    receiver: &mut broadcast::Receiver<Arc<RtmpMessage>>,
+    headers: &std::sync::Mutex<StreamHeaders>,
+) -> Result<()> {
+    let headers = headers.lock().unwrap().messages();
+    for header in &headers {
+        publisher.send(header).await?;
+    }
+    // キーフレームが届くまで映像を送らない
+    let mut waiting_keyframe = true;
+    loop {
+        let message = match receiver.recv().await {
+            Ok(message) => message,
+            Err(RecvError::Lagged(skipped)) => {
+                warn!("skipped {} messages", skipped);
+                waiting_keyframe = true;
+                continue;
+            }
+            Err(RecvError::Closed) => return Ok(()),
+        };
+        if message.type_id == MSG_VIDEO && !message.is_sequence_header() {
+            if waiting_keyframe && !message.is_keyframe() {
+                continue;
+            }
+            waiting_keyframe = false;
+        }
+        publisher.send(&message).await?;
+    }
+}
+
+/// 切断されても再接続し続ける
+async fn forward(
+    url: RtmpUrl,
+    mut receiver: broadcast::Receiver<Arc<RtmpMessage>>,
+    headers: Arc<std::sync::Mutex<StreamHeaders>>,
+    on_status: OnStatus,
+) {
+    let masked_url = url.masked();
+    let mut failed = false;
+    loop {
+        match RtmpPublisher::connect(&url.addr, &url.app, &url.stream_name).await {
+            Err(err) => {
+                warn!("{}: {}", masked_url, err);
+                if !failed {
+                    let message = format!("{} への接続に失敗しました。再試行します。", masked_url);
+                    on_status(Failure::Warn(message));
+                    failed = true;
+                }
+            }
+            Ok(mut publisher) => {
+                info!("{}: connected", masked_url);
+                if failed {
+                    let message = format!("{} に再接続しました。", masked_url);
+                    on_status(Failure::Warn(message));
+                }
+                // 接続していない間に溜まったメッセージは捨てる
+                receiver = receiver.resubscribe();
+                let Err(err) = forward_messages(&mut publisher, &mut receiver, &headers).await
+                else {
+                    return;
+                };
+                warn!("{}: {}", masked_url, err);
+                let message = format!("{} との接続が切れました。再接続します。", masked_url);
+                on_status(Failure::Warn(message));
+                failed = true;
+            }
+        }
+        sleep(RECONNECT_INTERVAL).await;
+    }
+}
+
+/// 配信元のメッセージを追加の配信先に中継する。
+/// 配信先ごとに独立して再接続するため、PeerCast への中継には影響しない
+pub struct RtmpFanOut {
+    sender: broadcast::Sender<Arc<RtmpMessage>>,
+    headers: Arc<std::sync::Mutex<StreamHeaders>>,
+    handles: Vec<JoinHandle<()>>,
+}
+
+impl RtmpFanOut {
+    pub fn spawn(urls: &[String], on_status: OnStatus) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        let headers: Arc<std::sync::Mutex<StreamHeaders>> = Default::default();
+        let handles = urls
+            .iter()
+            .enumerate()
+            .filter(|(_, url)| !url.trim().is_empty())
+            .filter_map(|(i, url)| match RtmpUrl::parse(url.trim()) {
+                Ok(url) => Some(url),
+                Err(err) => {
+                    // NOTE: ストリームキーが含まれている可能性があるので URL は表示しない
+                    let message =
+                        format!("{} 番目の追加の配信先は使用できません。({})", i + 1, err);
+                    on_status(Failure::Warn(message));
+                    None
+                }
+            })
+            .map(|url| {
+                spawn(forward(
+                    url,
+                    sender.subscribe(),
+                    headers.clone(),
+                    on_status.clone(),
+                ))
+            })
+            .collect();
+        Self {
+            sender,
+            headers,
+            handles,
+        }
+    }
+
+    pub fn send(&self, message: &RtmpMessage) {
+        if self.handles.is_empty() {
+            return;
+        }
+        self.headers.lock().unwrap().update(message);
+        // NOTE: 受信側がいない場合のエラーは無視する
+        let _ = self.sender.send(Arc::new(message.clone()));
+    }
+}
+
+impl Drop for RtmpFanOut {
+    fn drop(&mut self) {
+        for handle in &self.handles {
+            handle.abort();
+        }
+    }
+}
@@ -1,69 +1,401 @@
+use std::net::{IpAddr, SocketAddr};
 use std::num::NonZeroU16;
-use std::sync::Weak;
+use std::sync::{Arc, Weak};
+use std::time::Duration;
 
+use anyhow::{anyhow, bail};
 use async_trait::async_trait;
-use log::debug;
-use tokio::net::TcpStream;
-use tokio::{net::TcpListener, spawn, task::JoinHandle};
+use log::{debug, warn};
+use socket2::{Domain, Protocol, Socket, Type};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::select;
+use tokio::sync::{Mutex, MutexGuard, Notify};
+use tokio::time::{sleep, timeout};
+use tokio::{spawn, task::JoinHandle};
+
+use crate::core::{entities::settings::ConcurrentPublisherPolicy, utils::failure::Failure};
+
+use super::publish_session::{PUBLISH_BAD_NAME, PublishRequest};
+
+const PUBLISH_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const ACCEPT_RETRY_INTERVAL: Duration = Duration::from_secs(1);
+/// 配信を切り替える時に、配信中の配信元を中断し直す間隔
+const REPLACE_RETRY_INTERVAL: Duration = Duration::from_millis(100);
 
 #[async_trait]
 pub trait RtmpListenerDelegate {
-    async fn on_connect(&self, incoming: TcpStream);
+    async fn on_connect(&self, request: PublishRequest);
+    fn on_failure(&self, failure: &Failure);
+}
+
+/// 全ての待ち受けで共有する状態
+#[derive(Default)]
+struct Shared {
+    stream_key: std::sync::Mutex<String>,
+    concurrent_publisher_policy: std::sync::Mutex<ConcurrentPublisherPolicy>,
+    /// 配信を一度に一つずつ処理するためのロック
+    publishing: Mutex<()>,
+    /// 配信中の配信元を中断する。publishing を取ってから設定するので、少しの間は古いままになる
+    interrupter: std::sync::Mutex<Option<Arc<Notify>>>,
+}
+
+impl Shared {
+    fn interrupt(&self) {
+        if let Some(interrupter) = self.interrupter.lock().unwrap().as_ref() {
+            interrupter.notify_one();
+        }
+    }
+
+    /// 配信中の配信元を中断して publishing を取る。
+    /// 前の配信元が終わってから次の配信元が interrupter を設定するまでの間に呼ばれても切り替えられるよう、
+    /// 取れるまで中断し直す
+    async fn replace(&self) -> MutexGuard<'_, ()> {
+        let publishing = self.publishing.lock();
+        tokio::pin!(publishing);
+        loop {
+            self.interrupt();
+            select! {
+                publishing = &mut publishing => return publishing,
+                _ = sleep(REPLACE_RETRY_INTERVAL) => {}
+            }
+        }
+    }
+}
+
+/// どこまで一致したかが処理時間から分からないよう、長さが同じなら全てのバイトを比べる
+fn stream_key_matches(expected: &str, actual: &str) -> bool {
+    expected.len() == actual.len()
+        && expected
+            .bytes()
+            .zip(actual.bytes())
+            .fold(0, |diff, (a, b)| diff | (a ^ b))
+            == 0
+}
+
+type DynSendSyncRtmpListenerDelegate = dyn RtmpListenerDelegate + Send + Sync;
+
+async fn handle_connection(
+    incoming: TcpStream,
+    addr: SocketAddr,
+    delegate: Weak<DynSendSyncRtmpListenerDelegate>,
+    shared: Arc<Shared>,
+) {
+    let request = match timeout(PUBLISH_REQUEST_TIMEOUT, PublishRequest::read(incoming)).await {
+        Ok(Ok(request)) => request,
+        Ok(Err(err)) => {
+            debug!("invalid rtmp connection from {}: {}", addr, err);
+            return;
+        }
+        Err(_) => {
+            debug!("rtmp connection from {} timed out", addr);
+            return;
+        }
+    };
+    let Some(delegate) = delegate.upgrade() else {
+        return;
+    };
+    let stream_key = shared.stream_key.lock().unwrap().clone();
+    if !stream_key.is_empty() && !stream_key_matches(&stream_key, request.stream_key()) {
+        warn!("invalid stream key from {}", addr);
+        request
+            .reject(PUBLISH_BAD_NAME, "Invalid stream key.")
+            .await;
+        let message = format!(
+            "ストリームキーが一致しないため {} からの配信を拒否しました。",
+            addr.ip()
+        );
+        delegate.on_failure(&Failure::Warn(message));
+        return;
+    }
+
+    let publishing = match shared.publishing.try_lock() {
+        Ok(publishing) => publishing,
+        Err(_) => {
+            let policy = *shared.concurrent_publisher_policy.lock().unwrap();
+            match policy {
+                ConcurrentPublisherPolicy::Reject => {
+                    request
+                        .reject(PUBLISH_BAD_NAME, "Another publisher is streaming.")
+                        .await;
+                    let message = format!("配信中のため {} からの配信を拒否しました。", addr.ip());
+                    delegate.on_failure(&Failure::Warn(message));
+                    return;
+                }
+                ConcurrentPublisherPolicy::Replace => {
+                    let message = format!("{} からの配信に切り替えます。", addr.ip());
+                    delegate.on_failure(&Failure::Warn(message));
+                    shared.replace().await
+                }
+                ConcurrentPublisherPolicy::Queue => {
+                    let message =
+                        format!("配信中のため {} からの配信を待機させています。", addr.ip());
+                    delegate.on_failure(&Failure::Warn(message));
+                    shared.publishing.lock().await
+                }
+            }
+        }
+    };
+
+    *shared.interrupter.lock().unwrap() = Some(request.interrupter());
+    log::trace!("on_connect begin");
+    delegate.on_connect(request).await;
+    log::trace!("on_connect end");
+    *shared.interrupter.lock().unwrap() = None;
+    drop(publishing);
+}
+
+async fn accept_loop(
+    listener: TcpListener,
+    delegate: Weak<DynSendSyncRtmpListenerDelegate>,
+    shared: Arc<Shared>,
+) {
+    loop {
+        let (incoming, addr) = match listener.accept().await {
+            Ok(ok) => ok,
+            Err(err) => {
+                warn!("accept error: {}", err);
+                let Some(delegate) = delegate.upgrade() else {
+                    return;
+                };
+                let message = format!("RTMP の接続の受け付けに失敗しました。({})", err);
+                delegate.on_failure(&Failure::Warn(message));
+                sleep(ACCEPT_RETRY_INTERVAL).await;
+                continue;
+            }
+        };
+        spawn(handle_connection(
+            incoming,
+            addr,
+            delegate.clone(),
+            shared.clone(),
+        ));
+    }
+}
+
+/// NOTE: 0.0.0.0 と :: を同時に待ち受けられるよう、IPv6 のソケットは IPv6 だけを受け付ける
+fn bind(listen_addr: SocketAddr) -> std::io::Result<TcpListener> {
+    let socket = Socket::new(
+        Domain::for_address(listen_addr),
+        Type::STREAM,
+        Some(Protocol::TCP),
+    )?;
+    if listen_addr.is_ipv6() {
+        socket.set_only_v6(true)?;
+    }
+    // NOTE: TcpListener::bind と同じく、Windows 以外では再起動直後にも待ち受けられるようにする
+    #[cfg(not(windows))]
+    socket.set_reuse_address(true)?;
+    socket.set_nonblocking(true)?;
+    socket.bind(&listen_addr.into())?;
+    socket.listen(1024)?;
+    TcpListener::from_std(socket.into())
 }
 
 pub struct RtmpListener {
-    delegate: Option<Weak<dyn RtmpListenerDelegate + Send + Sync>>,
-    port: Option<NonZeroU16>,
-    listener_handle: Option<JoinHandle<()>>,
+    delegate: Option<Weak<DynSendSyncRtmpListenerDelegate>>,
+    listen_addrs: Vec<SocketAddr>,
+    shared: Arc<Shared>,
+    listener_handles: Vec<JoinHandle<()>>,
 }
 
 impl RtmpListener {
     pub fn new() -> Self {
         Self {
             delegate: None,
-            port: None,
-            listener_handle: None,
+            listen_addrs: Vec::new(),
+            shared: Default::default(),
+            listener_handles: Vec::new(),
         }
     }
 
-    pub fn set_delegate(&mut self, delegate: Weak<dyn RtmpListenerDelegate + Send + Sync>) {
+    pub fn set_delegate(&mut self, delegate: Weak<DynSendSyncRtmpListenerDelegate>) {
         self.delegate = Some(delegate);
     }
 
+    /// 空文字列の場合は認証を行わない
+    pub fn set_stream_key(&self, stream_key: &str) {
+        stream_key.clone_into(&mut self.shared.stream_key.lock().unwrap());
+    }
+
+    pub fn set_concurrent_publisher_policy(&self, policy: ConcurrentPublisherPolicy) {
+        *self.shared.concurrent_publisher_policy.lock().unwrap() = policy;
+    }
+
     pub fn stop_listener(&mut self) {
-        if let Some(listener_handle) = &self.listener_handle {
+        for listener_handle in self.listener_handles.drain(..) {
             listener_handle.abort();
-            self.listener_handle = None;
-            self.port = None;
         }
+        self.listen_addrs.clear();
     }
 
-    async fn spawn_listener(&mut self, rtmp_listen_port: NonZeroU16) -> anyhow::Result<()> {
-        assert!(self.listener_handle.is_none());
-        let delegate = self.delegate.clone().unwrap();
-        let rtmp_listen_host = format!("0.0.0.0:{}", rtmp_listen_port);
-        let listener = TcpListener::bind(&rtmp_listen_host).await?;
-        self.port = Some(rtmp_listen_port);
-        self.listener_handle = Some(spawn(async move {
-            debug!("listening on {}", rtmp_listen_port);
+    fn spawn_listener(
+        &mut self,
+        listener: TcpListener,
+        delegate: Weak<DynSendSyncRtmpListenerDelegate>,
+    ) {
+        let shared = self.shared.clone();
+        self.listener_handles
+            .push(spawn(accept_loop(listener, delegate, shared)));
+    }
 
-            let delegate = delegate.clone();
-            loop {
-                let (incoming, _addr) = listener.accept().await.unwrap();
+    /// 一部のアドレスで待ち受けに失敗した場合は delegate に警告を通知する。
+    /// 全てのアドレスで失敗した場合はエラーを返す
+    pub async fn update_listen_addrs(
+        &mut self,
+        listen_hosts: &[String],
+        listen_port: NonZeroU16,
+    ) -> anyhow::Result<()> {
+        let listen_addrs = listen_hosts
+            .iter()
+            .map(|host| {
+                let ip: IpAddr = host
+                    .parse()
+                    .map_err(|_| anyhow!("不正な待ち受けアドレスです: {}", host))?;
+                Ok(SocketAddr::new(ip, listen_port.get()))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        if listen_addrs.is_empty() {
+            bail!("RTMP の待ち受けアドレスが設定されていません。");
+        }
+        if self.listen_addrs == listen_addrs {
+            return Ok(());
+        }
+        let Some(delegate) = self.delegate.clone() else {
+            bail!("RTMP の待ち受けの準備ができていません。");
+        };
+        // NOTE: 同じアドレスで待ち受け直せるよう、ソケットが閉じられるまで待つ
+        for listener_handle in self.listener_handles.drain(..) {
+            listener_handle.abort();
+            let _ = listener_handle.await;
+        }
+        self.listen_addrs.clear();
 
-                log::trace!("on_connect begin");
-                delegate.upgrade().unwrap().on_connect(incoming).await;
-                log::trace!("on_connect end");
+        let mut errors = Vec::new();
+        // NOTE: 失敗したアドレスを次の呼び出しで再試行できるよう、待ち受けられたアドレスだけを覚える
+        let mut bound_addrs = Vec::new();
+        for &listen_addr in &listen_addrs {
+            match bind(listen_addr) {
+                Ok(listener) => {
+                    debug!("listening on {}", listen_addr);
+                    self.spawn_listener(listener, delegate.clone());
+                    bound_addrs.push(listen_addr);
+                }
+                Err(err) => errors.push(format!("{} ({})", listen_addr, err)),
             }
-        }));
+        }
+        if bound_addrs.is_empty() {
+            bail!("RTMP の待ち受けに失敗しました: {}", errors.join(", "));
+        }
+        self.listen_addrs = bound_addrs;
+        if !errors.is_empty() {
+            let message = format!("RTMP の待ち受けに失敗しました: {}", errors.join(", "));
+            match delegate.upgrade() {
+                Some(delegate) => delegate.on_failure(&Failure::Warn(message)),
+                None => warn!("{}", message),
+            }
+        }
         Ok(())
     }
+}
 
-    pub async fn update_listen_port(&mut self, listen_port: NonZeroU16) -> anyhow::Result<()> {
-        if self.port == Some(listen_port) {
-            return Ok(());
-        }
-        self.stop_listener();
-        self.spawn_listener(listen_port).await
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    struct NullDelegate;
+
+    #[async_trait]
+    impl RtmpListenerDelegate for NullDelegate {
+        async fn on_connect(&self, _request: PublishRequest) {}
+        fn on_failure(&self, _failure: &Failure) {}
+    }
+
+    #[test]
+    fn stream_key() {
+        assert!(stream_key_matches("key", "key"));
+        assert!(!stream_key_matches("key", "kez"));
+        assert!(!stream_key_matches("key", "ke"));
+        assert!(!stream_key_matches("key", "keys"));
+        assert!(!stream_key_matches("key", ""));
+    }
+
+    #[tokio::test]
+    async fn replace_before_interrupter_is_set() {
+        let shared = Arc::new(Shared::default());
+        let (locked, wait_locked) = tokio::sync::oneshot::channel();
+        let holder = spawn({
+            let shared = shared.clone();
+            async move {
+                let _publishing = shared.publishing.lock().await;
+                locked.send(()).unwrap();
+                // NOTE: 配信を始めてから interrupter を設定するまでの間に切り替えが来る
+                sleep(Duration::from_millis(300)).await;
+                let interrupter = Arc::new(Notify::new());
+                *shared.interrupter.lock().unwrap() = Some(interrupter.clone());
+                interrupter.notified().await;
+                *shared.interrupter.lock().unwrap() = None;
+            }
+        });
+        wait_locked.await.unwrap();
+        let _publishing = timeout(Duration::from_secs(5), shared.replace())
+            .await
+            .unwrap();
+        holder.await.unwrap();
+    }
+
+    fn free_port() -> NonZeroU16 {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        NonZeroU16::new(listener.local_addr().unwrap().port()).unwrap()
+    }
+
+    fn rtmp_listener(delegate: &Arc<DynSendSyncRtmpListenerDelegate>) -> RtmpListener {
+        let mut rtmp_listener = RtmpListener::new();
+        rtmp_listener.set_delegate(Arc::downgrade(delegate));
+        rtmp_listener
+    }
+
+    #[tokio::test]
+    async fn listen_ipv4_and_ipv6_any() {
+        let delegate: Arc<DynSendSyncRtmpListenerDelegate> = Arc::new(NullDelegate);
+        let mut rtmp_listener = rtmp_listener(&delegate);
+        let hosts = ["0.0.0.0".to_owned(), "::".to_owned()];
+        rtmp_listener
+            .update_listen_addrs(&hosts, free_port())
+            .await
+            .unwrap();
+        assert_eq!(rtmp_listener.listen_addrs.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn retry_failed_addrs() {
+        let delegate: Arc<DynSendSyncRtmpListenerDelegate> = Arc::new(NullDelegate);
+        let mut rtmp_listener = rtmp_listener(&delegate);
+        let port = free_port();
+        let hosts = ["127.0.0.1".to_owned(), "::1".to_owned()];
+        let blocker = std::net::TcpListener::bind(("127.0.0.1", port.get())).unwrap();
+        rtmp_listener
+            .update_listen_addrs(&hosts, port)
+            .await
+            .unwrap();
+        assert_eq!(rtmp_listener.listen_addrs.len(), 1);
+
+        drop(blocker);
+        rtmp_listener
+            .update_listen_addrs(&hosts, port)
+            .await
+            .unwrap();
+        assert_eq!(rtmp_listener.listen_addrs.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn fail_without_delegate() {
+        let mut rtmp_listener = RtmpListener::new();
+        let hosts = ["127.0.0.1".to_owned()];
+        assert!(
+            rtmp_listener
+                .update_listen_addrs(&hosts, free_port())
+                .await
+                .is_err()
+        );
     }
 }
@@ -0,0 +1,160 @@
+use anyhow::{Result, anyhow, bail};
+use async_trait::async_trait;
+use log::trace;
+use tokio::{
+    net::tcp::{OwnedReadHalf, OwnedWriteHalf},
+    spawn,
+    task::JoinHandle,
+};
+
+use crate::core::utils::tcp::connect;
+
+use super::{
+    amf0::{Amf0Value, object},
+    chunk::{
+        ChunkReader, ChunkWriter, MSG_AUDIO, MSG_COMMAND_AMF0, MSG_COMMAND_AMF3, MSG_VIDEO,
+        RtmpMessage,
+    },
+    handshake,
+    rtmp_relay::RelayDestination,
+};
+
+const CHUNK_SIZE: u32 = 4096;
+
+/// 指定したトランザクションの応答が届くまで読み進める
+async fn read_response(
+    reader: &mut ChunkReader<OwnedReadHalf>,
+    transaction_id: f64,
+) -> Result<Vec<Amf0Value>> {
+    loop {
+        let message = reader.read_message().await?;
+        if !matches!(message.type_id, MSG_COMMAND_AMF0 | MSG_COMMAND_AMF3) {
+            continue;
+        }
+        let values = message.decode_command()?;
+        let name = values.first().and_then(|x| x.as_str()).unwrap_or_default();
+        trace!("rtmp response: {} {:?}", name, values.get(1));
+        if name == "onStatus" && transaction_id == 0.0 {
+            return Ok(values);
+        }
+        if values.get(1).and_then(|x| x.as_f64()) != Some(transaction_id) {
+            continue;
+        }
+        match name {
+            "_result" => return Ok(values),
+            "_error" => bail!("{:?}", values.get(3)),
+            _ => {}
+        }
+    }
+}
+
+fn command(name: &str, transaction_id: f64, args: &[Amf0Value]) -> Vec<Amf0Value> {
+    let mut values = vec![
+        Amf0Value::String(name.to_owned()),
+        Amf0Value::Number(transaction_id),
+    ];
+    values.extend_from_slice(args);
+    values
+}
+
+/// RTMP サーバーに publish するクライアント
+pub struct RtmpPublisher {
+    writer: ChunkWriter<OwnedWriteHalf>,
+    stream_id: u32,
+    reader_handle: JoinHandle<()>,
+}
+
+impl RtmpPublisher {
+    pub async fn connect(host: &str, app: &str, stream_name: &str) -> Result<Self> {
+        let mut stream = connect(host).await.map_err(|err| anyhow!("{:?}", err))?;
+        handshake::connect(&mut stream).await?;
+        let (read_half, write_half) = stream.into_split();
+        let mut reader = ChunkReader::new(read_half);
+        let mut writer = ChunkWriter::new(write_half);
+        writer.set_chunk_size(CHUNK_SIZE).await?;
+
+        let command_object = object(&[
+            ("app", Amf0Value::String(app.to_owned())),
+            ("type", Amf0Value::String("nonprivate".to_owned())),
+            (
+                "flashVer",
+                Amf0Value::String("FMLE/3.0 (compatible; FMSc/1.0)".to_owned()),
+            ),
+            (
+                "tcUrl",
+                Amf0Value::String(format!("rtmp://{}/{}", host, app)),
+            ),
+        ]);
+        let connect = command("connect", 1.0, &[command_object]);
+        writer
+            .write_message(&RtmpMessage::command(3, 0, &connect))
+            .await?;
+        read_response(&mut reader, 1.0).await?;
+
+        let create_stream = command("createStream", 2.0, &[Amf0Value::Null]);
+        writer
+            .write_message(&RtmpMessage::command(3, 0, &create_stream))
+            .await?;
+        let stream_id = read_response(&mut reader, 2.0)
+            .await?
+            .get(3)
+            .and_then(|x| x.as_f64())
+            .ok_or_else(|| anyhow!("invalid createStream result"))? as u32;
+
+        let args = [
+            Amf0Value::Null,
+            Amf0Value::String(stream_name.to_owned()),
+            Amf0Value::String("live".to_owned()),
+        ];
+        let publish = command("publish", 0.0, &args);
+        writer
+            .write_message(&RtmpMessage::command(5, stream_id, &publish))
+            .await?;
+        let status = read_response(&mut reader, 0.0).await?;
+        let code = { status.get(3).and_then(|x| x.get("code")) }
+            .and_then(|x| x.as_str())
+            .unwrap_or_default();
+        if code != "NetStream.Publish.Start" {
+            bail!("publish failed: {}", code);
+        }
+
+        // NOTE: 読み捨てないとサーバーからの送信が詰まる
+        let reader_handle = spawn(async move {
+            while let Ok(message) = reader.read_message().await {
+                trace!("rtmp server message: {}", message.type_id);
+            }
+        });
+        Ok(Self {
+            writer,
+            stream_id,
+            reader_handle,
+        })
+    }
+
+    pub async fn send(&mut self, message: &RtmpMessage) -> Result<()> {
+        let csid = match message.type_id {
+            MSG_AUDIO => 4,
+            MSG_VIDEO => 6,
+            _ => 5,
+        };
+        let message = RtmpMessage {
+            csid,
+            stream_id: self.stream_id,
+            ..message.clone()
+        };
+        self.writer.write_message(&message).await
+    }
+}
+
+#[async_trait]
+impl RelayDestination for RtmpPublisher {
+    async fn send(&mut self, message: &RtmpMessage) -> Result<()> {
+        RtmpPublisher::send(self, message).await
+    }
+}
+
+impl Drop for RtmpPublisher {
+    fn drop(&mut self) {
+        self.reader_handle.abort();
+    }
+}
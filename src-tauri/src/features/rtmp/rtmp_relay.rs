@@ -0,0 +1,85 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use log::{error, trace};
+use tokio::{sync::watch, time::timeout};
+
+use crate::core::entities::stream_properties::StreamProperties;
+
+use super::{
+    chunk::RtmpMessage,
+    flv_recorder::FlvRecorder,
+    metadata::stream_properties,
+    publish_session::{Interrupted, PublishSession},
+    rtmp_fan_out::RtmpFanOut,
+    stream_analyzer::StreamAnalyzer,
+};
+
+/// PeerCast への中継先
+#[async_trait]
+pub trait RelayDestination: Send {
+    async fn send(&mut self, message: &RtmpMessage) -> anyhow::Result<()>;
+}
+
+/// 中継が正常に終わった理由
+#[derive(Debug)]
+pub enum RelayEnd {
+    /// 配信元が配信を終了した
+    Unpublished,
+    /// 配信元から一定時間データが届かなかった
+    Stalled,
+    /// 他の配信元に切り替えるために中断された
+    Replaced,
+}
+
+/// 配信元のメッセージを配信先に中継する。どちらかが切断されるまで返らない
+///
+/// 配信元が正常に配信を終了した場合、stall_timeout の間にデータが届かなかった場合と、
+/// 他の配信元に切り替えるために中断された場合は Ok を返す。
+/// 録画に失敗した場合は recorder を None にして中継を続ける。
+/// onMetaData を受け取ると properties に送る
+pub async fn relay(
+    session: &mut PublishSession,
+    publisher: &mut dyn RelayDestination,
+    analyzer: &std::sync::Mutex<StreamAnalyzer>,
+    recorder: &mut Option<FlvRecorder>,
+    fan_out: &RtmpFanOut,
+    properties: &watch::Sender<Option<StreamProperties>>,
+    stall_timeout: Option<Duration>,
+) -> anyhow::Result<RelayEnd> {
+    trace!("Start relaying");
+    let result = async {
+        loop {
+            let message = match stall_timeout {
+                None => session.read_media().await,
+                Some(stall_timeout) => match timeout(stall_timeout, session.read_media()).await {
+                    Ok(message) => message,
+                    Err(_) => return Ok(RelayEnd::Stalled),
+                },
+            };
+            let message = match message {
+                Err(err) if err.is::<Interrupted>() => return Ok(RelayEnd::Replaced),
+                message => message?,
+            };
+            let Some(message) = message else {
+                return Ok(RelayEnd::Unpublished);
+            };
+            analyzer.lock().unwrap().add(&message);
+            if let Some(stream_properties) = stream_properties(&message) {
+                trace!("{:?}", stream_properties);
+                properties.send_replace(Some(stream_properties));
+            }
+            if let Some(flv_recorder) = recorder
+                && let Err(err) = flv_recorder.write(&message).await
+            {
+                error!("{}: {}", flv_recorder.path(), err);
+                *recorder = None;
+            }
+            fan_out.send(&message);
+            publisher.send(&message).await?;
+        }
+    }
+    .await;
+    trace!("End relaying {:?}", result);
+    result
+}
@@ -24,26 +24,26 @@ impl RtmpServer {
         yp_configs: &[YPConfig],
         settings: &Settings,
     ) -> anyhow::Result<bool> {
-        let hosts = [
-            &settings.yellow_pages_settings.ipv4.host,
-            &settings.yellow_pages_settings.ipv6.host,
-        ];
-        let has_yp = hosts.iter().any(|host| !host.is_empty());
-        let agreed_all_terms = hosts
-            .into_iter()
-            .flat_map(|host| yp_configs.iter().find(|config| &config.host == host))
+        let yp_settings = &settings.yellow_pages_settings;
+        let has_yp = yp_settings.hosts().any(|host| !host.is_empty());
+        let agreed_all_terms = yp_settings
+            .hosts()
+            .flat_map(|host| yp_configs.iter().find(|config| config.host == host))
             .map(|config| &config.terms_url)
-            .all(|terms_url| {
-                settings
-                    .yellow_pages_settings
-                    .agreed_terms
-                    .contains_key(terms_url)
-            });
+            .all(|terms_url| yp_settings.agreed_terms.contains_key(terms_url));
 
         let should_listen = has_yp && agreed_all_terms;
+        self.rtmp_listener
+            .set_stream_key(&settings.general_settings.rtmp_stream_key);
+        self.rtmp_listener
+            .set_concurrent_publisher_policy(settings.general_settings.concurrent_publisher_policy);
         if should_listen {
+            let general_settings = &settings.general_settings;
             self.rtmp_listener
-                .update_listen_port(settings.general_settings.rtmp_listen_port)
+                .update_listen_addrs(
+                    &general_settings.rtmp_listen_hosts,
+                    general_settings.rtmp_listen_port,
+                )
                 .await?;
             Ok(true)
         } else {
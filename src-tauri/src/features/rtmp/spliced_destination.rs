@@ -0,0 +1,111 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use log::warn;
+use tokio::sync::Notify;
+
+use super::{
+    chunk::{MSG_VIDEO, RtmpMessage},
+    rtmp_fan_out::StreamHeaders,
+    rtmp_relay::RelayDestination,
+};
+
+#[derive(Default)]
+struct Shared {
+    /// 次のメッセージから使う中継先
+    replacement: Option<Box<dyn RelayDestination>>,
+    /// 中継先への送信に失敗し、差し替えを待っている場合はそのエラー
+    error: Option<String>,
+}
+
+/// 中継中に中継先を差し替えられる RelayDestination。
+/// 中継先への送信に失敗しても中継を止めず、Splicer に知らせて差し替えられるまでメッセージを捨てる
+pub struct SplicedDestination {
+    destination: Option<Box<dyn RelayDestination>>,
+    shared: Arc<Mutex<Shared>>,
+    broken: Arc<Notify>,
+    headers: StreamHeaders,
+    waiting_keyframe: bool,
+}
+
+/// SplicedDestination の中継先を差し替える
+#[derive(Clone)]
+pub struct Splicer {
+    shared: Arc<Mutex<Shared>>,
+    broken: Arc<Notify>,
+}
+
+pub fn splice(destination: Box<dyn RelayDestination>) -> (SplicedDestination, Splicer) {
+    let shared: Arc<Mutex<Shared>> = Default::default();
+    let broken: Arc<Notify> = Default::default();
+    let spliced_destination = SplicedDestination {
+        destination: Some(destination),
+        shared: shared.clone(),
+        broken: broken.clone(),
+        headers: Default::default(),
+        waiting_keyframe: false,
+    };
+    (spliced_destination, Splicer { shared, broken })
+}
+
+impl SplicedDestination {
+    async fn send_to_destination(&mut self, message: &RtmpMessage) {
+        let Some(destination) = &mut self.destination else {
+            return;
+        };
+        if let Err(err) = destination.send(message).await {
+            warn!("{}", err);
+            self.destination = None;
+            self.shared.lock().unwrap().error = Some(err.to_string());
+            self.broken.notify_one();
+        }
+    }
+}
+
+#[async_trait]
+impl RelayDestination for SplicedDestination {
+    async fn send(&mut self, message: &RtmpMessage) -> Result<()> {
+        let replacement = self.shared.lock().unwrap().replacement.take();
+        if let Some(replacement) = replacement {
+            self.destination = Some(replacement);
+            // 途中から接続した配信先と同じく、ヘッダーを送ってからキーフレームを待つ
+            self.waiting_keyframe = true;
+            for header in self.headers.messages() {
+                self.send_to_destination(&header).await;
+            }
+        }
+        self.headers.update(message);
+        if message.type_id == MSG_VIDEO && !message.is_sequence_header() {
+            if self.waiting_keyframe && !message.is_keyframe() {
+                return Ok(());
+            }
+            self.waiting_keyframe = false;
+        }
+        self.send_to_destination(message).await;
+        Ok(())
+    }
+}
+
+impl Splicer {
+    /// 中継先への送信に失敗し、差し替えを待っている
+    pub fn is_broken(&self) -> bool {
+        let shared = self.shared.lock().unwrap();
+        shared.error.is_some() && shared.replacement.is_none()
+    }
+
+    /// 中継先への送信に失敗するまで待ち、そのエラーを返す。
+    /// 失敗した後に初めて呼んだ場合もすぐに返る
+    pub async fn wait_for_break(&self) -> String {
+        self.broken.notified().await;
+        let shared = self.shared.lock().unwrap();
+        shared.error.clone().unwrap_or_default()
+    }
+
+    /// 次のメッセージから destination に中継する
+    pub fn splice(&self, destination: Box<dyn RelayDestination>) {
+        let mut shared = self.shared.lock().unwrap();
+        shared.replacement = Some(destination);
+        shared.error = None;
+    }
+}
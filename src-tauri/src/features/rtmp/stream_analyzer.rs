@@ -0,0 +1,59 @@
+use crate::core::entities::stream_stats::StreamStats;
+
+use super::{
+    bitrate_meter::BitrateMeter,
+    chunk::{MSG_AUDIO, MSG_VIDEO, RtmpMessage},
+};
+
+/// 配信元から受信したメッセージから受信ビットレートやキーフレーム間隔を調べる
+#[derive(Default)]
+pub struct StreamAnalyzer {
+    bitrate_meter: BitrateMeter,
+    last_keyframe_timestamp: Option<u32>,
+    /// 直前のキーフレームとその前のキーフレームの間隔 (ms)
+    keyframe_interval: Option<u32>,
+    last_video_timestamp: Option<u32>,
+    last_audio_timestamp: Option<u32>,
+}
+
+impl StreamAnalyzer {
+    pub fn add(&mut self, message: &RtmpMessage) {
+        self.bitrate_meter.add(message.payload.len());
+        match message.type_id {
+            MSG_VIDEO => {
+                self.last_video_timestamp = Some(message.timestamp);
+                if message.is_keyframe() && !message.is_sequence_header() {
+                    if let Some(last) = self.last_keyframe_timestamp {
+                        self.keyframe_interval = Some(message.timestamp.wrapping_sub(last));
+                    }
+                    self.last_keyframe_timestamp = Some(message.timestamp);
+                }
+            }
+            MSG_AUDIO => self.last_audio_timestamp = Some(message.timestamp),
+            _ => {}
+        }
+    }
+
+    /// kbps
+    pub fn kbps(&mut self) -> u32 {
+        self.bitrate_meter.kbps()
+    }
+
+    pub fn stats(&self) -> StreamStats {
+        // NOTE: キーフレームが届かなくなった場合にも気付けるよう、
+        //       直前のキーフレームからの経過時間も間隔とみなす
+        let elapsed = self
+            .last_keyframe_timestamp
+            .zip(self.last_video_timestamp)
+            .map(|(keyframe, video)| video.wrapping_sub(keyframe));
+        let keyframe_interval = self.keyframe_interval.max(elapsed);
+        let av_drift = self
+            .last_video_timestamp
+            .zip(self.last_audio_timestamp)
+            .map(|(video, audio)| video as i64 - audio as i64);
+        StreamStats {
+            keyframe_interval,
+            av_drift,
+        }
+    }
+}
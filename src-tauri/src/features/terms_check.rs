@@ -31,14 +31,11 @@ async fn expired_yp_terms<'a>(
     yp_configs: &'a [YPConfig],
     settings: &Settings,
 ) -> anyhow::Result<Vec<&'a str>> {
-    let hosts = [
-        &settings.yellow_pages_settings.ipv4.host,
-        &settings.yellow_pages_settings.ipv6.host,
-    ];
-    let yp_terms_urls = hosts
-        .into_iter()
+    let mut yp_terms_urls = settings
+        .yellow_pages_settings
+        .hosts()
         .filter(|host| !host.is_empty())
-        .map(|host| yp_configs.iter().find(|x| &x.host == host).unwrap())
+        .map(|host| yp_configs.iter().find(|x| x.host == host).unwrap())
         .filter(|yp_config| !yp_config.ignore_terms_check)
         .map(|yp_config| {
             (
@@ -47,6 +44,9 @@ async fn expired_yp_terms<'a>(
             )
         })
         .collect::<Vec<_>>();
+    // NOTE: 同じ規約の YP を複数使っている場合に何度も取得しない
+    yp_terms_urls.sort();
+    yp_terms_urls.dedup();
 
     let mut terms_hashes = Vec::new();
     for (yp_terms_url, yp_terms_selector) in yp_terms_urls {
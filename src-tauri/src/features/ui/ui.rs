@@ -6,15 +6,23 @@ use std::{
 use async_trait::async_trait;
 use log::{error, warn};
 
-use crate::core::{
-    entities::{
-        contact_status::ContactStatus,
-        settings::{
-            ChannelSettings, GeneralSettings, OtherSettings, Settings, YellowPagesSettings,
+use crate::{
+    core::{
+        entities::{
+            broadcast_state::BroadcastState,
+            contact_status::ContactStatus,
+            peercast_stats::PeerCastStats,
+            settings::{
+                ChannelSettings, GeneralSettings, OtherSettings, Settings, YellowPagesSettings,
+            },
+            yp_config::YPConfig,
         },
-        yp_config::YPConfig,
+        utils::{dialog::show_dialog, failure::Failure},
+    },
+    features::{
+        peercast::{broadcast_preview::BroadcastPreview, port_discovery::DiscoveredPorts},
+        yp_index::YPDirectory,
     },
-    utils::{dialog::show_dialog, failure::Failure},
 };
 
 use super::window::{Window, WindowDelegate};
@@ -22,6 +30,14 @@ use super::window::{Window, WindowDelegate};
 #[async_trait]
 pub trait UiDelegate {
     async fn initial_data(&self) -> (Vec<YPConfig>, Settings, ContactStatus);
+    /// 画面が通知を受け取れるようになってから呼ばれる
+    async fn check_peercast(&self);
+    /// 現在の設定の PeerCast の種類とホストで、PeerCast のポート番号を探す
+    async fn discover_peercast_ports(&self) -> DiscoveredPorts;
+    /// 設定されている全ての YP のチャンネル一覧
+    async fn fetch_yp_directories(&self) -> Vec<YPDirectory>;
+    /// PeerCast に接続せずに、配信を開始した時に送る内容を確認する
+    async fn preview_broadcast(&self) -> BroadcastPreview;
     async fn on_change_general_settings(&self, general_settings: GeneralSettings);
     async fn on_change_yellow_pages_settings(&self, yellow_pages_settings: YellowPagesSettings);
     async fn on_change_channel_settings(&self, channel_settings: ChannelSettings);
@@ -32,19 +48,21 @@ type DynSendSyncUiDelegate = dyn Send + Sync + UiDelegate;
 
 #[derive(Default)]
 pub struct Title {
-    pub rtmp: String,
+    pub broadcast_state: BroadcastState,
     pub channel_name: String,
 }
 
 impl Display for Title {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        let listening_icon = match self.rtmp.as_str() {
-            "idle" => '×',
-            "listening" => '○',
-            "streaming" => '●',
-            _ => unreachable!(),
+        let state_icon = match self.broadcast_state {
+            BroadcastState::Idle => '×',
+            BroadcastState::Listening => '○',
+            BroadcastState::Starting | BroadcastState::Stopping => '◎',
+            BroadcastState::Streaming => '●',
+            BroadcastState::Reconnecting => '◌',
+            BroadcastState::Error => '！',
         };
-        write!(f, "{}{}", listening_icon, self.channel_name)
+        write!(f, "{}{}", state_icon, self.channel_name)
     }
 }
 
@@ -77,6 +95,22 @@ impl WindowDelegate for UiWindowDelegate {
         self.ui_delegate().initial_data().await
     }
 
+    async fn check_peercast(&self) {
+        self.ui_delegate().check_peercast().await
+    }
+
+    async fn discover_peercast_ports(&self) -> DiscoveredPorts {
+        self.ui_delegate().discover_peercast_ports().await
+    }
+
+    async fn fetch_yp_directories(&self) -> Vec<YPDirectory> {
+        self.ui_delegate().fetch_yp_directories().await
+    }
+
+    async fn preview_broadcast(&self) -> BroadcastPreview {
+        self.ui_delegate().preview_broadcast().await
+    }
+
     async fn on_change_general_settings(&self, general_settings: GeneralSettings) {
         {
             let channel_name = &general_settings.channel_name[0];
@@ -135,12 +169,12 @@ impl Ui {
 
     pub fn prepare_ui(
         &self,
-        initial_rtmp: String,
+        initial_broadcast_state: BroadcastState,
         initial_channel_name: String,
         delegate: Weak<DynSendSyncUiDelegate>,
     ) {
         *self.title.lock().unwrap() = Title {
-            rtmp: initial_rtmp,
+            broadcast_state: initial_broadcast_state,
             channel_name: initial_channel_name,
         };
         *self.ui_window_delegate.lock().unwrap() = Some(Arc::new(UiWindowDelegate {
@@ -180,16 +214,26 @@ impl Ui {
         self.notify_error("YP の利用規約が変更されました。再度確認してください。");
     }
 
-    pub fn set_rtmp(&self, rtmp: String) {
-        self.window.set_rtmp(&rtmp);
+    pub fn set_broadcast_state(&self, broadcast_state: BroadcastState) {
+        self.window.set_broadcast_state(broadcast_state);
         let title_status = {
             let title = &mut self.title.lock().unwrap();
-            title.rtmp = rtmp;
+            title.broadcast_state = broadcast_state;
             title.to_string()
         };
         self.window.set_title_status(title_status);
     }
 
+    /// 受信ビットレート (kbps)。配信していない場合は None
+    pub fn set_ingest_bitrate(&self, ingest_bitrate: Option<u32>) {
+        self.window.set_ingest_bitrate(ingest_bitrate);
+    }
+
+    /// 配信していない場合は None
+    pub fn set_peercast_stats(&self, peercast_stats: Option<PeerCastStats>) {
+        self.window.set_peercast_stats(peercast_stats);
+    }
+
     fn notify_warn(&self, message: &str) {
         self.window.notify("warn", message)
     }
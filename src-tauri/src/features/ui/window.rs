@@ -8,14 +8,22 @@ use tauri::{
     ipc::{InvokeBody, InvokeMessage},
 };
 
-use crate::core::{
-    app::App,
-    entities::{
-        contact_status::ContactStatus,
-        settings::{
-            ChannelSettings, GeneralSettings, OtherSettings, Settings, YellowPagesSettings,
+use crate::{
+    core::{
+        app::App,
+        entities::{
+            broadcast_state::BroadcastState,
+            contact_status::ContactStatus,
+            peercast_stats::PeerCastStats,
+            settings::{
+                ChannelSettings, GeneralSettings, OtherSettings, Settings, YellowPagesSettings,
+            },
+            yp_config::YPConfig,
         },
-        yp_config::YPConfig,
+    },
+    features::{
+        peercast::{broadcast_preview::BroadcastPreview, port_discovery::DiscoveredPorts},
+        yp_index::YPDirectory,
     },
 };
 
@@ -36,6 +44,10 @@ use crate::core::{
 pub trait WindowDelegate {
     fn on_build_app(&self);
     async fn initial_data(&self) -> (Vec<YPConfig>, Settings, ContactStatus);
+    async fn check_peercast(&self);
+    async fn discover_peercast_ports(&self) -> DiscoveredPorts;
+    async fn fetch_yp_directories(&self) -> Vec<YPDirectory>;
+    async fn preview_broadcast(&self) -> BroadcastPreview;
     async fn on_change_general_settings(&self, general_settings: GeneralSettings);
     async fn on_change_yellow_pages_settings(&self, yellow_pages_settings: YellowPagesSettings);
     async fn on_change_channel_settings(&self, channel_settings: ChannelSettings);
@@ -133,8 +145,16 @@ impl Window {
         );
     }
 
-    pub fn set_rtmp(&self, rtmp: &str) {
-        self.send("status", json!({ "rtmp": rtmp }));
+    pub fn set_broadcast_state(&self, broadcast_state: BroadcastState) {
+        self.send("status", json!({ "rtmp": broadcast_state }));
+    }
+
+    pub fn set_ingest_bitrate(&self, ingest_bitrate: Option<u32>) {
+        self.send("status", json!({ "ingestBitrate": ingest_bitrate }));
+    }
+
+    pub fn set_peercast_stats(&self, peercast_stats: Option<PeerCastStats>) {
+        self.send("status", json!({ "peerCastStats": peercast_stats }));
     }
 
     pub fn set_title_status(&self, title_status: String) {
@@ -0,0 +1,221 @@
+use std::{
+    fmt::{self, Display},
+    time::Duration,
+};
+
+use anyhow::Result;
+use futures::future::join_all;
+use serde::Serialize;
+use tokio::time::{Instant, sleep};
+
+use crate::core::entities::{settings::YellowPagesSettings, yp_config::YPConfig};
+
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// index.txt の 1 行
+#[derive(Clone, Debug, Default, Eq, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YPChannel {
+    pub name: String,
+    pub id: String,
+    /// 配信者の IP アドレスとポート番号
+    pub tip: String,
+    pub contact_url: String,
+    pub genre: String,
+    pub desc: String,
+    /// 隠している場合は -1
+    pub listeners: i32,
+    pub relays: i32,
+    pub bitrate: u32,
+    pub content_type: String,
+    pub comment: String,
+}
+
+fn unescape(text: &str) -> String {
+    text.replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&#039;", "'")
+        .replace("&amp;", "&")
+}
+
+/// <> 区切りのチャンネル一覧。項目が足りない行は読み飛ばす
+pub fn parse_index_txt(text: &str) -> Vec<YPChannel> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<_> = line.split("<>").map(unescape).collect();
+            if fields.len() < 10 {
+                return None;
+            }
+            Some(YPChannel {
+                name: fields[0].clone(),
+                id: fields[1].clone(),
+                tip: fields[2].clone(),
+                contact_url: fields[3].clone(),
+                genre: fields[4].clone(),
+                desc: fields[5].clone(),
+                listeners: fields[6].parse().unwrap_or(-1),
+                relays: fields[7].parse().unwrap_or(-1),
+                bitrate: fields[8].parse().unwrap_or(0),
+                content_type: fields[9].clone(),
+                comment: fields.get(17).cloned().unwrap_or_default(),
+            })
+        })
+        .collect()
+}
+
+pub async fn fetch_index_txt(url: &str) -> Result<Vec<YPChannel>> {
+    let client = reqwest::Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()?;
+    let bytes = client
+        .get(url)
+        .send()
+        .await?
+        .error_for_status()?
+        .bytes()
+        .await?;
+    Ok(parse_index_txt(&String::from_utf8_lossy(&bytes)))
+}
+
+/// YP のチャンネル一覧と、その中での自分のチャンネルの位置
+#[derive(Clone, Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct YPDirectory {
+    pub yp_name: String,
+    pub host: String,
+    pub channels: Vec<YPChannel>,
+    /// 自分のチャンネルの channels での位置
+    pub own_channel_index: Option<usize>,
+    /// ジャンルに自分のジャンルを含むチャンネルの数
+    pub same_genre_count: usize,
+    /// 取得できなかった理由
+    pub error: Option<String>,
+}
+
+/// チャンネル一覧から探す自分のチャンネル
+pub struct OwnChannel<'a> {
+    /// 配信中のチャンネルの ID。配信していない場合は空
+    pub ids: Vec<&'a str>,
+    pub name: &'a str,
+    pub genre: &'a str,
+}
+
+impl OwnChannel<'_> {
+    /// 配信していない場合は名前で探す
+    fn is_own(&self, channel: &YPChannel) -> bool {
+        if self.ids.is_empty() {
+            !self.name.is_empty() && channel.name == self.name
+        } else {
+            self.ids
+                .iter()
+                .any(|id| channel.id.eq_ignore_ascii_case(id))
+        }
+    }
+}
+
+async fn fetch_directory(yp_config: &YPConfig, own_channel: &OwnChannel<'_>) -> YPDirectory {
+    let mut directory = YPDirectory {
+        yp_name: yp_config.name.clone(),
+        host: yp_config.host.clone(),
+        channels: Vec::new(),
+        own_channel_index: None,
+        same_genre_count: 0,
+        error: None,
+    };
+    let Some(index_txt_url) = &yp_config.index_txt_url else {
+        directory.error = Some("index.txt の URL が分かりません。".to_owned());
+        return directory;
+    };
+    match fetch_index_txt(index_txt_url).await {
+        Ok(channels) => {
+            directory.own_channel_index = channels.iter().position(|x| own_channel.is_own(x));
+            if !own_channel.genre.is_empty() {
+                directory.same_genre_count = channels
+                    .iter()
+                    .filter(|x| x.genre.contains(own_channel.genre))
+                    .count();
+            }
+            directory.channels = channels;
+        }
+        Err(err) => {
+            log::warn!("{}: {}", index_txt_url, err);
+            directory.error = Some(format!("index.txt を取得できません。({})", err));
+        }
+    }
+    directory
+}
+
+/// 掲載先に設定した YP のチャンネル一覧を並行して取得する
+pub async fn fetch_directories(
+    yp_configs: &[YPConfig],
+    yp_settings: &YellowPagesSettings,
+    own_channel: &OwnChannel<'_>,
+) -> Vec<YPDirectory> {
+    join_all(
+        yp_configs
+            .iter()
+            .filter(|yp_config| yp_settings.find(&yp_config.host).is_some())
+            .map(|yp_config| fetch_directory(yp_config, own_channel)),
+    )
+    .await
+}
+
+/// YP に載るはずのチャンネル
+#[derive(Clone, Debug)]
+pub struct ExpectedListing {
+    pub yp_name: String,
+    pub index_txt_url: String,
+    pub channel_id: String,
+    /// YPConfig::genre_full_text の値
+    pub genre: String,
+}
+
+/// 掲載を確認できなかった理由
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum ListingError {
+    /// index.txt を一度も取得できなかった
+    Unreachable,
+    NotListed,
+    WrongGenre(String),
+}
+
+impl Display for ListingError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ListingError::Unreachable => write!(f, "index.txt を取得できません"),
+            ListingError::NotListed => write!(f, "チャンネルが見つかりません"),
+            ListingError::WrongGenre(genre) => write!(f, "ジャンルが「{}」になっています", genre),
+        }
+    }
+}
+
+/// チャンネルが期待したジャンルで載るまで index.txt を取得し直す。
+/// timeout までに載らなければ最後に確認した状態を返す
+pub async fn wait_for_listing(
+    listing: &ExpectedListing,
+    poll_interval: Duration,
+    timeout: Duration,
+) -> Result<(), ListingError> {
+    let deadline = Instant::now() + timeout;
+    let mut last_error = ListingError::Unreachable;
+    loop {
+        match fetch_index_txt(&listing.index_txt_url).await {
+            Ok(channels) => {
+                let channel = channels
+                    .into_iter()
+                    .find(|x| x.id.eq_ignore_ascii_case(&listing.channel_id));
+                match channel {
+                    Some(channel) if channel.genre == listing.genre => return Ok(()),
+                    Some(channel) => last_error = ListingError::WrongGenre(channel.genre),
+                    None => last_error = ListingError::NotListed,
+                }
+            }
+            Err(err) => log::warn!("{}: {}", listing.index_txt_url, err),
+        }
+        if Instant::now() + poll_interval > deadline {
+            return Err(last_error);
+        }
+        sleep(poll_interval).await;
+    }
+}
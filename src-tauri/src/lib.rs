@@ -37,6 +37,18 @@ fn invoke_handler(
             "initial_data" => {
                 resolver.resolve(delegate.initial_data().await);
             }
+            "check_peercast" => {
+                delegate.check_peercast().await;
+            }
+            "discover_peercast_ports" => {
+                resolver.resolve(delegate.discover_peercast_ports().await);
+            }
+            "fetch_yp_directories" => {
+                resolver.resolve(delegate.fetch_yp_directories().await);
+            }
+            "preview_broadcast" => {
+                resolver.resolve(delegate.preview_broadcast().await);
+            }
             "put_settings" => {
                 if let Some(settings) = message.get_from_payload("generalSettings") {
                     delegate.on_change_general_settings(settings).await;
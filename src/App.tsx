@@ -6,6 +6,7 @@ import { useEffect, useState } from 'react';
 import Notification from './components/molecules/Notification';
 import TabContainer, { TabContent } from './components/molecules/TabContainer';
 import ChannelSettings from './components/ChannelSettings';
+import BroadcastPreview from './components/BroadcastPreview';
 import GeneralSettings from './components/GeneralSettings';
 import YellowPagesSettings from './components/YellowPagesSettings';
 import Settings from './entities/Settings';
@@ -13,17 +14,20 @@ import YPConfig from './entities/YPConfig';
 import listenWrapped from './utils/listenWrapped';
 import Status from './entities/Status';
 import OtherSettings from './components/OtherSettings';
+import YPChannels from './components/YPChannels';
 
 const initialStatus: Status = {
   rtmp: 'idle',
+  ingestBitrate: null,
+  peerCastStats: null,
 };
 
 function initialTab(ypConfigs: readonly YPConfig[], defaultSettings: Settings) {
   const doneGeneral = defaultSettings.generalSettings.channelName[0].length > 0;
 
   const ypSettings = defaultSettings.yellowPagesSettings;
-  const usingHosts = [ypSettings.ipv4, ypSettings.ipv6]
-    .map((x) => x.host)
+  const usingHosts = ypSettings.announcements
+    .map((x) => x.settings.host)
     .filter((host) => host.length > 0);
   const doneYP =
     usingHosts.length > 0 &&
@@ -52,7 +56,7 @@ export default function App(props: {
   >([]);
   const [settings, setSettings] = useState(props.defaultSettings);
   const [contactStatus, setContactStatus] = useState(props.contactStatus);
-  const [_status, setStatus] = useState(initialStatus);
+  const [status, setStatus] = useState(initialStatus);
   const [platform, setPlatform] = useState('');
   const [version, setVersion] = useState('');
 
@@ -87,10 +91,13 @@ export default function App(props: {
         setContactStatus(ev.payload);
       }
     );
-    const statusPromise = listen('status', (ev: Event<Status>) => {
-      setStatus(ev.payload);
+    const statusPromise = listen('status', (ev: Event<Partial<Status>>) => {
+      setStatus((status) => ({ ...status, ...ev.payload }));
     });
 
+    // NOTE: 起動時の警告を受け取れるよう、通知を待ち受けてから PeerCast を確認する
+    notifyPromise.then(() => invoke('check_peercast'));
+
     // TODO: 配信中に終了しようとした時に確認ダイアログを出す
     // TODO: パラメーターの編集中に終了した時に内容を保存する
 
@@ -145,11 +152,17 @@ export default function App(props: {
           <ChannelSettings
             settings={settings.channelSettings}
             contactStatus={contactStatus}
+            ingestBitrate={status.ingestBitrate}
+            peerCastStats={status.peerCastStats}
             onChange={(channelSettings) => {
               invoke('put_settings', { channelSettings });
               setSettings((settings) => ({ ...settings, channelSettings }));
             }}
           />
+          <BroadcastPreview />
+        </TabContent>
+        <TabContent label="YP のチャンネル">
+          <YPChannels />
         </TabContent>
         <TabContent label="その他">
           <OtherSettings
@@ -0,0 +1,122 @@
+import { css } from '@emotion/css';
+import { DefaultButton, MessageBar, MessageBarType } from '@fluentui/react';
+import { invoke } from '@tauri-apps/api/core';
+import { useState } from 'react';
+import BroadcastPreviewEntity, {
+  ChannelPreview,
+} from '../entities/BroadcastPreview';
+import { NetworkType } from '../entities/Settings';
+
+function hasProblems(preview: BroadcastPreviewEntity) {
+  return (
+    preview.problems.length > 0 ||
+    preview.channels.some((x) => x.problems.length > 0)
+  );
+}
+
+function Problems(props: { problems: readonly string[] }) {
+  return (
+    <>
+      {props.problems.map((problem, i) => (
+        <MessageBar key={i} messageBarType={MessageBarType.warning}>
+          {problem}
+        </MessageBar>
+      ))}
+    </>
+  );
+}
+
+function Channel(props: { channel: ChannelPreview }) {
+  const { channel } = props;
+  const rows: readonly [string, string][] = [
+    ['チャンネル名', channel.name],
+    ['ジャンル', channel.genre],
+    ['詳細', channel.desc],
+    ['コメント', channel.comment],
+    ['コンタクト URL', channel.contactUrl],
+    ['形式', channel.mimeType],
+    [
+      '配信元',
+      channel.sourceUri == null
+        ? channel.sourceType
+        : `${channel.sourceType} (${channel.sourceUri})`,
+    ],
+  ];
+  return (
+    <section>
+      <h3>
+        {channel.ypName} (
+        {channel.networkType === NetworkType.ipv6 ? 'IPv6' : 'IPv4'})
+      </h3>
+      <Problems problems={channel.problems} />
+      <table
+        className={css`
+          border-spacing: 8px 2px;
+        `}
+      >
+        <tbody>
+          {rows.map(([label, value]) => (
+            <tr key={label}>
+              <th
+                className={css`
+                  text-align: left;
+                  font-weight: normal;
+                  color: #605e5c;
+                  white-space: nowrap;
+                `}
+              >
+                {label}
+              </th>
+              <td
+                className={css`
+                  word-break: break-all;
+                `}
+              >
+                {value}
+              </td>
+            </tr>
+          ))}
+        </tbody>
+      </table>
+    </section>
+  );
+}
+
+export default function BroadcastPreview() {
+  const [preview, setPreview] = useState<BroadcastPreviewEntity | null>(null);
+
+  return (
+    <div
+      className={css`
+        display: flex;
+        flex-direction: column;
+        gap: 8px;
+        margin-top: 16px;
+      `}
+    >
+      <div>
+        <DefaultButton
+          iconProps={{ iconName: 'preview' }}
+          onClick={async () => {
+            setPreview(await invoke('preview_broadcast'));
+          }}
+        >
+          送信内容を確認
+        </DefaultButton>
+      </div>
+      {preview == null ? null : (
+        <>
+          <Problems problems={preview.problems} />
+          {hasProblems(preview) ? null : (
+            <MessageBar messageBarType={MessageBarType.success}>
+              問題は見つかりませんでした。
+            </MessageBar>
+          )}
+          {preview.channels.map((channel, i) => (
+            <Channel key={i} channel={channel} />
+          ))}
+        </>
+      )}
+    </div>
+  );
+}
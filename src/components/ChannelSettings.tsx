@@ -11,7 +11,9 @@ import { DOMAttributes, useRef, useState } from 'react';
 import {
   ChannelContent,
   ChannelSettings as Settings,
+  NetworkType,
 } from '../entities/Settings';
+import { AnnouncedChannelStats, PeerCastStats } from '../entities/Status';
 import HistoryTextField from './molecules/HistoryTextField';
 import ShowMore from './molecules/ShowMore';
 
@@ -78,6 +80,109 @@ function History(props: {
   );
 }
 
+type TrackKey = 'trackName' | 'trackCreator' | 'trackAlbum' | 'trackUrl';
+
+function TrackView(props: {
+  settings: Settings;
+  onChange(value: Settings): void;
+}): JSX.Element {
+  const [nowPlayingPath, setNowPlayingPath] = useState(
+    props.settings.nowPlayingPath,
+  );
+  const fields: readonly { key: TrackKey; label: string }[] = [
+    { key: 'trackName', label: 'トラック名' },
+    { key: 'trackCreator', label: 'アーティスト' },
+    { key: 'trackAlbum', label: 'アルバム' },
+    { key: 'trackUrl', label: 'トラック URL' },
+  ];
+  return (
+    <div
+      className={css`
+        display: flex;
+        flex-direction: column;
+        gap: 8px;
+      `}
+    >
+      <div
+        className={css`
+          display: grid;
+          grid-template-columns: 1fr 1fr;
+          gap: 8px;
+        `}
+      >
+        {fields.map(({ key, label }) => (
+          <HistoryTextField
+            key={key}
+            label={label}
+            value={props.settings[key][0]}
+            history={props.settings[key]
+              .slice(1)
+              .filter((x) => x.trim() !== '')}
+            onChange={(value) =>
+              props.onChange({
+                ...props.settings,
+                [key]: [value, ...props.settings[key].slice(1)],
+              })
+            }
+          />
+        ))}
+      </div>
+      <TextField
+        label="再生中の曲のファイル"
+        description="音楽プレイヤーなどが書き出すテキストファイルを指定すると、1 行目から順にトラック名、アーティスト、アルバム、トラック URL として読み込み、配信中に書き換わるたびに反映します。"
+        value={nowPlayingPath}
+        onChange={(_e, newValue) => setNowPlayingPath(newValue!!)}
+        onBlur={() => {
+          if (nowPlayingPath === props.settings.nowPlayingPath) {
+            return;
+          }
+          props.onChange({ ...props.settings, nowPlayingPath });
+        }}
+      />
+    </div>
+  );
+}
+
+function formatUptime(seconds: number): string {
+  const h = Math.floor(seconds / 3600);
+  const m = Math.floor((seconds % 3600) / 60);
+  const s = seconds % 60;
+  return `${h}:${String(m).padStart(2, '0')}:${String(s).padStart(2, '0')}`;
+}
+
+function formatChannelStats({
+  host,
+  networkType,
+  stats,
+}: AnnouncedChannelStats) {
+  const network = networkType === NetworkType.ipv6 ? 'IPv6' : 'IPv4';
+  const label = `${host} (${network})`;
+  return stats == null
+    ? null
+    : `${label}: リスナー ${stats.listeners} / リレー ${stats.relays} / ホスト ${stats.hosts}`;
+}
+
+function PeerCastStatsView(props: { stats: PeerCastStats }): JSX.Element {
+  const { stats } = props;
+  return (
+    <>
+      {stats.channels
+        .map(formatChannelStats)
+        .filter((x) => x != null)
+        .map((x) => (
+          <Text key={x} variant="small">
+            {x}
+          </Text>
+        ))}
+      <Text variant="small">
+        上り: {stats.bandwidthOut} kbps / 下り: {stats.bandwidthIn} kbps /
+        接続数: {stats.totalConnections} / PeerCast 稼働時間:{' '}
+        {formatUptime(stats.uptime)}
+      </Text>
+    </>
+  );
+}
+
 function ChannelContentView(props: {
   history: readonly ChannelContent[];
   channelContent: ChannelContent;
@@ -140,6 +245,8 @@ function ChannelContentView(props: {
 export default function ChannelSettings(props: {
   settings: Settings;
   contactStatus: { title: string; resCount: number };
+  ingestBitrate: number | null;
+  peerCastStats: PeerCastStats | null;
   onChange(value: Settings): void;
 }) {
   const [channelContent, setChannelContent] = useState({
@@ -244,6 +351,18 @@ export default function ChannelSettings(props: {
           </div>
         </div>
       </div>
+      <TrackView settings={props.settings} onChange={props.onChange} />
+      <Text variant="small">
+        概要とコメントの {'{width}'} {'{height}'} {'{fps}'} {'{video_codec}'}{' '}
+        {'{audio_codec}'} {'{bitrate}'} {'{encoder}'}{' '}
+        は配信ソフトから送られてきた値に置き換わります
+      </Text>
+      {props.ingestBitrate == null ? null : (
+        <Text variant="small">受信ビットレート: {props.ingestBitrate} kbps</Text>
+      )}
+      {props.peerCastStats == null ? null : (
+        <PeerCastStatsView stats={props.peerCastStats} />
+      )}
     </div>
   );
 }
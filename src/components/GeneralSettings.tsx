@@ -1,9 +1,11 @@
 import { css } from '@emotion/css';
 import {
   DefaultButton,
+  Dropdown,
   IRefObject,
   ITextFieldProps,
   ITooltipHost,
+  ResponsiveMode,
   Separator,
   SpinButton,
   Text,
@@ -13,7 +15,13 @@ import {
 } from '@fluentui/react';
 import { invoke } from '@tauri-apps/api/core';
 import { useRef, useState } from 'react';
-import { GeneralSettings as Settings } from '../entities/Settings';
+import {
+  ConcurrentPublisherPolicy,
+  ContentType,
+  GeneralSettings as Settings,
+  PeerCastType,
+} from '../entities/Settings';
+import DiscoveredPorts from '../entities/DiscoveredPorts';
 import HistoryTextField from './molecules/HistoryTextField';
 
 function CopyableTextField(props: ITextFieldProps) {
@@ -66,6 +74,72 @@ function CopyableTextField(props: ITextFieldProps) {
   );
 }
 
+function PeerCastTcpPort(props: {
+  value: number;
+  onChange(newValue: number): void;
+}): JSX.Element {
+  const [value, setValue] = useState(props.value);
+  return (
+    <div
+      className={css`
+        display: flex;
+        align-items: end;
+      `}
+    >
+      <SpinButton
+        label="データ通信 TCP ポート番号"
+        style={{ width: 0 }}
+        styles={{ input: { textAlign: 'end', textOverflow: 'clip' } }}
+        className={css`
+          z-index: 1;
+          width: auto;
+          > div:nth-of-type(2)::after {
+            border-top-right-radius: 0;
+            border-bottom-right-radius: 0;
+          }
+        `}
+        max={65535}
+        min={1}
+        value={String(value)}
+        onChange={(_e, newValue) => {
+          setValue(Number(newValue));
+          props.onChange(Number(newValue));
+        }}
+      />
+      <DefaultButton
+        className={css`
+          border-left: none;
+          border-top-left-radius: 0;
+          border-bottom-left-radius: 0;
+          min-width: 0;
+          padding: 0 8px;
+        `}
+        menuProps={{
+          items: [
+            {
+              key: 'discover',
+              text: 'PeerCast を探す',
+              onClick: () => {
+                (async () => {
+                  const discovered: DiscoveredPorts = await invoke(
+                    'discover_peercast_ports'
+                  );
+                  if (discovered.peerCastPort == null) {
+                    return;
+                  }
+                  setValue(discovered.peerCastPort);
+                  props.onChange(discovered.peerCastPort);
+                })();
+              },
+            },
+          ],
+        }}
+        iconProps={{ iconName: 'search' }}
+      />
+    </div>
+  );
+}
+
 function PeerCastRtmpTcpPort(props: {
   value: number;
   onChange(newValue: number): void;
@@ -119,6 +193,22 @@ function PeerCastRtmpTcpPort(props: {
                 })();
               },
             },
+            {
+              key: 'peerCastStationSettings',
+              text: 'PeerCastStation で以前に使ったポートを探す',
+              onClick: () => {
+                (async () => {
+                  const discovered: DiscoveredPorts = await invoke(
+                    'discover_peercast_ports'
+                  );
+                  if (discovered.peerCastRtmpPort == null) {
+                    return;
+                  }
+                  setValue(discovered.peerCastRtmpPort);
+                  props.onChange(discovered.peerCastRtmpPort);
+                })();
+              },
+            },
           ],
         }}
         iconProps={{ iconName: 'search' }}
@@ -134,7 +224,7 @@ export default function GeneralSettings(props: {
   const serverForObs = `rtmp://localhost${props.settings.rtmpListenPort === 1935
     ? ''
     : `:${props.settings.rtmpListenPort}`
-    }/live/livestream`;
+    }/live${props.settings.rtmpStreamKey === '' ? '/livestream' : ''}`;
 
   return (
     <div
@@ -176,32 +266,247 @@ export default function GeneralSettings(props: {
           })
         }
       />
+      <TextField
+        label="RTMP 待ち受けアドレス"
+        description="カンマ区切りで複数指定できます。他の PC から接続する場合は 0.0.0.0 (IPv4) や :: (IPv6) を指定します。"
+        defaultValue={props.settings.rtmpListenHosts.join(', ')}
+        onBlur={(e) => {
+          const rtmpListenHosts = e.target.value
+            .split(',')
+            .map((x) => x.trim())
+            .filter((x) => x !== '');
+          if (
+            rtmpListenHosts.join(',') === props.settings.rtmpListenHosts.join(',')
+          ) {
+            return;
+          }
+          props.onChange({ ...props.settings, rtmpListenHosts });
+        }}
+      />
+      <TextField
+        label="ストリームキー (空欄で認証なし)"
+        type="password"
+        canRevealPassword
+        defaultValue={props.settings.rtmpStreamKey}
+        onBlur={(e) => {
+          const rtmpStreamKey = e.target.value;
+          if (rtmpStreamKey === props.settings.rtmpStreamKey) {
+            return;
+          }
+          props.onChange({ ...props.settings, rtmpStreamKey });
+        }}
+      />
       <CopyableTextField
         label="OBS にカスタムサーバーとして設定する値"
         value={serverForObs}
       />
-      <Separator />
-      <Text variant="large">PeerCastStation</Text>
+      {props.settings.rtmpStreamKey === '' ? null : (
+        <CopyableTextField
+          label="OBS にストリームキーとして設定する値"
+          type="password"
+          value={props.settings.rtmpStreamKey}
+        />
+      )}
+      <Dropdown
+        label="配信中に別の配信元が接続してきた場合"
+        selectedKey={props.settings.concurrentPublisherPolicy}
+        responsiveMode={ResponsiveMode.large}
+        options={[
+          { key: ConcurrentPublisherPolicy.reject, text: '拒否する' },
+          { key: ConcurrentPublisherPolicy.replace, text: '切り替える' },
+          {
+            key: ConcurrentPublisherPolicy.queue,
+            text: '配信が終わるまで待たせる',
+          },
+        ]}
+        onChange={(_e, option) => {
+          const concurrentPublisherPolicy =
+            option?.key as ConcurrentPublisherPolicy;
+          if (
+            concurrentPublisherPolicy ===
+            props.settings.concurrentPublisherPolicy
+          ) {
+            return;
+          }
+          props.onChange({ ...props.settings, concurrentPublisherPolicy });
+        }}
+      />
       <SpinButton
-        label="データ通信 TCP ポート番号"
+        label="再接続を待つ秒数 (0 で待たない)"
         style={{ width: 0 }}
         styles={{ input: { textAlign: 'end', textOverflow: 'clip' } }}
-        max={65535}
-        min={1}
-        value={String(props.settings.peerCastPort)}
+        max={600}
+        min={0}
+        value={String(props.settings.reconnectGraceSeconds)}
         onChange={(_ev, newValue) =>
           props.onChange({
             ...props.settings,
-            peerCastPort: Number(newValue),
+            reconnectGraceSeconds: Number(newValue),
           })
         }
       />
+      <SpinButton
+        label="データが途切れてから配信を終了するまでの秒数 (0 で終了しない)"
+        style={{ width: 0 }}
+        styles={{ input: { textAlign: 'end', textOverflow: 'clip' } }}
+        max={600}
+        min={0}
+        value={String(props.settings.stallTimeoutSeconds)}
+        onChange={(_ev, newValue) =>
+          props.onChange({
+            ...props.settings,
+            stallTimeoutSeconds: Number(newValue),
+          })
+        }
+      />
+      <SpinButton
+        label="警告するキーフレーム間隔の秒数 (0 で警告しない)"
+        style={{ width: 0 }}
+        styles={{ input: { textAlign: 'end', textOverflow: 'clip' } }}
+        max={60}
+        min={0}
+        value={String(props.settings.maxKeyframeIntervalSeconds)}
+        onChange={(_ev, newValue) =>
+          props.onChange({
+            ...props.settings,
+            maxKeyframeIntervalSeconds: Number(newValue),
+          })
+        }
+      />
+      <SpinButton
+        label="チャンネルの状態を確かめる間隔の秒数 (0 で確かめない)"
+        style={{ width: 0 }}
+        styles={{ input: { textAlign: 'end', textOverflow: 'clip' } }}
+        max={600}
+        min={0}
+        value={String(props.settings.channelHealthCheckSeconds)}
+        onChange={(_ev, newValue) =>
+          props.onChange({
+            ...props.settings,
+            channelHealthCheckSeconds: Number(newValue),
+          })
+        }
+      />
+      <TextField
+        label="追加の中継先"
+        description="PeerCast の他に中継する RTMP の URL (rtmp://サーバー/アプリ/ストリームキー) を 1 行に 1 つずつ入力します。"
+        multiline
+        autoAdjustHeight
+        defaultValue={props.settings.rtmpExtraDestinations.join('\n')}
+        onBlur={(e) => {
+          const rtmpExtraDestinations = e.target.value
+            .split('\n')
+            .map((x) => x.trim())
+            .filter((x) => x !== '');
+          if (
+            rtmpExtraDestinations.join('\n') ===
+            props.settings.rtmpExtraDestinations.join('\n')
+          ) {
+            return;
+          }
+          props.onChange({ ...props.settings, rtmpExtraDestinations });
+        }}
+      />
+      <Separator />
+      <Text variant="large">PeerCast</Text>
+      <Dropdown
+        label="種類"
+        selectedKey={props.settings.peerCastType}
+        responsiveMode={ResponsiveMode.large}
+        options={[
+          { key: PeerCastType.peerCastStation, text: 'PeerCastStation' },
+          {
+            key: PeerCastType.peerCastOriginal,
+            text: 'PeerCast (VP, IM など)',
+          },
+        ]}
+        onChange={(_e, option) => {
+          const peerCastType = option?.key as PeerCastType;
+          if (peerCastType === props.settings.peerCastType) {
+            return;
+          }
+          props.onChange({ ...props.settings, peerCastType });
+        }}
+      />
+      <TextField
+        label="ホスト"
+        placeholder="localhost"
+        defaultValue={props.settings.peerCastHost}
+        onBlur={(e) => {
+          const peerCastHost = e.target.value;
+          if (peerCastHost === props.settings.peerCastHost) {
+            return;
+          }
+          props.onChange({ ...props.settings, peerCastHost });
+        }}
+      />
+      <PeerCastTcpPort
+        value={props.settings.peerCastPort}
+        onChange={(peerCastPort) =>
+          props.onChange({ ...props.settings, peerCastPort })
+        }
+      />
       <PeerCastRtmpTcpPort
         value={props.settings.peerCastRtmpPort}
         onChange={(peerCastRtmpPort) =>
           props.onChange({ ...props.settings, peerCastRtmpPort })
         }
       />
+      <Dropdown
+        label="チャンネルの形式"
+        selectedKey={props.settings.contentType}
+        responsiveMode={ResponsiveMode.large}
+        options={[
+          { key: ContentType.flv, text: 'FLV' },
+          // RTMP の配信元は FLV しか運べない
+          { key: ContentType.mkv, text: 'MKV (未対応)', disabled: true },
+          { key: ContentType.webM, text: 'WebM (未対応)', disabled: true },
+        ]}
+        onChange={(_e, option) => {
+          const contentType = option?.key as ContentType;
+          if (contentType === props.settings.contentType) {
+            return;
+          }
+          props.onChange({ ...props.settings, contentType });
+        }}
+      />
+      <TextField
+        label="BASIC 認証のユーザー名 (空欄で認証なし)"
+        defaultValue={props.settings.peerCastUser}
+        onBlur={(e) => {
+          const peerCastUser = e.target.value;
+          if (peerCastUser === props.settings.peerCastUser) {
+            return;
+          }
+          props.onChange({ ...props.settings, peerCastUser });
+        }}
+      />
+      <TextField
+        label="BASIC 認証のパスワード"
+        type="password"
+        canRevealPassword
+        defaultValue={props.settings.peerCastPassword}
+        onBlur={(e) => {
+          const peerCastPassword = e.target.value;
+          if (peerCastPassword === props.settings.peerCastPassword) {
+            return;
+          }
+          props.onChange({ ...props.settings, peerCastPassword });
+        }}
+      />
+      <TextField
+        label="認証トークン (空欄で使用しない)"
+        type="password"
+        canRevealPassword
+        defaultValue={props.settings.peerCastAuthToken}
+        onBlur={(e) => {
+          const peerCastAuthToken = e.target.value;
+          if (peerCastAuthToken === props.settings.peerCastAuthToken) {
+            return;
+          }
+          props.onChange({ ...props.settings, peerCastAuthToken });
+        }}
+      />
     </div>
   );
 }
@@ -6,6 +6,71 @@ import { useState } from 'react';
 import { LiteralUnion } from 'type-fest';
 import { OtherSettings as Settings } from '../entities/Settings';
 
+function DirectoryTextField(props: {
+  label: string;
+  disabled: boolean;
+  hideOpenDirectoryDialog: boolean;
+  value: string;
+  onChange(value: string): void;
+}) {
+  const [value, setValue] = useState(props.value);
+
+  return (
+    <div
+      className={css`
+        display: flex;
+        align-items: end;
+      `}
+    >
+      <TextField
+        className={css`
+          flex-grow: 1;
+        `}
+        styles={{
+          fieldGroup: props.hideOpenDirectoryDialog
+            ? {}
+            : {
+              borderRight: 'none',
+              borderTopRightRadius: 0,
+              borderBottomRightRadius: 0,
+            },
+        }}
+        label={props.label}
+        disabled={props.disabled}
+        value={value}
+        onChange={(_ev, newValue) => setValue(newValue!!)}
+        onBlur={() => {
+          if (value === props.value) {
+            return;
+          }
+          props.onChange(value);
+        }}
+      />
+      <DefaultButton
+        className={css`
+          border-top-left-radius: 0;
+          border-bottom-left-radius: 0;
+          min-width: 0;
+          ${props.hideOpenDirectoryDialog ? 'display: none;' : ''}
+        `}
+        iconProps={{ iconName: 'folderopen' }}
+        disabled={props.disabled}
+        onClick={async () => {
+          const newValue = (await dialog.open({
+            defaultPath: props.value,
+            directory: true,
+          })) as string | null;
+          if (newValue == null || newValue === value) {
+            return;
+          }
+          setValue(newValue);
+          props.onChange(newValue);
+        }}
+      />
+    </div>
+  );
+}
+
 export default function OtherSettings(props: {
   // WTF: mac だとディレクトリ選択ダイアログが正常に動作しない
   platform: LiteralUnion<
@@ -25,9 +90,6 @@ export default function OtherSettings(props: {
   settings: Settings;
   onChange(value: Settings): void;
 }) {
-  const [logOutputDirectory, setLogOutputDirectory] = useState(
-    props.settings.logOutputDirectory
-  );
   const hideOpenDirectoryDialog = props.platform === 'darwin';
 
   return (
@@ -45,64 +107,37 @@ export default function OtherSettings(props: {
           props.onChange({ ...props.settings, logEnabled: logEnabled === true })
         }
       />
-      <div
+      <DirectoryTextField
+        label="ログの出力先"
+        disabled={!props.settings.logEnabled}
+        hideOpenDirectoryDialog={hideOpenDirectoryDialog}
+        value={props.settings.logOutputDirectory}
+        onChange={(logOutputDirectory) =>
+          props.onChange({ ...props.settings, logOutputDirectory })
+        }
+      />
+      <Checkbox
+        label="配信を録画する (FLV)"
         className={css`
-          display: flex;
-          align-items: end;
+          margin-top: 16px;
         `}
-      >
-        <TextField
-          className={css`
-            flex-grow: 1;
-          `}
-          styles={{
-            fieldGroup: hideOpenDirectoryDialog
-              ? {}
-              : {
-                borderRight: 'none',
-                borderTopRightRadius: 0,
-                borderBottomRightRadius: 0,
-              },
-          }}
-          label="ログの出力先"
-          disabled={!props.settings.logEnabled}
-          value={logOutputDirectory}
-          onChange={(_ev, newValue) => setLogOutputDirectory(newValue!!)}
-          onBlur={() => {
-            if (logOutputDirectory === props.settings.logOutputDirectory) {
-              return;
-            }
-            props.onChange({ ...props.settings, logOutputDirectory });
-          }}
-        />
-        <DefaultButton
-          className={css`
-            border-top-left-radius: 0;
-            border-bottom-left-radius: 0;
-            min-width: 0;
-            ${hideOpenDirectoryDialog ? 'display: none;' : ''}
-          `}
-          iconProps={{ iconName: 'folderopen' }}
-          disabled={!props.settings.logEnabled}
-          onClick={async () => {
-            const newLogOutputDirectory = (await dialog.open({
-              defaultPath: props.settings.logOutputDirectory,
-              directory: true,
-            })) as string | null;
-            if (
-              newLogOutputDirectory == null ||
-              newLogOutputDirectory === logOutputDirectory
-            ) {
-              return;
-            }
-            setLogOutputDirectory(newLogOutputDirectory);
-            props.onChange({
-              ...props.settings,
-              logOutputDirectory: newLogOutputDirectory,
-            });
-          }}
-        />
-      </div>
+        checked={props.settings.recordingEnabled}
+        onChange={(_ev, recordingEnabled) =>
+          props.onChange({
+            ...props.settings,
+            recordingEnabled: recordingEnabled === true,
+          })
+        }
+      />
+      <DirectoryTextField
+        label="録画の出力先"
+        disabled={!props.settings.recordingEnabled}
+        hideOpenDirectoryDialog={hideOpenDirectoryDialog}
+        value={props.settings.recordingOutputDirectory}
+        onChange={(recordingOutputDirectory) =>
+          props.onChange({ ...props.settings, recordingOutputDirectory })
+        }
+      />
       <div
         className={css`
           margin-top: 4ex;
@@ -0,0 +1,136 @@
+import { css } from '@emotion/css';
+import { DefaultButton, Spinner, TextField } from '@fluentui/react';
+import { invoke } from '@tauri-apps/api/core';
+import { useState } from 'react';
+import YPDirectory, { YPChannel } from '../entities/YPDirectory';
+
+function matches(channel: YPChannel, query: string) {
+  return [channel.name, channel.genre, channel.desc, channel.comment].some(
+    (x) => x.toLowerCase().includes(query)
+  );
+}
+
+function ChannelRow(props: { channel: YPChannel; own: boolean }) {
+  const { channel } = props;
+  return (
+    <li
+      className={css`
+        padding: 4px 8px;
+        ${props.own ? 'background-color: #fff4ce; font-weight: bold;' : ''}
+      `}
+    >
+      <div>
+        {channel.name}
+        {props.own ? ' (自分のチャンネル)' : ''}
+      </div>
+      <div
+        className={css`
+          font-size: smaller;
+          color: #605e5c;
+        `}
+      >
+        {[channel.genre, channel.desc, channel.comment]
+          .filter((x) => x.length > 0)
+          .join(' - ')}
+        {' '}[{channel.listeners < 0 ? '-' : channel.listeners}/
+        {channel.relays < 0 ? '-' : channel.relays}] {channel.bitrate}kbps{' '}
+        {channel.contentType}
+      </div>
+    </li>
+  );
+}
+
+function Directory(props: { directory: YPDirectory; query: string }) {
+  const { directory } = props;
+  const channels = directory.channels
+    .map((channel, i) => ({ channel, own: i === directory.ownChannelIndex }))
+    .filter(({ channel }) => matches(channel, props.query));
+  return (
+    <section>
+      <h3>
+        {directory.ypName} ({directory.channels.length} チャンネル)
+      </h3>
+      {directory.error != null ? (
+        <div>{directory.error}</div>
+      ) : (
+        <>
+          <div>
+            {directory.ownChannelIndex == null
+              ? '自分のチャンネルは載っていません。'
+              : `自分のチャンネルは ${directory.ownChannelIndex + 1} 番目に載っています。`}
+            同じジャンルのチャンネル: {directory.sameGenreCount}
+          </div>
+          <ul
+            className={css`
+              list-style: none;
+              margin: 0;
+              padding: 0;
+            `}
+          >
+            {channels.map(({ channel, own }) => (
+              <ChannelRow key={channel.id} channel={channel} own={own} />
+            ))}
+          </ul>
+        </>
+      )}
+    </section>
+  );
+}
+
+export default function YPChannels() {
+  const [directories, setDirectories] = useState<readonly YPDirectory[]>([]);
+  const [loading, setLoading] = useState(false);
+  const [query, setQuery] = useState('');
+
+  return (
+    <div
+      className={css`
+        display: flex;
+        flex-direction: column;
+        gap: 8px;
+      `}
+    >
+      <div
+        className={css`
+          display: flex;
+          align-items: end;
+          gap: 8px;
+        `}
+      >
+        <TextField
+          className={css`
+            flex-grow: 1;
+          `}
+          label="検索"
+          value={query}
+          onChange={(_ev, newValue) => setQuery(newValue!!)}
+        />
+        <DefaultButton
+          iconProps={{ iconName: 'refresh' }}
+          disabled={loading}
+          onClick={async () => {
+            setLoading(true);
+            try {
+              const directories: YPDirectory[] = await invoke(
+                'fetch_yp_directories'
+              );
+              setDirectories(directories);
+            } finally {
+              setLoading(false);
+            }
+          }}
+        >
+          チャンネル一覧を取得
+        </DefaultButton>
+      </div>
+      {loading ? <Spinner /> : null}
+      {directories.map((directory) => (
+        <Directory
+          key={directory.host}
+          directory={directory}
+          query={query.toLowerCase()}
+        />
+      ))}
+    </div>
+  );
+}
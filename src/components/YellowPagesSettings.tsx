@@ -1,8 +1,11 @@
 import { css } from '@emotion/css';
+import { DefaultButton } from '@fluentui/react';
 import { invoke } from '@tauri-apps/api/core';
 import { open } from '@tauri-apps/plugin-shell';
 import {
   EachYellowPagesSettings,
+  NetworkType,
+  YPAnnouncement,
   YellowPagesSettings as Settings,
 } from '../entities/Settings';
 import YPConfig from '../entities/YPConfig';
@@ -11,22 +14,32 @@ import YellowPagesPrefixBuilder from './molecules/YellowPagesPrefixBuilder';
 import YPConflictWarning from './molecules/YPConflictWarning';
 import YPSelect from './molecules/YPSelect';
 
+const emptyEachYellowPagesSettings: EachYellowPagesSettings = {
+  host: '',
+  hideListeners: false,
+  namespace: '',
+  portBandwidthCheck: 0,
+  noLog: false,
+  icon: '',
+};
+
 function EachYellowPagesSettingsView(props: {
   protocol: 'IPv4' | 'IPv6';
   ypConfigs: readonly YPConfig[];
-  usedHostForIPV4?: string;
+  usedHosts: readonly string[];
   agreedTerms: { [url: string]: string };
   readedTerms: { [url: string]: string };
   value: EachYellowPagesSettings;
   onReadTerms: (termsURL: string, hash: string) => void;
   onChange(value: EachYellowPagesSettings): void;
   onChangeAgreeTerms(url: string, hash: string | null): void;
+  onRemove(): void;
 }): JSX.Element {
   const currentYPConfig = props.ypConfigs.find(
     (x) => x.host === props.value.host
   );
   const conflict =
-    currentYPConfig != null && currentYPConfig.host === props.usedHostForIPV4;
+    currentYPConfig != null && props.usedHosts.includes(currentYPConfig.host);
   const readedTerms: string | null =
     props.readedTerms[currentYPConfig?.termsURL ?? ''] ?? null;
   return (
@@ -48,7 +61,7 @@ function EachYellowPagesSettingsView(props: {
         <YPSelect
           label={`${props.protocol} 掲載 YP`}
           ypConfigs={props.ypConfigs}
-          usedHostForIPV4={props.usedHostForIPV4}
+          usedHosts={props.usedHosts}
           conflict={conflict}
           host={props.value.host}
           onChange={(host) => {
@@ -101,6 +114,14 @@ function EachYellowPagesSettingsView(props: {
           onChange={props.onChange}
         />
       </div>
+      <div>
+        <DefaultButton
+          iconProps={{ iconName: 'delete' }}
+          onClick={props.onRemove}
+        >
+          この掲載先を削除
+        </DefaultButton>
+      </div>
     </div>
   );
 }
@@ -116,50 +137,94 @@ export default function YellowPagesSettings(props: {
     props.onChange({ ...props.settings, ...newSettings });
   };
 
+  const announcements = props.settings.announcements;
+  const updateAnnouncements = (announcements: readonly YPAnnouncement[]) => {
+    update({ announcements });
+  };
+  const add = (networkType: NetworkType) => {
+    updateAnnouncements([
+      ...announcements,
+      { networkType, settings: { ...emptyEachYellowPagesSettings } },
+    ]);
+  };
+
   return (
     <div
       className={css`
         display: flex;
-        gap: 64px 16px;
-        flex-wrap: wrap;
+        flex-direction: column;
+        gap: 16px;
       `}
     >
-      {[
-        {
-          protocol: 'IPv4' as 'IPv4' | 'IPv6',
-          ypConfigs: props.ypConfigs,
-          usedHostForIPV4: undefined,
-          value: props.settings.ipv4,
-          onChange: (ipv4: EachYellowPagesSettings) => update({ ipv4 }),
-        },
-        {
-          protocol: 'IPv6' as 'IPv4' | 'IPv6',
-          ypConfigs: props.ypConfigs.filter((x) => x.supportIpv6),
-          usedHostForIPV4: props.settings.ipv4.host,
-          value: props.settings.ipv6,
-          onChange: (ipv6: EachYellowPagesSettings) => update({ ipv6 }),
-        },
-      ].map(({ protocol, ypConfigs, usedHostForIPV4, value, onChange }) => (
-        <EachYellowPagesSettingsView
-          key={protocol}
-          protocol={protocol}
-          ypConfigs={ypConfigs}
-          usedHostForIPV4={usedHostForIPV4}
-          value={value}
-          onChange={onChange}
-          agreedTerms={props.settings.agreedTerms}
-          readedTerms={props.readedTerms}
-          onReadTerms={props.onReadTerms}
-          onChangeAgreeTerms={(url, hash) =>
-            update({
-              agreedTerms: {
-                ...props.settings.agreedTerms,
-                [url]: hash ?? undefined!!,
-              },
-            })
-          }
-        />
-      ))}
+      <div
+        className={css`
+          display: flex;
+          gap: 64px 16px;
+          flex-wrap: wrap;
+        `}
+      >
+        {announcements.map((announcement, i) => (
+          <EachYellowPagesSettingsView
+            // NOTE: 名前空間などの入力欄は defaultValue なので、掲載先を削除した時に作り直す
+            key={`${i}-${announcement.settings.host}`}
+            protocol={
+              announcement.networkType === NetworkType.ipv6 ? 'IPv6' : 'IPv4'
+            }
+            ypConfigs={
+              announcement.networkType === NetworkType.ipv6
+                ? props.ypConfigs.filter((x) => x.supportIpv6)
+                : props.ypConfigs
+            }
+            usedHosts={announcements
+              .filter((_, j) => j !== i)
+              .map((x) => x.settings.host)}
+            value={announcement.settings}
+            onChange={(settings: EachYellowPagesSettings) =>
+              updateAnnouncements(
+                announcements.map((x, j) => (j !== i ? x : { ...x, settings }))
+              )
+            }
+            onRemove={() =>
+              updateAnnouncements(announcements.filter((_, j) => j !== i))
+            }
+            agreedTerms={props.settings.agreedTerms}
+            readedTerms={props.readedTerms}
+            onReadTerms={props.onReadTerms}
+            onChangeAgreeTerms={(url, hash) =>
+              update({
+                agreedTerms: {
+                  ...props.settings.agreedTerms,
+                  [url]: hash ?? undefined!!,
+                },
+              })
+            }
+          />
+        ))}
+      </div>
+      {announcements.length === 0 ? null : (
+        <div>
+          先頭の掲載先のチャンネルが配信を受け取り、他の掲載先のチャンネルはそれを中継します。
+        </div>
+      )}
+      <div
+        className={css`
+          display: flex;
+          gap: 8px;
+        `}
+      >
+        <DefaultButton
+          iconProps={{ iconName: 'add' }}
+          onClick={() => add(NetworkType.ipv4)}
+        >
+          IPv4 の掲載先を追加
+        </DefaultButton>
+        <DefaultButton
+          iconProps={{ iconName: 'add' }}
+          onClick={() => add(NetworkType.ipv6)}
+        >
+          IPv6 の掲載先を追加
+        </DefaultButton>
+      </div>
     </div>
   );
 }
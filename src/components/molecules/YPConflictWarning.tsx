@@ -13,7 +13,7 @@ export default function YPConflictWarning(): JSX.Element {
         z-index: 1;
       `}
     >
-      IPv4 と同じ YP を指定すると
+      他の掲載先と同じ YP を指定すると
       <span
         className={css`
           white-space: nowrap;
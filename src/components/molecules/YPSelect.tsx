@@ -6,7 +6,8 @@ export default function YPSelect(props: {
   label: string;
   ypConfigs: readonly YPConfig[];
   host: string;
-  usedHostForIPV4?: string;
+  /** 他の掲載先で使っているホスト */
+  usedHosts: readonly string[];
   conflict: boolean;
   onChange(host: string): void;
 }): JSX.Element {
@@ -36,7 +37,7 @@ export default function YPSelect(props: {
           key={item!!.key}
           className={css`
             button {
-              color: ${item!!.data.host !== props.usedHostForIPV4
+              color: ${!props.usedHosts.includes(item!!.data.host)
               ? 'initial'
               : '#ff2800'};
             }
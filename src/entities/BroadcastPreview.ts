@@ -0,0 +1,26 @@
+import { NetworkType } from './Settings';
+
+/** 配信を開始した時に 1 つの YP のチャンネルに送る内容 */
+export interface ChannelPreview {
+  host: string;
+  ypName: string;
+  networkType: NetworkType;
+  name: string;
+  /** YP の接頭辞を付けたジャンル */
+  genre: string;
+  desc: string;
+  comment: string;
+  contactUrl: string;
+  mimeType: string;
+  sourceType: string;
+  /** 配信開始時に決まる場合は null */
+  sourceUri: string | null;
+  problems: readonly string[];
+}
+
+/** PeerCast に接続せずに作った、配信を開始した時に送る内容 */
+export default interface BroadcastPreview {
+  channels: readonly ChannelPreview[];
+  /** 特定の YP に関係しない問題 */
+  problems: readonly string[];
+}
@@ -0,0 +1,6 @@
+/** 見つからない場合は null */
+export default interface DiscoveredPorts {
+  peerCastPort: number | null;
+  /** PeerCastStation で以前に使った RTMP の配信元のポート番号のうち、空いているもの */
+  peerCastRtmpPort: number | null;
+}
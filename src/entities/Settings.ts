@@ -3,11 +3,42 @@ export enum PeerCastType {
   peerCastStation = 'PeerCastStation',
 }
 
+export enum ContentType {
+  flv = 'Flv',
+  mkv = 'Mkv',
+  webM = 'WebM',
+}
+
+export enum NetworkType {
+  ipv4 = 'Ipv4',
+  ipv6 = 'Ipv6',
+}
+
+export enum ConcurrentPublisherPolicy {
+  reject = 'Reject',
+  replace = 'Replace',
+  queue = 'Queue',
+}
+
 export interface GeneralSettings {
+  peerCastType: PeerCastType;
+  peerCastHost: string;
   peerCastPort: number;
+  peerCastUser: string;
+  peerCastPassword: string;
+  peerCastAuthToken: string;
   peerCastRtmpPort: number;
+  contentType: ContentType;
   channelName: readonly string[];
   rtmpListenPort: number;
+  rtmpListenHosts: readonly string[];
+  rtmpStreamKey: string;
+  concurrentPublisherPolicy: ConcurrentPublisherPolicy;
+  rtmpExtraDestinations: readonly string[];
+  reconnectGraceSeconds: number;
+  stallTimeoutSeconds: number;
+  maxKeyframeIntervalSeconds: number;
+  channelHealthCheckSeconds: number;
 }
 
 export interface EachYellowPagesSettings {
@@ -19,9 +50,14 @@ export interface EachYellowPagesSettings {
   icon: string;
 }
 
+export interface YPAnnouncement {
+  networkType: NetworkType;
+  settings: EachYellowPagesSettings;
+}
+
 export interface YellowPagesSettings {
-  ipv4: EachYellowPagesSettings;
-  ipv6: EachYellowPagesSettings;
+  /** 先頭の YP のチャンネルが配信元から受け取り、他の YP のチャンネルはそれを中継する */
+  announcements: readonly YPAnnouncement[];
   agreedTerms: { [url: string]: string };
 }
 
@@ -36,11 +72,18 @@ export interface ChannelSettings {
   desc: string;
   comment: readonly string[];
   contactUrl: readonly string[];
+  trackName: readonly string[];
+  trackCreator: readonly string[];
+  trackAlbum: readonly string[];
+  trackUrl: readonly string[];
+  nowPlayingPath: string;
 }
 
 export interface OtherSettings {
   logEnabled: boolean;
   logOutputDirectory: string;
+  recordingEnabled: boolean;
+  recordingOutputDirectory: string;
 }
 
 export default interface Settings {
@@ -1,3 +1,42 @@
+import { NetworkType } from './Settings';
+
+export interface ChannelStats {
+  listeners: number;
+  relays: number;
+  hosts: number;
+}
+
+/** 掲載先ごとのチャンネルの状態 */
+export interface AnnouncedChannelStats {
+  /** 掲載先の YP のホスト */
+  host: string;
+  networkType: NetworkType;
+  /** viewxml にチャンネルが無ければ null */
+  stats: ChannelStats | null;
+}
+
+export interface PeerCastStats {
+  /** PeerCast の起動からの秒数 */
+  uptime: number;
+  /** kbps */
+  bandwidthIn: number;
+  /** kbps */
+  bandwidthOut: number;
+  totalConnections: number;
+  /** 配信中のチャンネルの順 */
+  channels: readonly AnnouncedChannelStats[];
+}
+
 export default interface Status {
-  rtmp: 'idle' | 'listening' | 'streaming';
+  rtmp:
+    | 'idle'
+    | 'listening'
+    | 'starting'
+    | 'streaming'
+    | 'reconnecting'
+    | 'stopping'
+    | 'error';
+  /** 受信ビットレート (kbps) */
+  ingestBitrate: number | null;
+  peerCastStats: PeerCastStats | null;
 }
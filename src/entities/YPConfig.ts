@@ -14,4 +14,6 @@ export default interface YPConfig {
   supportIpv6: boolean;
   prefixHeader: string;
   supportedParams: readonly YPConfigParam[];
+  /** 掲載の確認に使う */
+  indexTxtURL: string | null;
 }
@@ -0,0 +1,27 @@
+/** index.txt の 1 行 */
+export interface YPChannel {
+  name: string;
+  id: string;
+  tip: string;
+  contactUrl: string;
+  genre: string;
+  desc: string;
+  /** 隠している場合は -1 */
+  listeners: number;
+  relays: number;
+  bitrate: number;
+  contentType: string;
+  comment: string;
+}
+
+/** YP のチャンネル一覧と、その中での自分のチャンネルの位置 */
+export default interface YPDirectory {
+  ypName: string;
+  host: string;
+  channels: readonly YPChannel[];
+  ownChannelIndex: number | null;
+  /** ジャンルに自分のジャンルを含むチャンネルの数 */
+  sameGenreCount: number;
+  /** 取得できなかった理由 */
+  error: string | null;
+}